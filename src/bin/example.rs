@@ -23,27 +23,33 @@ fn main() {
         }
         UpdateKind::Query(query) => {
           match query.text.as_str() {
-            "increase" => {
-              counter += 1;
-              if let Err(e) = bot
-                .send_message(
-                  update.chat_id,
-                  format!(
-                    "counter <bold>increased</bold>\n\ncurrent count: <code>{}",
-                    counter
-                  ),
-                )
-                .add_button(Button::new("Increase count", "increase"))
-                .execute()
-              {
-                eprintln!("{}", e);
+            "increase" => match query.consume(&bot) {
+              //the button of a message can only be used once
+              Ok(true) => {
+                counter += 1;
+                if let Err(e) = bot
+                  .send_message(
+                    update.chat_id,
+                    format!(
+                      "counter <bold>increased</bold>\n\ncurrent count: <code>{}",
+                      counter
+                    ),
+                  )
+                  .add_button(Button::new("Increase count", "increase"))
+                  .execute()
+                {
+                  eprintln!("{}", e);
+                }
               }
-            }
+              Ok(false) => {}
+              Err(e) => eprintln!("{}", e),
+            },
             _ => {
               eprintln!("received invalid query: {}", query.text);
             }
           };
         }
+        _ => {}
       },
       Err(e) => {
         eprintln!("{}", e);
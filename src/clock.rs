@@ -0,0 +1,102 @@
+use std::fmt::Debug;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::MUTEX_POISONED;
+
+///the source of time for the poll loop, e.g. to wait after network errors
+pub trait Clock: Debug + Send + Sync {
+  fn now(&self) -> Instant;
+
+  fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+///the wall clock, sleeping with [`tokio::time::sleep`]
+#[derive(Debug, Copy, Clone, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+  fn now(&self) -> Instant {
+    Instant::now()
+  }
+
+  fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    Box::pin(tokio::time::sleep(duration))
+  }
+}
+
+///a clock which only moves when told to, so timing can be tested without waiting. Sleeping
+///advances the clock right away and records the duration.
+#[derive(Debug)]
+pub struct ManualClock {
+  start: Instant,
+  state: Mutex<ManualClockState>,
+}
+
+#[derive(Debug, Default)]
+struct ManualClockState {
+  elapsed: Duration,
+  sleeps: Vec<Duration>,
+}
+
+impl ManualClock {
+  pub fn new() -> Self {
+    Self {
+      start: Instant::now(),
+      state: Mutex::new(ManualClockState::default()),
+    }
+  }
+
+  pub fn advance(&self, duration: Duration) {
+    self.state.lock().expect(MUTEX_POISONED).elapsed += duration;
+  }
+
+  ///the time passed since the clock has been created
+  pub fn elapsed(&self) -> Duration {
+    self.state.lock().expect(MUTEX_POISONED).elapsed
+  }
+
+  ///all durations slept so far, in order
+  pub fn sleeps(&self) -> Vec<Duration> {
+    self.state.lock().expect(MUTEX_POISONED).sleeps.clone()
+  }
+}
+
+impl Default for ManualClock {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Clock for ManualClock {
+  fn now(&self) -> Instant {
+    self.start + self.elapsed()
+  }
+
+  fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    let mut state = self.state.lock().expect(MUTEX_POISONED);
+    state.elapsed += duration;
+    state.sleeps.push(duration);
+    Box::pin(std::future::ready(()))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use crate::clock::{Clock, ManualClock};
+
+  #[test]
+  fn test_manual_clock() {
+    let clock = ManualClock::new();
+    let start = clock.now();
+    clock.advance(Duration::from_secs(1));
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(clock.sleep(Duration::from_secs(2)));
+    assert_eq!(Duration::from_secs(3), clock.now() - start);
+    assert_eq!(vec![Duration::from_secs(2)], clock.sleeps());
+  }
+}
@@ -1,6 +1,10 @@
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
+use teloxide::{ApiError, RequestError};
+
+use crate::MessageID;
+
 #[derive(Debug)]
 pub struct TelegramError {
   pub kind: ErrorKind,
@@ -63,14 +67,216 @@ pub enum ErrorKind {
    * The telegram api is unreachable
    */
   Network,
+  /**
+   * The bot token has been rejected by telegram
+   */
+  InvalidToken,
+  /**
+   * The bot lacks the administrator rights required for the request
+   */
+  MissingRights,
   MessageCharLimitReached,
   QueryByteLimitReached,
+  /**
+   * The chat doesn't exist or the bot isn't a member of it
+   */
+  ChatNotFound,
+  /**
+   * The user doesn't exist or has never been a member of the chat
+   */
+  UserNotFound,
+  /**
+   * The user blocked the bot or deleted their account, or the bot has been removed from the chat,
+   * so the chat can't be reached anymore
+   */
+  BotBlocked,
+  /**
+   * An edit didn't change the message, as its content and buttons are already the same. Loops
+   * editing a message to a computed state should treat this as success, see
+   * [`ignore_not_modified`].
+   */
+  MessageNotModified,
+  /**
+   * The message to delete doesn't exist (anymore), e.g. because a user or another job already
+   * deleted it. Cleanup jobs can treat this as success, see [`ignore_message_not_found`].
+   */
+  MessageNotFound,
+  /**
+   * The message with the given id has been sent, but pinning it failed, see
+   * [`crate::request::SendMessage::pin`]
+   */
+  PinFailed(MessageID),
   #[default]
   Other,
 }
 
+///treat an edit which failed with [`ErrorKind::MessageNotModified`] as success, as the message
+///already shows the desired content. Other errors are kept.
+pub fn ignore_not_modified(result: crate::Result<()>) -> crate::Result<()> {
+  match result {
+    Err(e) if e.kind == ErrorKind::MessageNotModified => Ok(()),
+    result => result,
+  }
+}
+
+///treat a deletion which failed with [`ErrorKind::MessageNotFound`] as success, as the message is
+///gone either way. Other errors are kept.
+pub fn ignore_message_not_found(result: crate::Result<()>) -> crate::Result<()> {
+  match result {
+    Err(e) if e.kind == ErrorKind::MessageNotFound => Ok(()),
+    result => result,
+  }
+}
+
+///whether a failed request may have reached telegram anyway, e.g. after a timeout, so repeating it
+///may repeat its effect. Only requests failing to connect are known not to have been received.
+pub(crate) fn is_ambiguous(error: &TelegramError) -> bool {
+  error.kind == ErrorKind::Network
+    && !matches!(
+      error.cause.as_ref().and_then(|cause| cause.downcast_ref::<RequestError>()),
+      Some(RequestError::Network(e)) if e.is_connect()
+    )
+}
+
+///whether answering a callback query failed because it has already been answered, e.g.
+///automatically, or is too old to be answered
+pub(crate) fn is_query_answered(error: &TelegramError) -> bool {
+  matches!(
+    error
+      .cause
+      .as_ref()
+      .and_then(|cause| cause.downcast_ref::<RequestError>()),
+    Some(RequestError::Api(ApiError::InvalidQueryId))
+  )
+}
+
+impl From<&RequestError> for ErrorKind {
+  fn from(error: &RequestError) -> Self {
+    match error {
+      RequestError::Network(_) => Self::Network,
+      RequestError::Api(error) => match error {
+        ApiError::InvalidToken => Self::InvalidToken,
+        ApiError::NotEnoughRightsToChangeChatPermissions
+        | ApiError::NotEnoughRightsToManagePins
+        | ApiError::NotEnoughRightsToPinMessage
+        | ApiError::NotEnoughRightsToRestrict
+        | ApiError::NotEnoughRightsToPostMessages => Self::MissingRights,
+        //rights teloxide has no variant for, e.g. to change the chat photo
+        ApiError::Unknown(message)
+          if message.contains("not enough rights") || message.contains("CHAT_ADMIN_REQUIRED") =>
+        {
+          Self::MissingRights
+        }
+        ApiError::MessageIsTooLong | ApiError::EditedMessageIsTooLong => {
+          Self::MessageCharLimitReached
+        }
+        ApiError::ChatNotFound => Self::ChatNotFound,
+        ApiError::UserNotFound => Self::UserNotFound,
+        ApiError::BotBlocked
+        | ApiError::BotKicked
+        | ApiError::BotKickedFromSupergroup
+        | ApiError::BotKickedFromChannel
+        | ApiError::UserDeactivated => Self::BotBlocked,
+        ApiError::MessageNotModified => Self::MessageNotModified,
+        ApiError::MessageToDeleteNotFound => Self::MessageNotFound,
+        _ => Self::Other,
+      },
+      _ => Self::Other,
+    }
+  }
+}
+
 impl Display for ErrorKind {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     write!(f, "{:?}", self)
   }
 }
+
+#[cfg(test)]
+mod test {
+  use teloxide::{ApiError, RequestError};
+
+  use crate::error::{
+    ignore_message_not_found, ignore_not_modified, is_ambiguous, is_query_answered, ErrorKind,
+    TelegramError,
+  };
+
+  #[test]
+  fn test_error_kind_from_request_error() {
+    let kind = |error: ApiError| ErrorKind::from(&RequestError::Api(error));
+    assert_eq!(ErrorKind::ChatNotFound, kind(ApiError::ChatNotFound));
+    assert_eq!(ErrorKind::BotBlocked, kind(ApiError::BotBlocked));
+    assert_eq!(ErrorKind::BotBlocked, kind(ApiError::UserDeactivated));
+    assert_eq!(ErrorKind::UserNotFound, kind(ApiError::UserNotFound));
+    assert_eq!(
+      ErrorKind::MessageNotModified,
+      kind(ApiError::MessageNotModified)
+    );
+    assert_eq!(
+      ErrorKind::MessageCharLimitReached,
+      kind(ApiError::MessageIsTooLong)
+    );
+    assert_eq!(
+      ErrorKind::MissingRights,
+      kind(ApiError::Unknown(
+        "Bad Request: not enough rights to change chat photo".to_owned()
+      ))
+    );
+    assert_eq!(
+      ErrorKind::MessageNotFound,
+      kind(ApiError::MessageToDeleteNotFound)
+    );
+    assert_eq!(ErrorKind::Other, kind(ApiError::MessageCantBeDeleted));
+
+    let error = RequestError::Api(ApiError::BotBlocked);
+    let error = TelegramError::new("failed to send message")
+      .of_kind(&error)
+      .with_cause(error);
+    assert_eq!(ErrorKind::BotBlocked, error.kind);
+    assert!(error.cause.is_some());
+  }
+
+  #[test]
+  fn test_ignore_message_not_found() {
+    let error = RequestError::Api(ApiError::MessageToDeleteNotFound);
+    let error = TelegramError::new("failed to delete message")
+      .of_kind(&error)
+      .with_cause(error);
+    assert!(ignore_message_not_found(Err(error)).is_ok());
+    let error = TelegramError::new("failed to delete message").of_kind(ErrorKind::Network);
+    assert!(ignore_message_not_found(Err(error)).is_err());
+  }
+
+  #[test]
+  fn test_is_ambiguous() {
+    assert!(is_ambiguous(
+      &TelegramError::new("timed out").of_kind(ErrorKind::Network)
+    ));
+    let error = RequestError::Api(ApiError::BotBlocked);
+    assert!(!is_ambiguous(
+      &TelegramError::new("failed to send message")
+        .of_kind(&error)
+        .with_cause(error)
+    ));
+  }
+
+  #[test]
+  fn test_is_query_answered() {
+    let error =
+      |e: RequestError| TelegramError::new("failed to answer callback query").with_cause(e);
+    assert!(is_query_answered(&error(RequestError::Api(
+      ApiError::InvalidQueryId
+    ))));
+    assert!(!is_query_answered(&error(RequestError::Api(
+      ApiError::BotBlocked
+    ))));
+  }
+
+  #[test]
+  fn test_ignore_not_modified() {
+    let error = |kind| Err(TelegramError::new("failed to edit message").of_kind(kind));
+    assert!(ignore_not_modified(error(ErrorKind::MessageNotModified)).is_ok());
+    assert!(ignore_not_modified(error(ErrorKind::ChatNotFound)).is_err());
+    assert!(ignore_not_modified(Ok(())).is_ok());
+  }
+}
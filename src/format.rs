@@ -1,20 +1,71 @@
+use std::collections::{BTreeSet, HashMap};
 use std::default::Default;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::str::FromStr;
 
 use enum_assoc::Assoc;
 use linkify::LinkFinder;
 
+use crate::error::TelegramError;
 use crate::VarArgs;
 
 pub fn parse(text: &str) -> Result<Vec<Component>, InvalidTagError> {
+  parse_with_depth(text).map(|(components, _)| components)
+}
+
+///statistics about a formatted text, see [`analyze`]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct FormatStats {
+  ///the amount of components, including empty ones
+  pub components: usize,
+  ///the names of all decorations used
+  pub decorations: BTreeSet<&'static str>,
+  ///the maximum amount of tags open at the same time
+  pub max_depth: usize,
+}
+
+///parse a text and collect statistics about its formatting, without rendering it
+pub fn analyze(text: &str) -> Result<FormatStats, InvalidTagError> {
+  let (components, max_depth) = parse_with_depth(text)?;
+  Ok(FormatStats {
+    components: components.len(),
+    decorations: components
+      .iter()
+      .flat_map(|component| component.style.tags())
+      .map(Decoration::name)
+      .collect(),
+    max_depth,
+  })
+}
+
+fn parse_with_depth(text: &str) -> Result<(Vec<Component>, usize), InvalidTagError> {
+  let (components, max_depth, mut errors) = parse_recovering(text);
+  if errors.is_empty() {
+    Ok((components, max_depth))
+  } else {
+    Err(errors.remove(0))
+  }
+}
+
+///check a text for invalid tags, returning all of them instead of only the first like [`parse`]
+pub fn validate(text: &str) -> Vec<InvalidTagError> {
+  parse_recovering(text).2
+}
+
+///parse a text, skipping invalid tags and collecting an error for each of them
+fn parse_recovering(text: &str) -> (Vec<Component>, usize, Vec<InvalidTagError>) {
+  let mut errors = vec![];
   let mut components: Vec<Component> = vec![];
   let mut open_tags: Vec<Decoration> = vec![];
+  let mut max_depth = 0;
 
   let mut token = String::new();
   let mut building_tag = false;
-  let mut iter = text.chars().peekable();
-  while let Some(c) = iter.next() {
+  let mut tag_position = 0;
+  let mut iter = text.chars().enumerate().peekable();
+  while let Some((position, c)) = iter.next() {
     match c {
       '<' if !building_tag => {
         if !token.is_empty() {
@@ -22,10 +73,18 @@ pub fn parse(text: &str) -> Result<Vec<Component>, InvalidTagError> {
           token = String::new();
         }
         building_tag = true;
+        tag_position = position;
       }
       '>' if building_tag => {
         building_tag = false;
-        let tag = create_tag(&token)?;
+        let tag = match create_tag(&token) {
+          Ok(tag) => tag,
+          Err(e) => {
+            errors.push(e.at(tag_position));
+            token = String::new();
+            continue;
+          }
+        };
         token = String::new();
         if tag.closing {
           if let Some((index, _)) = open_tags
@@ -37,15 +96,16 @@ pub fn parse(text: &str) -> Result<Vec<Component>, InvalidTagError> {
           }
         } else {
           open_tags.push(tag.decoration);
+          max_depth = max_depth.max(open_tags.len());
         }
       }
       '\\'
         if iter
           .peek()
-          .filter(|c| ['<', '>', '\\'].contains(c))
+          .filter(|(_, c)| ['<', '>', '\\'].contains(c))
           .is_some() =>
       {
-        token.push(iter.next().unwrap());
+        token.push(iter.next().unwrap().1);
       }
       _ => token.push(c),
     }
@@ -53,38 +113,42 @@ pub fn parse(text: &str) -> Result<Vec<Component>, InvalidTagError> {
   if !building_tag {
     components.push(create_component(&token, &open_tags));
   } else {
-    Err(InvalidTagError::new(format!(
-      "missing closing bracket after '{}'",
-      token
-    )))?;
+    errors.push(
+      InvalidTagError::new(format!("missing closing bracket after '{}'", token)).at(tag_position),
+    );
   }
 
-  Ok(components)
+  (components, max_depth, errors)
 }
 
-fn create_component(content: &str, open_tags: &Vec<Decoration>) -> Component {
-  Component::from(content)
-    .style(Style::default().decorate(open_tags.iter().map(|tag| tag.clone()).collect::<Vec<_>>()))
+fn create_component(content: &str, open_tags: &[Decoration]) -> Component {
+  Component::from(content).style(Style::from_decorations(open_tags.to_vec()))
 }
 
+///create a tag from its unescaped content between `<` and `>`. The content is split at the first
+///`:` only, so everything after it is the link target, which may contain further colons like in
+///`tg://user?id=5` or `mailto:a@b.com`. Only opening link tags have a target, which may contain
+///`<`, `>` and `\\` escaped with a backslash, see [`Decoration::to_tag`].
 fn create_tag(content: &str) -> Result<Tag, InvalidTagError> {
-  let mut split = content.splitn(2, ':');
-  let id = split.next().unwrap();
-  let (name, closing) = if id.starts_with('/') {
-    (&id[1..], true)
-  } else {
-    (id, false)
+  let (id, target) = match content.split_once(':') {
+    Some((id, target)) => (id, Some(target)),
+    None => (content, None),
+  };
+  let (name, closing) = match id.strip_prefix('/') {
+    Some(name) => (name, true),
+    None => (id, false),
   };
   let mut decoration = Decoration::from(name).ok_or(InvalidTagError::new(content))?;
-  if !closing {
-    if let Decoration::Link(_) = &decoration {
-      decoration = Decoration::Link(
-        split
-          .next()
-          .ok_or(InvalidTagError::new("missing link target"))?
-          .to_owned(),
-      )
+  match (&decoration, target) {
+    (Decoration::Link(_), Some(target)) if !closing => {
+      decoration = Decoration::Link(target.to_owned())
     }
+    (Decoration::Link(_), None) if !closing => Err(InvalidTagError::new("missing link target"))?,
+    (_, Some(target)) => Err(InvalidTagError::new(format!(
+      "unexpected target '{}' of tag '{}'",
+      target, id
+    )))?,
+    (_, None) => {}
   }
   Ok(Tag {
     decoration,
@@ -92,6 +156,32 @@ fn create_tag(content: &str) -> Result<Tag, InvalidTagError> {
   })
 }
 
+///the maximum amount of consecutive blank lines kept by [`normalize_line_breaks`]
+pub const MAX_CONSECUTIVE_BLANK_LINES: usize = 2;
+
+///convert windows (`\r\n`) and old mac (`\r`) line endings to `\n`. If `collapse_blank_lines`
+///is set, more than [`MAX_CONSECUTIVE_BLANK_LINES`] consecutive blank lines are collapsed.
+pub fn normalize_line_breaks(text: &str, collapse_blank_lines: bool) -> String {
+  let normalized = text.replace("\r\n", "\n").replace('\r', "\n");
+  if !collapse_blank_lines {
+    return normalized;
+  }
+  let mut collapsed = String::with_capacity(normalized.len());
+  let mut line_breaks = 0;
+  for c in normalized.chars() {
+    if c == '\n' {
+      line_breaks += 1;
+      if line_breaks > MAX_CONSECUTIVE_BLANK_LINES + 1 {
+        continue;
+      }
+    } else {
+      line_breaks = 0;
+    }
+    collapsed.push(c);
+  }
+  collapsed
+}
+
 ///escape all characters which might be interpreted as tags in a text
 pub fn escape_tags(text: &str) -> String {
   text
@@ -100,24 +190,563 @@ pub fn escape_tags(text: &str) -> String {
     .replace('>', "\\>")
 }
 
-///surround all link urls in the text with a link tag pointing to the link
+///whether [`sanitize`] removes the char. These are
+///- the C0 control chars `U+0000` to `U+001F` except tab (`U+0009`), line feed (`U+000A`) and
+///  carriage return (`U+000D`)
+///- delete (`U+007F`) and the C1 control chars `U+0080` to `U+009F`
+///- the bidi embeddings and overrides `U+202A` to `U+202E`, which can reverse the displayed text
+///- the bidi isolates `U+2066` to `U+2069`
+pub fn is_disallowed(c: char) -> bool {
+  matches!(
+    c,
+    '\u{0}'..='\u{8}'
+      | '\u{b}'
+      | '\u{c}'
+      | '\u{e}'..='\u{1f}'
+      | '\u{7f}'..='\u{9f}'
+      | '\u{202a}'..='\u{202e}'
+      | '\u{2066}'..='\u{2069}'
+  )
+}
+
+///remove all chars telegram rejects or which can spoof the displayed text, see [`is_disallowed`]
+pub fn sanitize(text: &str) -> String {
+  text.chars().filter(|&c| !is_disallowed(c)).collect()
+}
+
+///whether a text contains `<` or `>` which aren't escaped with a backslash, so they would be
+///interpreted as tags. Meant to check user content before interpolating it into a template while
+///testing, see [`escape_tags`].
+pub fn looks_unescaped(text: &str) -> bool {
+  let mut chars = text.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => {
+        chars.next();
+      }
+      '<' | '>' => return true,
+      _ => {}
+    }
+  }
+  false
+}
+
+///find valid tags which are likely caused by interpolating text without [`escape_tags`]: closing
+///tags which don't close any tag, and tags opened again while already open. Tags left open aren't
+///reported, as leaving the last tags unclosed is common.
+pub fn lint(text: &str) -> Vec<String> {
+  let mut warnings = vec![];
+  let mut open_tags: Vec<&'static str> = vec![];
+  //the position and content of the tag being read
+  let mut tag: Option<(usize, String)> = None;
+  let mut chars = text.chars().enumerate().peekable();
+  while let Some((position, c)) = chars.next() {
+    match c {
+      '\\'
+        if chars
+          .peek()
+          .is_some_and(|(_, c)| ['<', '>', '\\'].contains(c)) =>
+      {
+        let escaped = chars.next().unwrap().1;
+        if let Some((_, content)) = &mut tag {
+          content.push(escaped);
+        }
+      }
+      '<' if tag.is_none() => tag = Some((position, String::new())),
+      '>' if tag.is_some() => {
+        let (position, content) = tag.take().unwrap();
+        let Ok(parsed) = create_tag(&content) else {
+          continue;
+        };
+        let name = parsed.decoration.name();
+        if parsed.closing {
+          match open_tags.iter().rposition(|open_tag| *open_tag == name) {
+            Some(index) => {
+              open_tags.remove(index);
+            }
+            None => warnings.push(format!(
+              "closing tag '<{}>' at char {} doesn't close any tag",
+              content, position
+            )),
+          }
+        } else {
+          if open_tags.contains(&name) {
+            warnings.push(format!(
+              "tag '<{}>' at char {} is already open",
+              content, position
+            ));
+          }
+          open_tags.push(name);
+        }
+      }
+      c => {
+        if let Some((_, content)) = &mut tag {
+          content.push(c);
+        }
+      }
+    }
+  }
+  warnings
+}
+
+///the inverse of [`escape_tags`]
+fn unescape_tags(text: &str) -> String {
+  let mut unescaped = String::with_capacity(text.len());
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    match chars.peek() {
+      Some(&next) if c == '\\' && ['<', '>', '\\'].contains(&next) => {
+        unescaped.push(next);
+        chars.next();
+      }
+      _ => unescaped.push(c),
+    }
+  }
+  unescaped
+}
+
+///surround all link urls in a plain text with a link tag pointing to the link, and escape the
+///rest of the text (see [`escape_tags`]), so the result can be passed to [`parse`] or sent as it
+///is. Use [`tag_links_unescaped`] for text which already contains tags.
 pub fn tag_links(text: &str) -> String {
+  tag_links_with(text, escape_tags)
+}
+
+///like [`tag_links`], but leaves the text around the links untouched, for callers who escape the
+///text themselves
+pub fn tag_links_unescaped(text: &str) -> String {
+  tag_links_with(text, str::to_owned)
+}
+
+fn tag_links_with<F>(text: &str, escape: F) -> String
+where
+  F: Fn(&str) -> String,
+{
   let mut tagged = String::new();
   for span in LinkFinder::new().spans(text) {
     if span.kind().is_some() {
       let link = span.as_str();
       let tag = Decoration::link(link);
-      tagged += &format!("{}{}{}", tag.to_tag(false), link, tag.to_tag(true));
+      tagged += &format!("{}{}{}", tag.to_tag(false), escape(link), tag.to_tag(true));
     } else {
-      tagged += span.as_str();
+      tagged += &escape(span.as_str());
+    }
+  }
+  tagged
+}
+
+///how [`tag_entities`] renders a kind of entity it detects
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum EntityStyle {
+  ///leave the entity as it is, telegram clients still highlight most of them
+  #[default]
+  Ignore,
+  ///link hashtags and cashtags to the in-app search, mentions to the profile and phone numbers to
+  ///the dialer
+  Link,
+  Bold,
+}
+
+///the entities [`tag_entities`] detects and how it renders them, all ignored by default
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct EntityConfig {
+  ///like `#rust`
+  pub hashtags: EntityStyle,
+  ///usernames like `@ferris_bot`
+  pub mentions: EntityStyle,
+  ///like `$USD`
+  pub cashtags: EntityStyle,
+  ///international phone numbers like `+1 555 0100`
+  pub phone_numbers: EntityStyle,
+}
+
+impl EntityConfig {
+  pub fn hashtags(mut self, style: EntityStyle) -> Self {
+    self.hashtags = style;
+    self
+  }
+
+  pub fn mentions(mut self, style: EntityStyle) -> Self {
+    self.mentions = style;
+    self
+  }
+
+  pub fn cashtags(mut self, style: EntityStyle) -> Self {
+    self.cashtags = style;
+    self
+  }
+
+  pub fn phone_numbers(mut self, style: EntityStyle) -> Self {
+    self.phone_numbers = style;
+    self
+  }
+
+  fn style(&self, kind: TextEntity) -> EntityStyle {
+    match kind {
+      TextEntity::Hashtag => self.hashtags,
+      TextEntity::Mention => self.mentions,
+      TextEntity::Cashtag => self.cashtags,
+      TextEntity::PhoneNumber => self.phone_numbers,
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum TextEntity {
+  Hashtag,
+  Mention,
+  Cashtag,
+  PhoneNumber,
+}
+
+impl TextEntity {
+  ///the link target of an entity of this kind, including its prefix
+  fn link(self, entity: &str) -> String {
+    match self {
+      Self::Hashtag | Self::Cashtag => format!("tg://search_hashtag?hashtag={}", &entity[1..]),
+      Self::Mention => format!("https://t.me/{}", &entity[1..]),
+      Self::PhoneNumber => format!(
+        "tel:+{}",
+        entity
+          .chars()
+          .filter(char::is_ascii_digit)
+          .collect::<String>()
+      ),
+    }
+  }
+}
+
+///surround the hashtags, mentions, cashtags and phone numbers of a plain text with the tags the
+///config selects, and escape the rest of the text like [`tag_links`]. Entities have to start at
+///the beginning of a word, so email addresses aren't taken for mentions, and link urls are skipped.
+pub fn tag_entities(text: &str, config: &EntityConfig) -> String {
+  let mut tagged = String::new();
+  for span in LinkFinder::new().spans(text) {
+    if span.kind().is_some() {
+      tagged += &escape_tags(span.as_str());
+      continue;
+    }
+    let span = span.as_str();
+    let mut plain_start = 0;
+    let mut index = 0;
+    while let Some(c) = span[index..].chars().next() {
+      let after_word = span[..index].chars().next_back().is_some_and(is_word_char);
+      let entity = detect_entity(&span[index..]).filter(|_| !after_word);
+      let decoration = entity.and_then(|(kind, len)| match config.style(kind) {
+        EntityStyle::Ignore => None,
+        EntityStyle::Link => Some(Decoration::link(kind.link(&span[index..index + len]))),
+        EntityStyle::Bold => Some(Decoration::Bold),
+      });
+      match entity.zip(decoration) {
+        Some(((_, len), decoration)) => {
+          tagged += &escape_tags(&span[plain_start..index]);
+          tagged += &format!(
+            "{}{}{}",
+            decoration.to_tag(false),
+            escape_tags(&span[index..index + len]),
+            decoration.to_tag(true)
+          );
+          index += len;
+          plain_start = index;
+        }
+        None => index += c.len_utf8(),
+      }
+    }
+    tagged += &escape_tags(&span[plain_start..]);
+  }
+  tagged
+}
+
+fn is_word_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+///the entity at the start of the text with its length in bytes
+fn detect_entity(text: &str) -> Option<(TextEntity, usize)> {
+  let mut chars = text.chars();
+  let prefix = chars.next()?;
+  let rest = chars.as_str();
+  let prefix_len = |predicate: fn(char) -> bool| {
+    rest
+      .char_indices()
+      .find(|(_, c)| !predicate(*c))
+      .map(|(index, _)| index)
+      .unwrap_or(rest.len())
+  };
+  match prefix {
+    '#' => {
+      let len = prefix_len(is_word_char);
+      let name = &rest[..len];
+      (!name.is_empty() && !name.chars().all(|c| c.is_ascii_digit()))
+        .then_some((TextEntity::Hashtag, 1 + len))
+    }
+    '@' => {
+      let len = prefix_len(|c| c.is_ascii_alphanumeric() || c == '_');
+      let ends_word = !rest[len..].chars().next().is_some_and(is_word_char);
+      ((5..=32).contains(&len) && ends_word).then_some((TextEntity::Mention, 1 + len))
+    }
+    '$' => {
+      let len = prefix_len(|c| c.is_ascii_uppercase());
+      let ends_word = !rest[len..].chars().next().is_some_and(is_word_char);
+      ((1..=8).contains(&len) && ends_word).then_some((TextEntity::Cashtag, 1 + len))
+    }
+    '+' if rest.starts_with(|c: char| c.is_ascii_digit()) => {
+      let number = &rest[..prefix_len(|c| c.is_ascii_digit() || c == ' ' || c == '-')];
+      let number = number.trim_end_matches([' ', '-']);
+      let digits = number.chars().filter(char::is_ascii_digit).count();
+      let ends_word = !rest[number.len()..]
+        .chars()
+        .next()
+        .is_some_and(is_word_char);
+      ((7..=15).contains(&digits) && ends_word)
+        .then_some((TextEntity::PhoneNumber, 1 + number.len()))
+    }
+    _ => None,
+  }
+}
+
+///decorate all link urls in the text of the components with a link to them, like [`tag_links`]
+///does for a text. Components which are already links or monospace are left untouched.
+pub fn decorate_links(components: Vec<Component>) -> Vec<Component> {
+  let mut decorated = Vec::with_capacity(components.len());
+  for component in components {
+    let skip = component
+      .style
+      .tags()
+      .iter()
+      .any(|tag| matches!(tag, Decoration::Link(_) | Decoration::MonoSpace));
+    if skip {
+      decorated.push(component);
+      continue;
+    }
+    for span in LinkFinder::new().spans(&component.text) {
+      let part = Component::from(span.as_str()).style(component.style.clone());
+      decorated.push(if span.kind().is_some() {
+        part.decorate(Decoration::link(span.as_str()))
+      } else {
+        part
+      });
+    }
+  }
+  decorated
+}
+
+///split a text into components, decorating the given char ranges, e.g. the matches of a search.
+///Fails if ranges overlap or exceed the text.
+pub fn highlight(
+  text: &str,
+  ranges: &[Range<usize>],
+  decoration: Decoration,
+) -> crate::Result<Vec<Component>> {
+  let chars: Vec<char> = text.chars().collect();
+  let mut ranges = ranges.to_vec();
+  ranges.sort_by_key(|range| range.start);
+  let mut components = vec![];
+  let mut end = 0;
+  for range in ranges {
+    if range.start > range.end || range.end > chars.len() {
+      return Err(TelegramError::new(format!(
+        "range {:?} is out of bounds of a text with {} chars",
+        range,
+        chars.len()
+      )));
+    }
+    if range.start < end {
+      return Err(TelegramError::new(format!(
+        "range {:?} overlaps the range ending at {}",
+        range, end
+      )));
+    }
+    components.push(Component::from(String::from_iter(&chars[end..range.start])));
+    components
+      .push(Component::from(String::from_iter(&chars[range.clone()])).decorate(decoration.clone()));
+    end = range.end;
+  }
+  components.push(Component::from(String::from_iter(&chars[end..])));
+  components.retain(|component| !component.is_empty());
+  Ok(components)
+}
+
+///the text of all components without any formatting
+pub fn plain_text(components: &[Component]) -> String {
+  components
+    .iter()
+    .map(|component| component.text.as_str())
+    .collect()
+}
+
+///convert components back to a text with tags, which parses to the same components
+pub fn to_tags(components: &[Component]) -> String {
+  let mut tagged = String::new();
+  for component in components {
+    let tags = component.style.canonical_tags();
+    for tag in &tags {
+      tagged += &tag.to_tag(false);
+    }
+    tagged += &escape_tags(&component.text);
+    for tag in tags.iter().rev() {
+      tagged += &tag.to_tag(true);
     }
   }
   tagged
 }
 
+///parse a text and convert it back to tags, see [`to_tags`]. Adjacent components with equal styles
+///are merged, so the result is the canonical form of the text, which round trips to itself.
+pub fn roundtrip(text: &str) -> Result<String, InvalidTagError> {
+  parse(text).map(|components| to_tags(&coalesce(components)))
+}
+
+///remove all tags from a text, keeping only the plain text with escaped chars unescaped
+pub fn strip_tags(text: &str) -> Result<String, InvalidTagError> {
+  parse(text).map(|components| plain_text(&components))
+}
+
+///render components for a terminal using ANSI escape codes, e.g. to log messages during
+///development. Links are shown as `text (url)`.
+pub fn to_ansi(components: &[Component]) -> String {
+  let mut rendered = String::new();
+  for component in components {
+    let mut link = None;
+    let mut codes = vec![];
+    for decoration in component.style.canonical_tags() {
+      match decoration {
+        Decoration::Bold => codes.push("1"),
+        Decoration::Italic => codes.push("3"),
+        Decoration::Underlined => codes.push("4"),
+        Decoration::Strikethrough => codes.push("9"),
+        Decoration::MonoSpace => codes.push("36"),
+        Decoration::Spoiler => codes.push("7"),
+        Decoration::Link(target) => link = Some(target),
+      }
+    }
+    if codes.is_empty() {
+      rendered += &component.text;
+    } else {
+      rendered += &format!("\x1b[{}m{}\x1b[0m", codes.join(";"), component.text);
+    }
+    if let Some(link) = link {
+      rendered += &format!(" ({})", link);
+    }
+  }
+  rendered
+}
+
+///merge adjacent components with equal styles into one, dropping empty components
+pub fn coalesce(components: Vec<Component>) -> Vec<Component> {
+  let mut coalesced: Vec<Component> = Vec::with_capacity(components.len());
+  for component in components {
+    if component.is_empty() {
+      continue;
+    }
+    match coalesced.last_mut() {
+      Some(last) if last.style == component.style => last.text += &component.text,
+      _ => coalesced.push(component),
+    }
+  }
+  coalesced
+}
+
+///replace `{name}` placeholders in a template with the matching parameters. The values are
+///escaped with [`escape_tags`], so they can't break the formatting of the template. Placeholders
+///without a matching parameter are kept as they are.
+pub fn interpolate(template: &str, params: &[(&str, &str)]) -> String {
+  let mut interpolated = String::with_capacity(template.len());
+  let mut rest = template;
+  while let Some(start) = rest.find('{') {
+    interpolated += &rest[..start];
+    rest = &rest[start..];
+    let value = rest.find('}').and_then(|end| {
+      params
+        .iter()
+        .find(|(name, _)| *name == &rest[1..end])
+        .map(|(_, value)| (end, value))
+    });
+    match value {
+      Some((end, value)) => {
+        interpolated += &escape_tags(value);
+        rest = &rest[end + 1..];
+      }
+      None => {
+        interpolated.push('{');
+        rest = &rest[1..];
+      }
+    }
+  }
+  interpolated += rest;
+  interpolated
+}
+
+///templates containing format tags and placeholders (see [`interpolate`]) in multiple languages
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Localizer {
+  default_language: String,
+  templates: HashMap<String, HashMap<String, String>>,
+}
+
+impl Localizer {
+  ///create a localizer falling back to the templates of `default_language` for missing keys
+  pub fn new<S>(default_language: S) -> Self
+  where
+    S: ToString,
+  {
+    Self {
+      default_language: default_language.to_string(),
+      templates: HashMap::new(),
+    }
+  }
+
+  ///add all templates of a language, failing if any template contains invalid tags
+  pub fn with_templates<S>(
+    mut self,
+    language: S,
+    templates: HashMap<String, String>,
+  ) -> Result<Self, InvalidTagError>
+  where
+    S: ToString,
+  {
+    for (key, template) in templates {
+      self.insert(language.to_string(), key, template)?;
+    }
+    Ok(self)
+  }
+
+  ///add a single template, failing if it contains invalid tags
+  pub fn insert<L, K, T>(&mut self, language: L, key: K, template: T) -> Result<(), InvalidTagError>
+  where
+    L: ToString,
+    K: ToString,
+    T: ToString,
+  {
+    let template = template.to_string();
+    parse(&template)?;
+    self
+      .templates
+      .entry(language.to_string())
+      .or_default()
+      .insert(key.to_string(), template);
+    Ok(())
+  }
+
+  ///resolve the template of a key in the given language, or the default language if it's missing
+  ///there, and interpolate the parameters. Returns [`None`] if neither language has the key.
+  pub fn localize(&self, key: &str, language: &str, params: &[(&str, &str)]) -> Option<String> {
+    [language, &self.default_language]
+      .into_iter()
+      .find_map(|language| self.templates.get(language)?.get(key))
+      .map(|template| interpolate(template, params))
+  }
+
+  pub fn default_language(&self) -> &str {
+    &self.default_language
+  }
+}
+
 #[derive(Debug)]
 pub struct InvalidTagError {
   tag: String,
+  ///the char index of the `<` opening the tag
+  position: Option<usize>,
 }
 
 impl InvalidTagError {
@@ -127,13 +756,28 @@ impl InvalidTagError {
   {
     InvalidTagError {
       tag: tag.to_string(),
+      position: None,
     }
   }
+
+  pub fn at(mut self, position: usize) -> Self {
+    self.position = Some(position);
+    self
+  }
+
+  ///the char index of the `<` opening the invalid tag, if known
+  pub fn position(&self) -> Option<usize> {
+    self.position
+  }
 }
 
 impl Display for InvalidTagError {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    write!(f, "invalid tag: '{}'", self.tag)
+    write!(f, "invalid tag: '{}'", self.tag)?;
+    if let Some(position) = self.position {
+      write!(f, " at char {}", position)?;
+    }
+    Ok(())
   }
 }
 
@@ -150,6 +794,32 @@ impl Component {
     self.text.is_empty()
   }
 
+  ///the amount of chars of the text
+  pub fn len_chars(&self) -> usize {
+    self.text.chars().count()
+  }
+
+  ///split the component after `n` chars, both parts keep the style. If the text has less than `n`
+  ///chars, the second part is empty.
+  pub fn split_at(&self, n: usize) -> (Component, Component) {
+    let index = self
+      .text
+      .char_indices()
+      .nth(n)
+      .map(|(index, _)| index)
+      .unwrap_or(self.text.len());
+    let (first, second) = self.text.split_at(index);
+    (
+      Component::from(first).style(self.style.clone()),
+      Component::from(second).style(self.style.clone()),
+    )
+  }
+
+  ///whether the text has any decorations
+  pub fn is_styled(&self) -> bool {
+    !self.style.is_empty()
+  }
+
   pub fn style(mut self, style: Style) -> Self {
     self.style = style;
     self
@@ -176,12 +846,29 @@ where
   }
 }
 
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+///decorations applied to a text. Styles with the same decorations are equal, regardless of the
+///order the decorations were added in.
+#[derive(Clone, Debug, Default, Eq)]
 pub struct Style {
   tags: Vec<Decoration>,
 }
 
+impl PartialEq for Style {
+  fn eq(&self, other: &Self) -> bool {
+    self.canonical_tags() == other.canonical_tags()
+  }
+}
+
 impl Style {
+  ///a style with the given decorations, see [`Style::decorate`]
+  pub fn from_decorations<D>(decorations: D) -> Self
+  where
+    D: VarArgs<Decoration>,
+  {
+    Self::default().decorate(decorations)
+  }
+
+  ///add decorations, skipping those with the name of a decoration the style already has
   pub fn decorate<D>(mut self, decorations: D) -> Self
   where
     D: VarArgs<Decoration>,
@@ -194,9 +881,22 @@ impl Style {
     self
   }
 
+  ///whether the style has no decorations, so text with it is plain
+  pub fn is_empty(&self) -> bool {
+    self.tags.is_empty()
+  }
+
+  ///the decorations in the order they have been added
   pub fn tags(&self) -> &Vec<Decoration> {
     &self.tags
   }
+
+  ///the decorations ordered by [`Decoration::precedence`], which is the order they are rendered in
+  pub fn canonical_tags(&self) -> Vec<&Decoration> {
+    let mut tags: Vec<&Decoration> = self.tags.iter().collect();
+    tags.sort_by_key(|tag| tag.precedence());
+    tags
+  }
 }
 
 struct Tag {
@@ -207,25 +907,38 @@ struct Tag {
 #[derive(Assoc, Clone, Debug, Eq, PartialEq, Hash)]
 #[func(pub const fn name(& self) -> & 'static str)]
 #[func(fn by_name(name: & str) -> Option < Self >)]
+///the position in the canonical order of decorations, outer decorations come first
+#[func(pub const fn precedence(& self) -> u8)]
 pub enum Decoration {
   #[assoc(name = "bold")]
   #[assoc(by_name = "bold")]
+  #[assoc(precedence = 0)]
   Bold,
   #[assoc(name = "italic")]
   #[assoc(by_name = "italic")]
+  #[assoc(precedence = 1)]
   Italic,
   #[assoc(name = "underline")]
   #[assoc(by_name = "underline")]
   #[assoc(by_name = "underlined")]
+  #[assoc(precedence = 2)]
   Underlined,
+  #[assoc(name = "strikethrough")]
+  #[assoc(by_name = "strikethrough")]
+  #[assoc(by_name = "strike")]
+  #[assoc(precedence = 3)]
+  Strikethrough,
   #[assoc(name = "mono-space")]
   #[assoc(by_name = "mono-space")]
   #[assoc(by_name = "code")]
+  #[assoc(precedence = 4)]
   MonoSpace,
   #[assoc(name = "spoiler")]
   #[assoc(by_name = "spoiler")]
+  #[assoc(precedence = 5)]
   Spoiler,
   #[assoc(name = "link")]
+  #[assoc(precedence = 6)]
   Link(String),
 }
 
@@ -252,7 +965,7 @@ impl Decoration {
         "<{}{}>",
         self.name(),
         match self {
-          Self::Link(link) => format!(":{}", link),
+          Self::Link(link) => format!(":{}", escape_tags(link)),
           _ => String::new(),
         }
       )
@@ -260,9 +973,122 @@ impl Decoration {
   }
 }
 
+impl Display for Decoration {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.to_tag(false))
+  }
+}
+
+impl FromStr for Decoration {
+  type Err = InvalidTagError;
+
+  ///parse an opening tag like `bold` or `link:url`, optionally enclosed in `<>` like the output of
+  ///[`Display`]
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let content = s
+      .strip_prefix('<')
+      .and_then(|s| s.strip_suffix('>'))
+      .unwrap_or(s);
+    let tag = create_tag(&unescape_tags(content))?;
+    if tag.closing {
+      return Err(InvalidTagError::new(s));
+    }
+    Ok(tag.decoration)
+  }
+}
+
+///build a [`Component`] from a text and decorations, an alternative to format tags.
+///
+///Decorations are named like their tags: `bold`, `italic`, `underline`, `strikethrough`, `code` (or `mono_space`),
+///`spoiler` and `link(url)`, e.g. `component!(bold, link("https://crab.rs"); "text")`.
+#[macro_export]
+macro_rules! component {
+  ($($decoration:ident $(($link:expr))?),* ; $text:expr) => {{
+    let decorations: ::std::vec::Vec<$crate::format::Decoration> =
+      ::std::vec![$($crate::__decoration!($decoration $(($link))?)),*];
+    $crate::format::Component::from($text).decorate(decorations)
+  }};
+}
+
+///build a `Vec<Component>` from texts and bracketed [`component!`]s, e.g.
+///`text!["hello ", [bold, italic; name], "!"]`
+#[macro_export]
+macro_rules! text {
+  (@components [$($component:expr),*]) => {{
+    let components: ::std::vec::Vec<$crate::format::Component> = ::std::vec![$($component),*];
+    components
+  }};
+  (@components [$($component:expr),*] [$($inner:tt)*] $(, $($rest:tt)*)?) => {
+    $crate::text!(@components [$($component,)* $crate::component!($($inner)*)] $($($rest)*)?)
+  };
+  (@components [$($component:expr),*] $text:expr $(, $($rest:tt)*)?) => {
+    $crate::text!(@components [$($component,)* $crate::format::Component::from($text)] $($($rest)*)?)
+  };
+  ($($item:tt)*) => {
+    $crate::text!(@components [] $($item)*)
+  };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __decoration {
+  (bold) => {
+    $crate::format::Decoration::Bold
+  };
+  (italic) => {
+    $crate::format::Decoration::Italic
+  };
+  (underline) => {
+    $crate::format::Decoration::Underlined
+  };
+  (strikethrough) => {
+    $crate::format::Decoration::Strikethrough
+  };
+  (code) => {
+    $crate::format::Decoration::MonoSpace
+  };
+  (mono_space) => {
+    $crate::format::Decoration::MonoSpace
+  };
+  (spoiler) => {
+    $crate::format::Decoration::Spoiler
+  };
+  (link($link:expr)) => {
+    $crate::format::Decoration::link($link)
+  };
+}
+
 #[cfg(test)]
 mod test {
-  use crate::format::{escape_tags, parse, tag_links, Component, Decoration};
+  use std::collections::{BTreeSet, HashMap};
+
+  use crate::format::{
+    analyze, coalesce, decorate_links, escape_tags, highlight, interpolate, is_disallowed, lint,
+    looks_unescaped, normalize_line_breaks, parse, roundtrip, sanitize, strip_tags, tag_entities,
+    tag_links, tag_links_unescaped, to_ansi, to_tags, validate, Component, Decoration,
+    EntityConfig, EntityStyle, Localizer, Style,
+  };
+
+  #[test]
+  fn test_component_macros() {
+    assert_eq!(
+      Component::from("text").decorate([Decoration::Bold, Decoration::Italic]),
+      crate::component!(bold, italic; "text")
+    );
+    assert_eq!(Component::from(42), crate::component!(; 42));
+    let name = "crab";
+    assert_eq!(
+      parse("hello <bold><italic>dear</italic></bold> <link:https://crab.rs>crab</link>!").unwrap(),
+      crate::text![
+        "hello ",
+        [bold, italic; "dear"],
+        " ",
+        [link("https://crab.rs"); name],
+        "!",
+      ]
+    );
+    assert!(crate::text![].is_empty());
+  }
 
   #[test]
   fn test_decoration_from() {
@@ -301,6 +1127,46 @@ mod test {
     );
   }
 
+  #[test]
+  fn test_validate() {
+    assert!(validate("<bold>foo</bold> \\<bar>").is_empty());
+    let errors = validate("<blink>foo <bold>bar</bold> <link> <italic");
+    let positions: Vec<_> = errors.iter().map(|e| e.position()).collect();
+    assert_eq!(vec![Some(0), Some(28), Some(35)], positions);
+    assert_eq!("invalid tag: 'blink' at char 0", errors[0].to_string());
+  }
+
+  #[test]
+  fn test_sanitize() {
+    assert_eq!("evil.exe", sanitize("evil\u{202e}.exe"));
+    assert_eq!("a\tb\nc\r\n", sanitize("a\u{0}\tb\u{7}\nc\r\n\u{85}"));
+    assert_eq!("foo", sanitize("\u{2066}foo\u{2069}"));
+    assert!(is_disallowed('\u{202e}'));
+    assert!(!is_disallowed('\u{200d}'));
+  }
+
+  #[test]
+  fn test_looks_unescaped() {
+    assert!(!looks_unescaped("foo"));
+    assert!(looks_unescaped("<bold>foo"));
+    assert!(looks_unescaped("1 > 2"));
+    assert!(!looks_unescaped(&escape_tags("<bold>foo \\ 1 > 2")));
+    assert!(looks_unescaped("\\\\<bold>"));
+  }
+
+  #[test]
+  fn test_lint() {
+    assert!(lint("<bold>foo</bold> <italic>bar").is_empty());
+    assert!(lint(&format!("<bold>{}</bold>", escape_tags("</bold> <bold>"))).is_empty());
+    assert_eq!(
+      vec![
+        "tag '<bold>' at char 9 is already open".to_owned(),
+        "closing tag '</italic>' at char 33 doesn't close any tag".to_owned(),
+      ],
+      lint("<bold>hi <bold>user</bold></bold></italic>")
+    );
+  }
+
   #[test]
   fn test_escape_tags() {
     let text = "Foo<T> \\o/";
@@ -323,9 +1189,346 @@ mod test {
     assert_eq!("</link>", Decoration::link("papermc.io").to_tag(true));
   }
 
+  #[test]
+  fn test_link_targets() {
+    for target in [
+      "tg://user?id=5",
+      "mailto:a@b.com",
+      ":leading",
+      "https://crab.rs/?a=<b>&c=\\",
+    ] {
+      let link = Decoration::link(target);
+      let components = parse(&format!("{}crab</link>", link.to_tag(false))).unwrap();
+      assert_eq!(
+        vec![&link],
+        components[0].style.tags().iter().collect::<Vec<_>>()
+      );
+      assert_eq!(link, link.to_string().parse().unwrap());
+    }
+    assert_eq!(
+      "<link:https://crab.rs/?a=\\<b\\>&c=\\\\>",
+      Decoration::link("https://crab.rs/?a=<b>&c=\\").to_tag(false)
+    );
+    assert!(parse("<link>crab</link>").is_err());
+    assert!(parse("<bold:foo>crab").is_err());
+    assert!(parse("crab</link:foo>").is_err());
+  }
+
+  #[test]
+  fn test_decoration_from_str() {
+    assert_eq!(Decoration::Underlined, "underline".parse().unwrap());
+    assert_eq!(
+      Decoration::link("https://papermc.io"),
+      "link:https://papermc.io".parse().unwrap()
+    );
+    let link = Decoration::link("https://papermc.io");
+    assert_eq!("<link:https://papermc.io>", link.to_string());
+    assert_eq!(link, link.to_string().parse().unwrap());
+    assert!("blink".parse::<Decoration>().is_err());
+    assert!("bold:foo".parse::<Decoration>().is_err());
+    assert!("/bold".parse::<Decoration>().is_err());
+    assert!("link".parse::<Decoration>().is_err());
+  }
+
+  #[test]
+  fn test_component_split_at() {
+    let component = Component::from("fö🦀bar").decorate(Decoration::Bold);
+    assert_eq!(6, component.len_chars());
+    assert_eq!(
+      (
+        Component::from("fö🦀").decorate(Decoration::Bold),
+        Component::from("bar").decorate(Decoration::Bold)
+      ),
+      component.split_at(3)
+    );
+    assert_eq!(
+      (
+        component.clone(),
+        Component::from("").decorate(Decoration::Bold)
+      ),
+      component.split_at(10)
+    );
+  }
+
+  #[test]
+  fn test_normalize_line_breaks() {
+    let text = "foo\r\nbar\rbuzz\n\r\n\r\n\r\nfee";
+    assert_eq!(
+      "foo\nbar\nbuzz\n\n\n\nfee",
+      normalize_line_breaks(text, false)
+    );
+    assert_eq!("foo\nbar\nbuzz\n\n\nfee", normalize_line_breaks(text, true));
+    assert_eq!("foo\n\nbar", normalize_line_breaks("foo\r\n\r\nbar", true));
+  }
+
   #[test]
   fn test_tag_links() {
     let link = "https://papermc.io/";
     assert_eq!(format!("<link:{}>{}</link>", link, link), tag_links(link));
+
+    let text = format!("1 < 2 <bold> {}", link);
+    let mut components = parse(&tag_links(&text)).unwrap();
+    components.retain(|component| !component.is_empty());
+    assert_eq!(
+      vec![
+        Component::from("1 < 2 <bold> "),
+        Component::from(link).decorate(Decoration::link(link)),
+      ],
+      components
+    );
+    assert_eq!(
+      format!("1 < 2 <bold> <link:{}>{}</link>", link, link),
+      tag_links_unescaped(&text)
+    );
+  }
+
+  #[test]
+  fn test_decorate_links() {
+    let link = "https://papermc.io/";
+    let components = parse(&format!(
+      "<bold>see {}</bold> <code>{}</code> <link:{}>docs</link>",
+      link, link, link
+    ))
+    .unwrap();
+    let mut decorated = decorate_links(components);
+    decorated.retain(|component| !component.is_empty());
+    assert_eq!(
+      vec![
+        Component::from("see ").decorate(Decoration::Bold),
+        Component::from(link).decorate(vec![Decoration::Bold, Decoration::link(link)]),
+        Component::from(" "),
+        Component::from(link).decorate(Decoration::MonoSpace),
+        Component::from(" "),
+        Component::from("docs").decorate(Decoration::link(link)),
+      ],
+      decorated
+    );
+  }
+
+  #[test]
+  fn test_tag_entities() {
+    let config = EntityConfig::default()
+      .hashtags(EntityStyle::Bold)
+      .mentions(EntityStyle::Link);
+    assert_eq!(
+      "see <bold>#rust</bold> by <link:https://t.me/ferris_bot>@ferris_bot</link>, not \
+       me@ferris.rs #1 $USD",
+      tag_entities(
+        "see #rust by @ferris_bot, not me@ferris.rs #1 $USD",
+        &config
+      )
+    );
+    assert_eq!(
+      "call <link:tel:+15550100>+1 555-0100</link> \\<3",
+      tag_entities(
+        "call +1 555-0100 <3",
+        &EntityConfig::default().phone_numbers(EntityStyle::Link)
+      )
+    );
+    let link = "https://crab.rs/#docs";
+    assert_eq!(link, tag_entities(link, &config));
+  }
+
+  #[test]
+  fn test_highlight() {
+    assert_eq!(
+      vec![
+        Component::from("a "),
+        Component::from("crab").decorate(Decoration::Bold),
+        Component::from(" and a "),
+        Component::from("crab").decorate(Decoration::Bold),
+      ],
+      highlight("a crab and a crab", &[13..17, 2..6], Decoration::Bold).unwrap()
+    );
+    assert!(highlight("crab", &[0..2, 1..3], Decoration::Bold).is_err());
+    assert!(highlight("crab", &[0..1, 2..5], Decoration::Bold).is_err());
+  }
+
+  #[test]
+  fn test_interpolate() {
+    assert_eq!(
+      "hello <bold>\\<T\\></bold> {missing} {",
+      interpolate("hello <bold>{name}</bold> {missing} {", &[("name", "<T>")])
+    );
+  }
+
+  #[test]
+  fn test_localizer() {
+    let localizer = Localizer::new("en")
+      .with_templates(
+        "en",
+        HashMap::from([
+          ("greet".to_owned(), "hello <bold>{name}".to_owned()),
+          ("bye".to_owned(), "bye".to_owned()),
+        ]),
+      )
+      .expect("invalid template")
+      .with_templates(
+        "de",
+        HashMap::from([("greet".to_owned(), "hallo <bold>{name}".to_owned())]),
+      )
+      .expect("invalid template");
+    assert_eq!(
+      Some("hallo <bold>Steppy".to_owned()),
+      localizer.localize("greet", "de", &[("name", "Steppy")])
+    );
+    assert_eq!(Some("bye".to_owned()), localizer.localize("bye", "de", &[]));
+    assert_eq!(None, localizer.localize("missing", "de", &[]));
+    assert!(Localizer::new("en")
+      .insert("en", "broken", "<foo>")
+      .is_err());
+  }
+
+  #[test]
+  fn test_is_styled() {
+    assert!(Style::default().is_empty());
+    assert!(!Style::default().decorate(Decoration::Bold).is_empty());
+    assert!(!Component::from("foo").is_styled());
+    assert!(Component::from("foo")
+      .decorate(Decoration::Italic)
+      .is_styled());
+    assert!(!Component::from("foo").style(Style::default()).is_styled());
+  }
+
+  #[test]
+  fn test_style_from_decorations() {
+    assert_eq!(
+      Style::default().decorate([Decoration::Bold, Decoration::Italic]),
+      Style::from_decorations([Decoration::Bold, Decoration::Italic])
+    );
+    assert_eq!(Style::default(), Style::from_decorations(vec![]));
+    let style = Style::from_decorations([
+      Decoration::link("https://crab.rs"),
+      Decoration::Bold,
+      Decoration::link("https://papermc.io"),
+    ]);
+    assert_eq!(
+      &vec![Decoration::link("https://crab.rs"), Decoration::Bold],
+      style.tags()
+    );
+  }
+
+  #[test]
+  fn test_style_canonical_order() {
+    let bold_italic = Style::default().decorate([Decoration::Bold, Decoration::Italic]);
+    let italic_bold = Style::default().decorate([Decoration::Italic, Decoration::Bold]);
+    assert_eq!(bold_italic, italic_bold);
+    assert_eq!(
+      &vec![Decoration::Italic, Decoration::Bold],
+      italic_bold.tags()
+    );
+    assert_eq!(
+      vec![&Decoration::Bold, &Decoration::Italic],
+      italic_bold.canonical_tags()
+    );
+    assert_ne!(bold_italic, Style::default().decorate(Decoration::Bold));
+  }
+
+  #[test]
+  fn test_coalesce() {
+    let components = vec![
+      Component::from("foo").decorate([Decoration::Bold, Decoration::Italic]),
+      Component::from("bar").decorate([Decoration::Italic, Decoration::Bold]),
+      Component::from(""),
+      Component::from(" buzz"),
+    ];
+    assert_eq!(
+      vec![
+        Component::from("foobar").decorate([Decoration::Bold, Decoration::Italic]),
+        Component::from(" buzz"),
+      ],
+      coalesce(components)
+    );
+  }
+
+  #[test]
+  fn test_analyze() {
+    let stats = analyze("<bold>foo <italic>bar</italic></bold> <link:papermc.io>buzz").unwrap();
+    assert_eq!(4, stats.components);
+    assert_eq!(
+      BTreeSet::from(["bold", "italic", "link"]),
+      stats.decorations
+    );
+    assert_eq!(2, stats.max_depth);
+    assert!(analyze("<bold").is_err());
+  }
+
+  #[test]
+  fn test_to_ansi() {
+    assert_eq!(
+      "\x1b[1mfoo\x1b[0m bar",
+      to_ansi(&[
+        Component::from("foo").decorate(Decoration::Bold),
+        Component::from(" bar")
+      ])
+    );
+    assert_eq!(
+      "\x1b[1;3mfoo\x1b[0m (papermc.io)",
+      to_ansi(&parse("<italic><bold><link:papermc.io>foo").unwrap())
+    );
+  }
+
+  #[test]
+  fn test_strip_tags() {
+    assert_eq!(
+      "Foo<T> bar buzz fee far *klick*",
+      strip_tags(
+        "<bold>Foo\\<T> <italic>bar</bold> buzz</italic> fee <spoiler>far <link:papermc.io>*klick*"
+      )
+      .unwrap()
+    );
+    assert!(strip_tags("<bold").is_err());
+  }
+
+  #[test]
+  fn test_roundtrip() {
+    let texts = [
+      "<bold>Foo\\<T> <italic>bar</bold> buzz</italic> fee <spoiler>far <link:papermc.io>*klick*",
+      "",
+      "plain",
+      "\\\\<bold>a\\\\</bold> 1 \\ 2",
+      "<link:tg://user?id=5>a:b</link>",
+      "<link:https://crab.rs/?q=\\<x\\>>crab",
+      "<code>\\<T\\></code><bold></bold><bold>a</bold><bold>b",
+      "<italic><bold>a</italic>b</bold>",
+    ];
+    for text in texts {
+      let roundtrip_text = roundtrip(text).unwrap();
+      assert_eq!(
+        coalesce(parse(text).unwrap()),
+        coalesce(parse(&roundtrip_text).unwrap()),
+        "{}",
+        text
+      );
+      assert_eq!(
+        roundtrip_text,
+        roundtrip(&roundtrip_text).unwrap(),
+        "{}",
+        text
+      );
+    }
+    assert_eq!(
+      "<bold>ab</bold>",
+      roundtrip("<bold>a</bold><bold>b").unwrap()
+    );
+    assert!(roundtrip("<foo>").is_err());
+  }
+
+  #[test]
+  fn test_to_tags() {
+    let text =
+      "<bold>Foo\\<T> <italic>bar</bold> buzz</italic> fee <spoiler>far <link:papermc.io>*klick*";
+    let components = parse(text).unwrap();
+    assert_eq!(
+      coalesce(components.clone()),
+      coalesce(parse(&to_tags(&components)).unwrap())
+    );
+    assert_eq!(
+      "<bold><italic>foo</italic></bold> bar",
+      to_tags(&[
+        Component::from("foo").decorate([Decoration::Italic, Decoration::Bold]),
+        Component::from(" bar")
+      ])
+    );
   }
 }
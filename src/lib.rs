@@ -1,134 +1,566 @@
-use std::sync::mpsc::{Receiver, SendError, Sender};
-use std::sync::{mpsc, Arc, Mutex};
-use std::time::Duration;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SendError, Sender};
+use std::sync::{mpsc, Arc, Mutex, OnceLock, TryLockError};
+use std::time::{Duration, Instant, SystemTime};
 
 use teloxide::dispatching::dialogue::GetChatId;
 use teloxide::prelude::*;
 use teloxide::types as tg;
 use teloxide::types::MessageId;
-use tokio::runtime::Runtime;
-use tokio::time;
+use teloxide::{ApiError, RequestError};
+use tokio::runtime::{Runtime, RuntimeFlavor};
+use tokio::task::JoinHandle;
 
+use clock::{Clock, SystemClock};
 use error::TelegramError;
-use request::SendMessage;
+use offset::OffsetStore;
+use rate_limit::{RateLimit, RateLimiter};
+use render::{HtmlRenderer, Renderer};
+use request::{
+  check_invite_link, check_poll, check_reactions, check_topic_name, check_voice_file,
+  explanation_html, message_html, render_caption, Audio, BlockedChats, ChatPermissions,
+  InlineResult, InputFile, InviteLinkConfig, Invoice, MediaItem, PollConfig, SendMessage,
+  SendOptions, SentKeys, SentMessage, ShippingOption, TopicColor, Video, DELETE_MESSAGES_LIMIT,
+  FORWARD_MESSAGES_LIMIT, USER_PROFILE_PHOTOS_LIMIT,
+};
+use retry::{retry, RetryPolicy};
 
 use crate::error::ErrorKind;
-use crate::update::{Query, UpdateKind, User};
+use crate::update::{
+  user_chat_id, ChatJoinRequest, ChatMember, ChatMemberUpdate, Command, InviteLink, PhotoSizes,
+  PollResults, PreCheckoutQuery, Query, ShippingQuery, StickerSet, UpdateFilter, UpdateKind,
+  UpdateSource, User,
+};
 
+pub mod clock;
 pub mod error;
 pub mod format;
+pub mod offset;
+pub mod progress;
+pub mod rate_limit;
+pub mod render;
 pub mod request;
+pub mod retry;
 pub mod update;
 
 pub type Result<T> = std::result::Result<T, TelegramError>;
-pub type ChatID = i64;
-pub type MessageID = i32;
+
+///identifies a chat, which for private chats is the id of the user, see [`update::user_chat_id`]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ChatID(pub i64);
+
+impl From<i64> for ChatID {
+  fn from(chat_id: i64) -> Self {
+    Self(chat_id)
+  }
+}
+
+///allows integer literals like `send_message(123, ..)`, which default to `i32`
+impl From<i32> for ChatID {
+  fn from(chat_id: i32) -> Self {
+    Self(chat_id.into())
+  }
+}
+
+impl From<ChatID> for i64 {
+  fn from(chat_id: ChatID) -> Self {
+    chat_id.0
+  }
+}
+
+impl Display for ChatID {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+///identifies a message within its chat
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct MessageID(pub i32);
+
+impl From<i32> for MessageID {
+  fn from(message_id: i32) -> Self {
+    Self(message_id)
+  }
+}
+
+impl From<MessageID> for i32 {
+  fn from(message_id: MessageID) -> Self {
+    message_id.0
+  }
+}
+
+impl Display for MessageID {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
 
 const MUTEX_POISONED: &str = "mutex has been poisoned";
 
+///the time a [`Query`] waits for an answer before it is answered automatically, see
+///[`Bot::set_auto_ack_callbacks`]
+pub const AUTO_ACK_DELAY: Duration = Duration::from_secs(1);
+///the environment variables [`Bot::from_env`] reads the token from, in order
+pub const TOKEN_ENV_VARS: [&str; 2] = ["TELOXIDE_TOKEN", "TELEGRAM_BOT_TOKEN"];
+
 #[derive(Debug, Clone)]
 pub struct Bot {
   update_receiver: Arc<Mutex<Receiver<Result<update::Update>>>>,
-  network_error_cooldown: Arc<Mutex<Duration>>,
+  update_sender: Sender<Result<update::Update>>,
+  ///the id of the last received update, locked by the polling task for each poll
+  ack: Arc<tokio::sync::Mutex<Option<i32>>>,
+  polling: Arc<Mutex<Option<JoinHandle<()>>>>,
+  network_errors: Arc<Mutex<NetworkErrors>>,
+  default_send_options: Arc<Mutex<SendOptions>>,
+  default_renderer: Arc<Mutex<Arc<dyn Renderer>>>,
+  retry_policy: Arc<Mutex<RetryPolicy>>,
+  allowed_updates: Arc<Mutex<Option<Vec<UpdateFilter>>>>,
+  me: Arc<OnceLock<User>>,
+  clock: Arc<dyn Clock>,
+  offset_store: Option<Arc<dyn OffsetStore>>,
+  callback_acks: Arc<Mutex<CallbackAcks>>,
+  ignore_own_updates: Arc<AtomicBool>,
+  unknown_updates: Arc<AtomicBool>,
+  reaction_limit: Arc<AtomicBool>,
+  rate_limiter: Arc<RateLimiter>,
+  blocked_chats: Arc<Mutex<BlockedChats>>,
+  sent_keys: Arc<SentKeys>,
   bot: teloxide::Bot,
   runtime: Arc<Runtime>,
 }
 
 impl Bot {
+  ///create a bot without contacting telegram, so an invalid token only surfaces with the first
+  ///request. Use [`Bot::connect`] to validate the token right away, or [`Bot::builder`] for further
+  ///options.
   pub fn new<S>(token: S) -> Result<Self>
   where
     S: Into<String>,
   {
-    let bot = teloxide::Bot::new(token);
-    let network_error_cooldown = Arc::new(Mutex::new(Duration::from_secs(2)));
-    let nec_mutex = network_error_cooldown.clone();
-    let (update_sender, update_receiver) = mpsc::channel();
-    let update_receiver = Arc::new(Mutex::new(update_receiver));
-    let runtime = Arc::new(
-      Runtime::new()
-        .map_err(|e| TelegramError::new("failed to create tokio runtime").with_cause(e))?,
-    );
-    let poll_bot = bot.clone();
-    runtime.spawn(async move {
-      let mut ack: Option<i32> = None;
-      while let Ok(_) = Self::poll(&poll_bot, &mut ack, &update_sender, || {
-        nec_mutex.lock().unwrap().clone()
-      })
-      .await
-      {}
-    });
-    let instance = Self {
-      update_receiver,
-      network_error_cooldown,
-      bot,
-      runtime,
-    };
-    Ok(instance)
+    Self::builder(token).build()
   }
 
-  async fn poll<F>(
-    bot: &teloxide::Bot,
-    ack: &mut Option<i32>,
-    update_sender: &Sender<Result<update::Update>>,
-    network_error_cooldown_supplier: F,
-  ) -> std::result::Result<(), SendError<Result<update::Update>>>
+  ///create a bot with the token of the first of the [`TOKEN_ENV_VARS`] which is set, e.g. for
+  ///containerized bots
+  pub fn from_env() -> Result<Self> {
+    Self::new(token_from_env(&TOKEN_ENV_VARS)?)
+  }
+
+  ///create a bot with the token of the given environment variable
+  pub fn from_env_var(name: &str) -> Result<Self> {
+    Self::new(token_from_env(&[name])?)
+  }
+
+  ///create a bot and validate its token by requesting the bot user, before polling for updates
+  pub fn connect<S>(token: S) -> Result<Self>
+  where
+    S: Into<String>,
+  {
+    Self::builder(token).validate_token(true).build()
+  }
+
+  ///create a bot which discards all updates sent while it was offline, see
+  ///[`Bot::flush_pending_updates`]
+  pub fn new_skipping_backlog<S>(token: S) -> Result<Self>
+  where
+    S: Into<String>,
+  {
+    Self::builder(token).skip_backlog(true).build()
+  }
+
+  ///create a bot with a fixed network error cooldown, which already applies to network errors
+  ///right after creation, see [`Bot::set_network_error_cooldown`]
+  pub fn with_network_error_cooldown<S>(token: S, network_error_cooldown: Duration) -> Result<Self>
+  where
+    S: Into<String>,
+  {
+    Self::builder(token)
+      .network_error_cooldown(network_error_cooldown)
+      .build()
+  }
+
+  ///create a bot using the given clock for all waiting, e.g. a [`clock::ManualClock`] in tests
+  pub fn with_clock<S>(token: S, clock: Arc<dyn Clock>) -> Result<Self>
+  where
+    S: Into<String>,
+  {
+    Self::builder(token).clock(clock).build()
+  }
+
+  ///create a bot which continues polling after the offset loaded from the given store, and saves
+  ///the offset after each received batch of updates, so no update is received twice across restarts
+  pub fn with_offset_store<S>(token: S, offset_store: Arc<dyn OffsetStore>) -> Result<Self>
+  where
+    S: Into<String>,
+  {
+    Self::builder(token).offset_store(offset_store).build()
+  }
+
+  ///create a bot running on a runtime from the given builder, e.g. a current thread runtime for
+  ///low resource deployments, see [`BotBuilder::runtime_builder`]. On a current thread runtime the
+  ///bot doesn't poll for updates, as polling can't make progress there, so updates have to be
+  ///fetched with [`Bot::fetch_updates`].
+  pub fn with_runtime_builder<S>(token: S, runtime_builder: tokio::runtime::Builder) -> Result<Self>
+  where
+    S: Into<String>,
+  {
+    let bot = Self::builder(token)
+      .polling(false)
+      .runtime_builder(runtime_builder)
+      .build()?;
+    if bot.runtime.handle().runtime_flavor() != RuntimeFlavor::CurrentThread {
+      bot.start_polling()?;
+    }
+    Ok(bot)
+  }
+
+  ///create a bot which only sends, edits and deletes messages and doesn't poll for updates, which
+  ///saves requests for bots only sending notifications. [`Bot::poll_update`] never returns an update
+  ///and [`Bot::await_update`] fails right away. Polling can still be started later with
+  ///[`Bot::restart_polling`].
+  pub fn send_only<S>(token: S) -> Result<Self>
+  where
+    S: Into<String>,
+  {
+    Self::builder(token).polling(false).build()
+  }
+
+  ///configure a bot before it starts polling
+  pub fn builder<S>(token: S) -> BotBuilder
   where
-    F: Fn() -> Duration,
+    S: Into<String>,
   {
+    BotBuilder::new(token)
+  }
+
+  ///spawn a new polling task, stopping the current one. Fails on a current thread runtime, where
+  ///awaiting updates doesn't drive the runtime, so the polling task would never run.
+  fn start_polling(&self) -> Result<()> {
+    if self.runtime.handle().runtime_flavor() == RuntimeFlavor::CurrentThread {
+      Err(TelegramError::new(
+        "polling requires a multi thread runtime, disable polling and fetch updates instead",
+      ))?;
+    }
+    let poller = Poller {
+      bot: self.bot.clone(),
+      update_sender: self.update_sender.clone(),
+      network_errors: self.network_errors.clone(),
+      allowed_updates: self.allowed_updates.clone(),
+      clock: self.clock.clone(),
+      offset_store: self.offset_store.clone(),
+      callback_acks: self.callback_acks.clone(),
+      ignore_own_updates: self.ignore_own_updates.clone(),
+      unknown_updates: self.unknown_updates.clone(),
+      me: self.me.clone(),
+    };
+    let ack = self.ack.clone();
+    let polling = self
+      .runtime
+      .spawn(async move { while Self::poll(&poller, &mut *ack.lock().await).await.is_ok() {} });
+    if let Some(previous) = self.polling.lock().expect(MUTEX_POISONED).replace(polling) {
+      previous.abort();
+    }
+    Ok(())
+  }
+
+  ///stop the polling task and start a new one, e.g. when it seems to be stuck or has stopped (see
+  ///[`RecvState::Disconnected`]). Polling continues after the last received update, and updates
+  ///already received can still be awaited. Fails on a current thread runtime, see
+  ///[`BotBuilder::runtime_builder`].
+  pub fn restart_polling(&self) -> Result<()> {
+    self.start_polling()
+  }
+
+  async fn poll(
+    poller: &Poller,
+    ack: &mut Option<i32>,
+  ) -> std::result::Result<(), SendError<Result<update::Update>>> {
+    let Poller {
+      bot,
+      update_sender,
+      network_errors,
+      allowed_updates,
+      clock,
+      offset_store,
+      ..
+    } = poller;
+    let me = match poller.ignore_own_updates.load(Ordering::Relaxed) {
+      true => match Self::cached_me(bot, &poller.me).await {
+        Ok(me) => Some(me),
+        Err(e) => {
+          if Self::back_off(network_errors, &**clock).await {
+            update_sender.send(Err(e))?;
+          }
+          return Ok(());
+        }
+      },
+      false => None,
+    };
+    let previous_ack = *ack;
     let mut get_updates = bot.get_updates();
     get_updates.offset = ack.map(|ack| ack + 1);
-    get_updates.allowed_updates = Some(vec![]); //receive all updates
+    get_updates.allowed_updates = Some(Self::allowed_updates_tg(
+      allowed_updates.lock().expect(MUTEX_POISONED).as_deref(),
+    ));
     match get_updates.await {
       Ok(updates) => {
+        network_errors.lock().expect(MUTEX_POISONED).consecutive = 0;
         for update in updates {
-          *ack = (*ack).max(Some(update.id));
-          match update.kind {
-            tg::UpdateKind::Message(message) => Self::wrap_message(message, false, &update_sender)?,
-            tg::UpdateKind::EditedMessage(message) => {
-              Self::wrap_message(message, true, &update_sender)?
-            }
-            tg::UpdateKind::CallbackQuery(callback_query) => {
-              if let Some(query) = Query::from(&callback_query) {
-                if let Some(chat_id) = callback_query.chat_id().map(|id| id.0) {
-                  update_sender.send(Ok(update::Update {
-                    user: User::from(&callback_query.from),
-                    chat_id,
-                    kind: UpdateKind::Query(query),
-                  }))?;
-                }
-              }
+          *ack = (*ack).max(Some(update.id.0 as i32));
+          if me
+            .as_ref()
+            .is_some_and(|me| Self::is_own_update(&update, me))
+          {
+            continue;
+          }
+          if let tg::UpdateKind::CallbackQuery(callback_query) = &update.kind {
+            Self::schedule_callback_ack(&callback_query.id.0, poller);
+          }
+          let unknown = (poller.unknown_updates.load(Ordering::Relaxed)
+            && !Self::is_known_kind(&update.kind))
+          .then(|| Self::wrap_unknown_update(&update, UpdateSource::Polling));
+          if let Some(update) = Self::wrap_update(update.kind, UpdateSource::Polling).or(unknown) {
+            update_sender.send(Ok(update))?;
+          }
+        }
+        if let (Some(store), Some(ack)) = (offset_store, *ack) {
+          if previous_ack != Some(ack) {
+            if let Err(e) = store.save(ack) {
+              update_sender.send(Err(e))?;
             }
-            _ => {}
           }
         }
       }
       Err(e) => {
-        time::sleep(network_error_cooldown_supplier()).await;
-        update_sender.send(Err(
-          TelegramError::new("failed to poll updates")
-            .of_kind(ErrorKind::Network)
-            .with_cause(e),
-        ))?;
+        if Self::back_off(network_errors, &**clock).await {
+          update_sender.send(Err(
+            TelegramError::new("failed to poll updates")
+              .of_kind(ErrorKind::Network)
+              .with_cause(e),
+          ))?;
+        }
       }
     }
     Ok(())
   }
 
-  fn wrap_message(
-    message: Message,
-    edit: bool,
-    update_sender: &Sender<Result<update::Update>>,
-  ) -> std::result::Result<(), SendError<Result<update::Update>>> {
-    if let Some(m) = update::Message::from(&message) {
-      if let Some(user) = message.from() {
-        update_sender.send(Ok(update::Update {
-          user: User::from(user),
-          chat_id: message.chat.id.0,
-          kind: UpdateKind::Message { message: m, edit },
-        }))?;
+  ///the user of the bot from the cache of [`Bot::me`], requested from telegram if it isn't known yet
+  async fn cached_me(bot: &teloxide::Bot, me: &OnceLock<User>) -> Result<User> {
+    if let Some(me) = me.get() {
+      return Ok(me.clone());
+    }
+    let user = bot.get_me().await.map_err(|e| {
+      TelegramError::new("failed to get bot user")
+        .of_kind(&e)
+        .with_cause(e)
+    })?;
+    let user = bot_user(&user.user)?;
+    Ok(me.get_or_init(|| user).clone())
+  }
+
+  ///whether the update has been caused by the bot itself: sent by it, via it or by it on behalf of
+  ///a business account. Telegram doesn't tell who posted in a channel, so own posts aren't detected.
+  fn is_own_update(update: &tg::Update, me: &User) -> bool {
+    let is_me = |user: &tg::User| update::user_chat_id(user.id) == Some(me.id);
+    if update.from().is_some_and(is_me) {
+      return true;
+    }
+    let message = match &update.kind {
+      tg::UpdateKind::Message(message)
+      | tg::UpdateKind::EditedMessage(message)
+      | tg::UpdateKind::BusinessMessage(message)
+      | tg::UpdateKind::EditedBusinessMessage(message)
+      | tg::UpdateKind::ChannelPost(message)
+      | tg::UpdateKind::EditedChannelPost(message) => message,
+      _ => return false,
+    };
+    message.via_bot.as_ref().is_some_and(is_me)
+      || message.sender_business_bot.as_ref().is_some_and(is_me)
+  }
+
+  ///answer the query after the [`AUTO_ACK_DELAY`] unless it has been answered before, if
+  ///[`Bot::set_auto_ack_callbacks`] is enabled
+  fn schedule_callback_ack(query_id: &str, poller: &Poller) {
+    if !poller
+      .callback_acks
+      .lock()
+      .expect(MUTEX_POISONED)
+      .schedule(query_id)
+    {
+      return;
+    }
+    let query_id = query_id.to_owned();
+    let bot = poller.bot.clone();
+    let clock = poller.clock.clone();
+    let callback_acks = poller.callback_acks.clone();
+    tokio::spawn(async move {
+      clock.sleep(AUTO_ACK_DELAY).await;
+      if callback_acks.lock().expect(MUTEX_POISONED).take(&query_id) {
+        //nobody is waiting for the result, the spinner clears after a while anyway
+        let _ = bot
+          .answer_callback_query(tg::CallbackQueryId(query_id))
+          .await;
       }
+    });
+  }
+
+  ///the update kinds to request. An empty list would keep the previous setting of the bot, so
+  ///without filters all kinds are listed, except the ones which have to be requested explicitly.
+  fn allowed_updates_tg(allowed_updates: Option<&[UpdateFilter]>) -> Vec<tg::AllowedUpdate> {
+    use tg::AllowedUpdate::*;
+    match allowed_updates {
+      Some(filters) => filters.iter().map(|filter| filter.to_tg()).collect(),
+      None => vec![
+        Message,
+        EditedMessage,
+        ChannelPost,
+        EditedChannelPost,
+        BusinessConnection,
+        BusinessMessage,
+        EditedBusinessMessage,
+        DeletedBusinessMessages,
+        InlineQuery,
+        ChosenInlineResult,
+        CallbackQuery,
+        ShippingQuery,
+        PreCheckoutQuery,
+        PurchasedPaidMedia,
+        Poll,
+        PollAnswer,
+        MyChatMember,
+        ChatJoinRequest,
+        ChatBoost,
+        RemovedChatBoost,
+      ],
     }
-    Ok(())
+  }
+
+  ///wait for the cooldown after a network error, returns whether the error should be reported
+  async fn back_off(network_errors: &Mutex<NetworkErrors>, clock: &dyn Clock) -> bool {
+    let (cooldown, report) = {
+      let mut network_errors = network_errors.lock().expect(MUTEX_POISONED);
+      let cooldown = jitter(
+        network_errors.backoff.cooldown(network_errors.consecutive),
+        network_errors.jitter,
+        random_signed_unit(),
+      );
+      let report = !network_errors.suppress_repeated || network_errors.consecutive == 0;
+      network_errors.consecutive = network_errors.consecutive.saturating_add(1);
+      (cooldown, report)
+    };
+    clock.sleep(cooldown).await;
+    report
+  }
+
+  ///convert an update received from telegram, `None` for updates of kinds this crate doesn't handle
+  fn wrap_update(kind: tg::UpdateKind, source: UpdateSource) -> Option<update::Update> {
+    match kind {
+      tg::UpdateKind::Message(message) => Self::wrap_message(message, false, source),
+      tg::UpdateKind::EditedMessage(message) => Self::wrap_message(message, true, source),
+      tg::UpdateKind::BusinessMessage(message) => Self::wrap_message(message, false, source),
+      tg::UpdateKind::EditedBusinessMessage(message) => Self::wrap_message(message, true, source),
+      tg::UpdateKind::ChannelPost(message) => Self::wrap_message(message, false, source),
+      tg::UpdateKind::EditedChannelPost(message) => Self::wrap_message(message, true, source),
+      tg::UpdateKind::CallbackQuery(callback_query) => Some(update::Update {
+        source,
+        user: User::from(&callback_query.from)?,
+        chat_id: callback_query
+          .chat_id()
+          .map(from_tg_chat_id)
+          .or_else(|| user_chat_id(callback_query.from.id))?,
+        kind: UpdateKind::Query(Query::from(&callback_query)?),
+      }),
+      tg::UpdateKind::PreCheckoutQuery(pre_checkout_query) => Some(update::Update {
+        source,
+        user: User::from(&pre_checkout_query.from)?,
+        chat_id: user_chat_id(pre_checkout_query.from.id)?,
+        kind: UpdateKind::PreCheckoutQuery(PreCheckoutQuery::from(&pre_checkout_query)),
+      }),
+      tg::UpdateKind::ShippingQuery(shipping_query) => Some(update::Update {
+        source,
+        user: User::from(&shipping_query.from)?,
+        chat_id: user_chat_id(shipping_query.from.id)?,
+        kind: UpdateKind::ShippingQuery(ShippingQuery::from(&shipping_query)),
+      }),
+      tg::UpdateKind::MyChatMember(chat_member_updated) => Some(update::Update {
+        source,
+        user: User::from(&chat_member_updated.from)?,
+        chat_id: from_tg_chat_id(chat_member_updated.chat.id),
+        kind: UpdateKind::MyChatMember(ChatMemberUpdate::from(&chat_member_updated)?),
+      }),
+      tg::UpdateKind::ChatMember(chat_member_updated) => Some(update::Update {
+        source,
+        user: User::from(&chat_member_updated.from)?,
+        chat_id: from_tg_chat_id(chat_member_updated.chat.id),
+        kind: UpdateKind::ChatMember(ChatMemberUpdate::from(&chat_member_updated)?),
+      }),
+      tg::UpdateKind::ChatJoinRequest(chat_join_request) => Some(update::Update {
+        source,
+        user: User::from(&chat_join_request.from)?,
+        chat_id: from_tg_chat_id(chat_join_request.chat.id),
+        kind: UpdateKind::ChatJoinRequest(ChatJoinRequest::from(&chat_join_request)?),
+      }),
+      _ => None,
+    }
+  }
+
+  ///whether the kind is converted by [`Bot::wrap_update`], which may still skip single updates of it
+  fn is_known_kind(kind: &tg::UpdateKind) -> bool {
+    matches!(
+      kind,
+      tg::UpdateKind::Message(_)
+        | tg::UpdateKind::EditedMessage(_)
+        | tg::UpdateKind::BusinessMessage(_)
+        | tg::UpdateKind::EditedBusinessMessage(_)
+        | tg::UpdateKind::ChannelPost(_)
+        | tg::UpdateKind::EditedChannelPost(_)
+        | tg::UpdateKind::CallbackQuery(_)
+        | tg::UpdateKind::PreCheckoutQuery(_)
+        | tg::UpdateKind::ShippingQuery(_)
+        | tg::UpdateKind::MyChatMember(_)
+        | tg::UpdateKind::ChatMember(_)
+        | tg::UpdateKind::ChatJoinRequest(_)
+    )
+  }
+
+  fn wrap_unknown_update(update: &tg::Update, source: UpdateSource) -> update::Update {
+    update::Update {
+      source,
+      user: update.from().and_then(User::from).unwrap_or(User {
+        id: ChatID(0),
+        user_name: None,
+        first_name: String::new(),
+        last_name: None,
+      }),
+      chat_id: update
+        .chat()
+        .map(|chat| from_tg_chat_id(chat.id))
+        .unwrap_or_default(),
+      kind: UpdateKind::Unknown {
+        kind_name: update_kind_name(&update.kind),
+      },
+    }
+  }
+
+  fn wrap_message(message: Message, edit: bool, source: UpdateSource) -> Option<update::Update> {
+    Some(update::Update {
+      source,
+      user: match (&message.from, &message.sender_chat) {
+        (Some(user), _) => User::from(user)?,
+        //channel posts are sent by the channel itself
+        (None, Some(chat)) => User::from_chat(chat),
+        (None, None) => return None,
+      },
+      chat_id: from_tg_chat_id(message.chat.id),
+      kind: UpdateKind::Message {
+        message: update::Message::from(&message)?,
+        edit,
+      },
+    })
   }
 
   pub fn send_message<I, S>(&self, chat_id: I, text: S) -> SendMessage
@@ -141,79 +573,2791 @@ impl Bot {
       chat_id.into(),
       self.bot.clone(),
       self.runtime.clone(),
+      self.get_default_send_options(),
     )
+    .rate_limited(self.rate_limiter.clone())
+    .sent_keys(self.sent_keys.clone())
+    .blocked_chats(self.blocked_chats.clone())
+    .renderer(self.get_default_renderer())
+    .retried(self.get_retry_policy(), self.clock.clone())
+  }
+
+  ///shortcut for sending a message without notification sound
+  pub fn send_message_silent<I, S>(&self, chat_id: I, text: S) -> SendMessage
+  where
+    I: Into<ChatID>,
+    S: ToString,
+  {
+    self.send_message(chat_id, text).disable_notification(true)
+  }
+
+  ///replace the text of a message sent by this bot, which may contain format tags. Fails with
+  ///[`ErrorKind::MessageNotModified`] if the message already has the text, which can be ignored
+  ///with [`error::ignore_not_modified`].
+  pub fn edit_message_text<I, M, S>(&self, chat_id: I, message_id: M, text: S) -> Result<()>
+  where
+    I: Into<ChatID>,
+    M: Into<MessageID>,
+    S: AsRef<str>,
+  {
+    let edit_message_text = self
+      .bot
+      .edit_message_text(
+        to_tg_chat_id(chat_id.into()),
+        to_tg_message_id(message_id.into()),
+        message_html(text.as_ref())?,
+      )
+      .parse_mode(tg::ParseMode::Html);
+    let policy = self.get_retry_policy();
+    self.runtime.block_on(retry(&policy, &*self.clock, || {
+      let edit_message_text = edit_message_text.clone();
+      async move {
+        edit_message_text.await.map_err(|e| {
+          TelegramError::new("failed to edit message")
+            .of_kind(&e)
+            .with_cause(e)
+        })
+      }
+    }))?;
+    Ok(())
+  }
+
+  ///replace the photo, video or document of a message sent by this bot, e.g. to update a chart in
+  ///place. The message has to contain media already, as text messages can't get media by editing.
+  ///Returns the id of the edited message.
+  pub fn edit_message_media<I, M>(
+    &self,
+    chat_id: I,
+    message_id: M,
+    media: &MediaItem,
+  ) -> Result<MessageID>
+  where
+    I: Into<ChatID>,
+    M: Into<MessageID>,
+  {
+    let edit_message_media = self.bot.edit_message_media(
+      to_tg_chat_id(chat_id.into()),
+      to_tg_message_id(message_id.into()),
+      media.to_tg()?,
+    );
+    let message = self
+      .runtime
+      .block_on(async move { edit_message_media.await })
+      .map_err(|e| {
+        TelegramError::new("failed to edit message media")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(from_tg_message_id(message.id))
+  }
+
+  ///remove the inline keyboard of a message sent by this bot. Fails with
+  ///[`ErrorKind::MessageNotModified`] if the message has no buttons.
+  pub fn remove_buttons<I, M>(&self, chat_id: I, message_id: M) -> Result<()>
+  where
+    I: Into<ChatID>,
+    M: Into<MessageID>,
+  {
+    let edit_message_reply_markup = self.bot.edit_message_reply_markup(
+      to_tg_chat_id(chat_id.into()),
+      to_tg_message_id(message_id.into()),
+    );
+    self
+      .runtime
+      .block_on(async move { edit_message_reply_markup.await })
+      .map_err(|e| {
+        TelegramError::new("failed to remove buttons")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(())
   }
 
+  ///delete a message. Fails with [`ErrorKind::MessageNotFound`] if the message has already been
+  ///deleted, which can be ignored with [`error::ignore_message_not_found`].
   pub fn delete_message<I, M>(&self, chat_id: I, message_id: M) -> Result<()>
   where
     I: Into<ChatID>,
     M: Into<MessageID>,
   {
+    let delete_message = self.bot.delete_message(
+      to_tg_chat_id(chat_id.into()),
+      to_tg_message_id(message_id.into()),
+    );
+    let policy = self.get_retry_policy();
+    self.runtime.block_on(retry(&policy, &*self.clock, || {
+      let delete_message = delete_message.clone();
+      async move {
+        delete_message.await.map_err(|e| {
+          TelegramError::new("failed to delete message")
+            .of_kind(&e)
+            .with_cause(e)
+        })
+      }
+    }))?;
+    Ok(())
+  }
+
+  ///delete 1 up to [`DELETE_MESSAGES_LIMIT`] messages of the same chat at once.
+  ///
+  ///Messages which can't be found are skipped by telegram, so an error is only returned if the
+  ///request as a whole failed.
+  pub fn delete_messages<I>(&self, chat_id: I, message_ids: &[MessageID]) -> Result<()>
+  where
+    I: Into<ChatID>,
+  {
+    let count = check_message_count(message_ids, DELETE_MESSAGES_LIMIT)?;
     self.runtime.block_on(async move {
       self
         .bot
-        .delete_message(ChatId(chat_id.into()), MessageId(message_id.into()))
+        .delete_messages(
+          to_tg_chat_id(chat_id.into()),
+          message_ids.iter().copied().map(to_tg_message_id),
+        )
         .await
-        .map_err(|e| TelegramError::new("failed to delete message").with_cause(e))
+        .map_err(|e| {
+          TelegramError::new(format!("failed to delete {} messages", count))
+            .of_kind(&e)
+            .with_cause(e)
+        })
     })?;
     Ok(())
   }
 
-  pub fn poll_update(&self) -> Option<Result<update::Update>> {
-    self
-      .update_receiver
-      .lock()
-      .expect(MUTEX_POISONED)
-      .try_recv()
-      .ok()
+  ///forward 1 up to [`FORWARD_MESSAGES_LIMIT`] messages of the same chat at once, keeping their
+  ///order. Messages which can't be found or forwarded are skipped by telegram.
+  ///
+  ///Returns the ids of the forwarded messages in the target chat.
+  pub fn forward_messages<T, F>(
+    &self,
+    to: T,
+    from: F,
+    message_ids: &[MessageID],
+  ) -> Result<Vec<MessageID>>
+  where
+    T: Into<ChatID>,
+    F: Into<ChatID>,
+  {
+    let count = check_message_count(message_ids, FORWARD_MESSAGES_LIMIT)?;
+    let message_ids = self.runtime.block_on(async move {
+      self
+        .bot
+        .forward_messages(
+          to_tg_chat_id(to.into()),
+          to_tg_chat_id(from.into()),
+          message_ids.iter().copied().map(to_tg_message_id),
+        )
+        .await
+        .map_err(|e| {
+          TelegramError::new(format!("failed to forward {} messages", count))
+            .of_kind(&e)
+            .with_cause(e)
+        })
+    })?;
+    Ok(message_ids.into_iter().map(from_tg_message_id).collect())
   }
 
-  pub fn await_update(&self) -> Result<update::Update> {
-    self
-      .update_receiver
-      .lock()
-      .expect(MUTEX_POISONED)
-      .recv()
-      .map_err(|e| TelegramError::new("update sender has gone out of scope").with_cause(e))
-      .and_then(|r| r)
+  ///copy 1 up to [`FORWARD_MESSAGES_LIMIT`] messages of the same chat at once, keeping their
+  ///order. Unlike forwarded messages, copies don't link to the original message. Messages which
+  ///can't be found or copied are skipped by telegram.
+  ///
+  ///Returns the ids of the copies.
+  pub fn copy_messages<T, F>(
+    &self,
+    to: T,
+    from: F,
+    message_ids: &[MessageID],
+  ) -> Result<Vec<MessageID>>
+  where
+    T: Into<ChatID>,
+    F: Into<ChatID>,
+  {
+    let count = check_message_count(message_ids, FORWARD_MESSAGES_LIMIT)?;
+    let message_ids = self.runtime.block_on(async move {
+      self
+        .bot
+        .copy_messages(
+          to_tg_chat_id(to.into()),
+          to_tg_chat_id(from.into()),
+          message_ids.iter().copied().map(to_tg_message_id),
+        )
+        .await
+        .map_err(|e| {
+          TelegramError::new(format!("failed to copy {} messages", count))
+            .of_kind(&e)
+            .with_cause(e)
+        })
+    })?;
+    Ok(message_ids.into_iter().map(from_tg_message_id).collect())
   }
 
-  pub fn await_update_with_timeout(&self, time_out: Duration) -> Option<Result<update::Update>> {
-    self
-      .update_receiver
-      .lock()
-      .expect(MUTEX_POISONED)
-      .recv_timeout(time_out)
-      .ok()
+  ///the url to download a file from, e.g. to let another service fetch it. The url contains the
+  ///bot token, so it shouldn't be shared publicly, and is only valid for at least an hour, after
+  ///which a new one has to be requested. Files larger than 20 MB can't be downloaded by bots.
+  pub fn get_file_url<S>(&self, file_id: S) -> Result<String>
+  where
+    S: Into<String>,
+  {
+    let get_file = self.bot.get_file(tg::FileId(file_id.into()));
+    let file = self
+      .runtime
+      .block_on(async move { get_file.await })
+      .map_err(|e| {
+        TelegramError::new("failed to get file")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(file_url(self.bot.api_url(), self.bot.token(), &file.path))
   }
 
-  pub fn get_network_error_cooldown(&self) -> Duration {
-    self.network_error_cooldown.lock().unwrap().clone()
+  ///the user of this bot. It is requested from telegram once and cached afterwards.
+  pub fn me(&self) -> Result<User> {
+    self.runtime.block_on(Self::cached_me(&self.bot, &self.me))
   }
 
-  pub fn set_network_error_cooldown(&mut self, network_error_cooldown: Duration) {
-    *self.network_error_cooldown.lock().unwrap() = network_error_cooldown;
+  ///request the user of this bot, bypassing the cache of [`Bot::me`], and return the round trip
+  ///time. Meant to be called repeatedly by health checks: errors have the kind
+  ///[`ErrorKind::Network`] if telegram is unreachable and [`ErrorKind::InvalidToken`] if the token
+  ///has been revoked.
+  pub fn ping(&self) -> Result<Duration> {
+    let start = Instant::now();
+    self.runtime.block_on(async {
+      self.bot.get_me().await.map_err(|e| {
+        TelegramError::new("failed to ping telegram")
+          .of_kind(&e)
+          .with_cause(e)
+      })
+    })?;
+    Ok(start.elapsed())
   }
-}
 
-pub trait VarArgs<T> {
-  fn args(self) -> Vec<T>;
-}
+  ///parse a command from the message, ignoring commands addressed to other bots. See
+  ///[`Command::parse`].
+  pub fn parse_command(&self, message: &update::Message) -> Result<Option<Command>> {
+    let me = self.me()?;
+    Ok(Command::parse(&message.text, me.user_name.as_deref()))
+  }
 
-impl<T> VarArgs<T> for T {
-  fn args(self) -> Vec<T> {
-    vec![self]
+  ///send a sticker, identified by the file id of [`update::Sticker::file_id`] or uploaded as a
+  ///`.webp`, `.tgs` or `.webm` file
+  pub fn send_sticker<I>(&self, chat_id: I, sticker: &InputFile) -> Result<MessageID>
+  where
+    I: Into<ChatID>,
+  {
+    let send_sticker = self
+      .bot
+      .send_sticker(to_tg_chat_id(chat_id.into()), sticker.to_tg()?)
+      .disable_notification(self.get_default_send_options().disable_notification)
+      .protect_content(self.get_default_send_options().protect_content);
+    let message = self
+      .runtime
+      .block_on(async move { send_sticker.await })
+      .map_err(|e| {
+        TelegramError::new("failed to send sticker")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(from_tg_message_id(message.id))
   }
-}
 
-impl<T, const N: usize> VarArgs<T> for [T; N] {
-  fn args(self) -> Vec<T> {
-    self.into_iter().collect()
+  ///send a game set up with @BotFather. Pressing its button sends a [`Query`] with the
+  ///[`Query::game_short_name`], which has to be answered with the url of the game, see
+  ///[`Query::open_game`].
+  pub fn send_game<I, S>(&self, chat_id: I, game_short_name: S) -> Result<MessageID>
+  where
+    I: Into<ChatID>,
+    S: ToString,
+  {
+    let send_game = self
+      .bot
+      .send_game(to_tg_chat_id(chat_id.into()), game_short_name.to_string())
+      .disable_notification(self.get_default_send_options().disable_notification)
+      .protect_content(self.get_default_send_options().protect_content);
+    let message = self
+      .runtime
+      .block_on(async move { send_game.await })
+      .map_err(|e| {
+        TelegramError::new("failed to send game")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(from_tg_message_id(message.id))
   }
-}
 
-impl<T> VarArgs<T> for Vec<T> {
-  fn args(self) -> Vec<T> {
+  pub fn send_audio<I>(&self, chat_id: I, audio: &Audio) -> Result<MessageID>
+  where
+    I: Into<ChatID>,
+  {
+    let mut send_audio = self
+      .bot
+      .send_audio(to_tg_chat_id(chat_id.into()), audio.file.to_tg()?)
+      .disable_notification(self.get_default_send_options().disable_notification)
+      .protect_content(self.get_default_send_options().protect_content);
+    if let Some(caption) = &audio.caption {
+      let caption = render_caption(caption, audio.caption_entities.as_deref())?;
+      send_audio.caption = Some(caption.text);
+      send_audio.parse_mode = caption.parse_mode;
+      send_audio.caption_entities = caption.entities;
+    }
+    send_audio.title = audio.title.clone();
+    send_audio.performer = audio.performer.clone();
+    send_audio.duration = audio.duration.map(|duration| duration.as_secs() as u32);
+    let message = self
+      .runtime
+      .block_on(async move { send_audio.await })
+      .map_err(|e| {
+        TelegramError::new("failed to send audio")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(from_tg_message_id(message.id))
+  }
+
+  ///send a voice message, which has to be an OGG file encoded with OPUS. Local files are checked
+  ///with [`request::check_voice_file`].
+  pub fn send_voice<I>(&self, chat_id: I, voice: &InputFile) -> Result<MessageID>
+  where
+    I: Into<ChatID>,
+  {
+    check_voice_file(voice)?;
+    let send_voice = self
+      .bot
+      .send_voice(to_tg_chat_id(chat_id.into()), voice.to_tg()?)
+      .disable_notification(self.get_default_send_options().disable_notification)
+      .protect_content(self.get_default_send_options().protect_content);
+    let message = self
+      .runtime
+      .block_on(async move { send_voice.await })
+      .map_err(|e| {
+        TelegramError::new("failed to send voice message")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(from_tg_message_id(message.id))
+  }
+
+  pub fn send_video<I>(&self, chat_id: I, video: &Video) -> Result<MessageID>
+  where
+    I: Into<ChatID>,
+  {
+    let mut send_video = self
+      .bot
+      .send_video(to_tg_chat_id(chat_id.into()), video.file.to_tg()?)
+      .disable_notification(self.get_default_send_options().disable_notification)
+      .protect_content(self.get_default_send_options().protect_content);
+    if let Some(caption) = &video.caption {
+      let caption = render_caption(caption, video.caption_entities.as_deref())?;
+      send_video.caption = Some(caption.text);
+      send_video.parse_mode = caption.parse_mode;
+      send_video.caption_entities = caption.entities;
+    }
+    if video.spoiler {
+      send_video = send_video.has_spoiler(true);
+    }
+    send_video.width = video.width;
+    send_video.height = video.height;
+    send_video.duration = video.duration.map(|duration| duration.as_secs() as u32);
+    send_video.thumbnail = video.thumbnail.as_ref().map(InputFile::to_tg).transpose()?;
+    let message = self
+      .runtime
+      .block_on(async move { send_video.await })
+      .map_err(|e| {
+        TelegramError::new("failed to send video")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(from_tg_message_id(message.id))
+  }
+
+  ///send a rounded square video of up to 1 minute, which can't have a caption
+  pub fn send_video_note<I>(&self, chat_id: I, video_note: &InputFile) -> Result<MessageID>
+  where
+    I: Into<ChatID>,
+  {
+    let send_video_note = self
+      .bot
+      .send_video_note(to_tg_chat_id(chat_id.into()), video_note.to_tg()?)
+      .disable_notification(self.get_default_send_options().disable_notification)
+      .protect_content(self.get_default_send_options().protect_content);
+    let message = self
+      .runtime
+      .block_on(async move { send_video_note.await })
+      .map_err(|e| {
+        TelegramError::new("failed to send video note")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(from_tg_message_id(message.id))
+  }
+
+  ///set the default permissions of all members of a group without administrator rights, e.g. to
+  ///lock down a group by allowing nothing
+  pub fn set_chat_permissions<I>(&self, chat_id: I, permissions: ChatPermissions) -> Result<()>
+  where
+    I: Into<ChatID>,
+  {
+    let set_chat_permissions = self
+      .bot
+      .set_chat_permissions(to_tg_chat_id(chat_id.into()), permissions.to_tg());
     self
+      .runtime
+      .block_on(async move { set_chat_permissions.await })
+      .map_err(|e| {
+        match &e {
+          RequestError::Api(ApiError::NotEnoughRightsToChangeChatPermissions) => {
+            TelegramError::new("the bot is not allowed to change the chat permissions")
+              .of_kind(ErrorKind::MissingRights)
+          }
+          RequestError::Api(ApiError::MethodNotAvailableInPrivateChats) => {
+            TelegramError::new("chat permissions can only be set in groups")
+          }
+          _ => TelegramError::new("failed to set chat permissions").of_kind(&e),
+        }
+        .with_cause(e)
+      })?;
+    Ok(())
+  }
+
+  ///change the photo of a group or channel, which requires the right to change the chat info. The
+  ///photo has to be uploaded, either from a path or from memory.
+  pub fn set_chat_photo<I>(&self, chat_id: I, photo: &InputFile) -> Result<()>
+  where
+    I: Into<ChatID>,
+  {
+    if !matches!(photo, InputFile::Path(_) | InputFile::Memory(_)) {
+      Err(TelegramError::new(
+        "a chat photo has to be uploaded from a path or from memory",
+      ))?;
+    }
+    let set_chat_photo = self
+      .bot
+      .set_chat_photo(to_tg_chat_id(chat_id.into()), photo.to_tg()?);
+    self
+      .runtime
+      .block_on(async move { set_chat_photo.await })
+      .map_err(|e| chat_photo_error("failed to set chat photo", e))?;
+    Ok(())
+  }
+
+  ///remove the photo of a group or channel, which requires the right to change the chat info
+  pub fn delete_chat_photo<I>(&self, chat_id: I) -> Result<()>
+  where
+    I: Into<ChatID>,
+  {
+    self.runtime.block_on(async move {
+      self
+        .bot
+        .delete_chat_photo(to_tg_chat_id(chat_id.into()))
+        .await
+        .map_err(|e| chat_photo_error("failed to delete chat photo", e))
+    })?;
+    Ok(())
+  }
+
+  ///create a topic in a forum supergroup, returning its thread id. Requires the right to manage
+  ///topics.
+  pub fn create_forum_topic<I, S>(
+    &self,
+    chat_id: I,
+    name: S,
+    icon_color: TopicColor,
+    icon_custom_emoji_id: Option<&str>,
+  ) -> Result<MessageID>
+  where
+    I: Into<ChatID>,
+    S: ToString,
+  {
+    let name = name.to_string();
+    check_topic_name(&name)?;
+    let mut create_forum_topic = self
+      .bot
+      .create_forum_topic(to_tg_chat_id(chat_id.into()), name)
+      .icon_color(tg::Rgb::from_u32(icon_color.rgb()));
+    create_forum_topic.icon_custom_emoji_id =
+      icon_custom_emoji_id.map(|id| tg::CustomEmojiId(id.to_owned()));
+    let topic = self
+      .runtime
+      .block_on(async move { create_forum_topic.await })
+      .map_err(|e| {
+        TelegramError::new("failed to create forum topic")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(from_tg_message_id(topic.thread_id.0))
+  }
+
+  ///change the name and icon of a forum topic, [`None`] keeps the current value. An empty emoji id
+  ///removes the icon.
+  pub fn edit_forum_topic<I>(
+    &self,
+    chat_id: I,
+    thread_id: MessageID,
+    name: Option<&str>,
+    icon_custom_emoji_id: Option<&str>,
+  ) -> Result<()>
+  where
+    I: Into<ChatID>,
+  {
+    if let Some(name) = name {
+      check_topic_name(name)?;
+    }
+    let mut edit_forum_topic = self.bot.edit_forum_topic(
+      to_tg_chat_id(chat_id.into()),
+      tg::ThreadId(to_tg_message_id(thread_id)),
+    );
+    edit_forum_topic.name = name.map(str::to_owned);
+    edit_forum_topic.icon_custom_emoji_id =
+      icon_custom_emoji_id.map(|id| tg::CustomEmojiId(id.to_owned()));
+    self
+      .runtime
+      .block_on(async move { edit_forum_topic.await })
+      .map_err(|e| {
+        TelegramError::new("failed to edit forum topic")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(())
+  }
+
+  pub fn close_forum_topic<I>(&self, chat_id: I, thread_id: MessageID) -> Result<()>
+  where
+    I: Into<ChatID>,
+  {
+    let close_forum_topic = self.bot.close_forum_topic(
+      to_tg_chat_id(chat_id.into()),
+      tg::ThreadId(to_tg_message_id(thread_id)),
+    );
+    self
+      .runtime
+      .block_on(async move { close_forum_topic.await })
+      .map_err(|e| {
+        TelegramError::new("failed to close forum topic")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(())
+  }
+
+  pub fn reopen_forum_topic<I>(&self, chat_id: I, thread_id: MessageID) -> Result<()>
+  where
+    I: Into<ChatID>,
+  {
+    let reopen_forum_topic = self.bot.reopen_forum_topic(
+      to_tg_chat_id(chat_id.into()),
+      tg::ThreadId(to_tg_message_id(thread_id)),
+    );
+    self
+      .runtime
+      .block_on(async move { reopen_forum_topic.await })
+      .map_err(|e| {
+        TelegramError::new("failed to reopen forum topic")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(())
+  }
+
+  pub fn get_sticker_set<S>(&self, name: S) -> Result<StickerSet>
+  where
+    S: ToString,
+  {
+    let get_sticker_set = self.bot.get_sticker_set(name.to_string());
+    let sticker_set = self
+      .runtime
+      .block_on(async move { get_sticker_set.await })
+      .map_err(|e| {
+        TelegramError::new("failed to get sticker set")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(StickerSet::from(&sticker_set))
+  }
+
+  ///send a poll or quiz with 2 up to 10 options, see [`request::check_poll`]
+  pub fn send_poll<I, Q, O>(
+    &self,
+    chat_id: I,
+    question: Q,
+    options: &[O],
+    config: &PollConfig,
+  ) -> Result<MessageID>
+  where
+    I: Into<ChatID>,
+    Q: AsRef<str>,
+    O: AsRef<str>,
+  {
+    let question = question.as_ref();
+    check_poll(question, options, config)?;
+    let mut send_poll = self
+      .bot
+      .send_poll(
+        to_tg_chat_id(chat_id.into()),
+        question,
+        options
+          .iter()
+          .map(|option| tg::InputPollOption::new(option.as_ref())),
+      )
+      .is_anonymous(config.is_anonymous)
+      .type_(config.kind.to_tg())
+      .disable_notification(self.get_default_send_options().disable_notification)
+      .protect_content(self.get_default_send_options().protect_content);
+    send_poll.correct_option_id = config.correct_option_id;
+    if let Some(explanation) = &config.explanation {
+      send_poll.explanation = Some(explanation_html(explanation)?);
+      send_poll.explanation_parse_mode = Some(tg::ParseMode::Html);
+    }
+    let message = self
+      .runtime
+      .block_on(async move { send_poll.await })
+      .map_err(|e| {
+        TelegramError::new("failed to send poll")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(from_tg_message_id(message.id))
+  }
+
+  ///create an additional invite link for a chat, which requires the bot to be an administrator
+  ///allowed to invite users. See [`check_invite_link`].
+  pub fn create_chat_invite_link<I>(
+    &self,
+    chat_id: I,
+    config: &InviteLinkConfig,
+  ) -> Result<InviteLink>
+  where
+    I: Into<ChatID>,
+  {
+    check_invite_link(config, SystemTime::now())?;
+    let mut create_chat_invite_link = self
+      .bot
+      .create_chat_invite_link(to_tg_chat_id(chat_id.into()));
+    create_chat_invite_link.name = config.name.clone();
+    create_chat_invite_link.expire_date = config.expire_date.map(chrono::DateTime::from);
+    create_chat_invite_link.member_limit = config.member_limit;
+    if config.creates_join_request {
+      create_chat_invite_link.creates_join_request = Some(true);
+    }
+    let invite_link = self
+      .runtime
+      .block_on(async move { create_chat_invite_link.await })
+      .map_err(|e| {
+        TelegramError::new("failed to create invite link")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(InviteLink::from(&invite_link))
+  }
+
+  ///let a user join a chat, see [`UpdateKind::ChatJoinRequest`]
+  pub fn approve_chat_join_request<I, U>(&self, chat_id: I, user_id: U) -> Result<()>
+  where
+    I: Into<ChatID>,
+    U: Into<ChatID>,
+  {
+    let user_id = to_user_id(user_id.into())?;
+    self.runtime.block_on(async move {
+      self
+        .bot
+        .approve_chat_join_request(to_tg_chat_id(chat_id.into()), user_id)
+        .await
+        .map_err(|e| {
+          TelegramError::new("failed to approve join request")
+            .of_kind(&e)
+            .with_cause(e)
+        })
+    })?;
+    Ok(())
+  }
+
+  ///the membership of a single user in a chat, e.g. to check whether the user is still in a group.
+  ///Fails with [`ErrorKind::UserNotFound`] for users who have never been a member of the chat.
+  pub fn get_chat_member<I, U>(&self, chat_id: I, user_id: U) -> Result<ChatMember>
+  where
+    I: Into<ChatID>,
+    U: Into<ChatID>,
+  {
+    let user_id = to_user_id(user_id.into())?;
+    let chat_member = self.runtime.block_on(async move {
+      self
+        .bot
+        .get_chat_member(to_tg_chat_id(chat_id.into()), user_id)
+        .await
+        .map_err(|e| {
+          TelegramError::new(format!("failed to get chat member {}", user_id))
+            .of_kind(&e)
+            .with_cause(e)
+        })
+    })?;
+    ChatMember::from(&chat_member).ok_or_else(|| {
+      TelegramError::new(format!(
+        "the id of chat member {} exceeds the chat id range",
+        user_id
+      ))
+    })
+  }
+
+  ///the profile photos of a user, starting with the current one. `offset` skips the first photos and
+  ///1 up to [`request::USER_PROFILE_PHOTOS_LIMIT`] photos are returned at once. Users without
+  ///profile photos, or which hide them from the bot, have none.
+  pub fn get_user_profile_photos<U>(
+    &self,
+    user_id: U,
+    offset: u32,
+    limit: u32,
+  ) -> Result<Vec<PhotoSizes>>
+  where
+    U: Into<ChatID>,
+  {
+    if limit == 0 || limit > USER_PROFILE_PHOTOS_LIMIT {
+      Err(TelegramError::new(format!(
+        "profile photo limit ({}) must be between 1 and {}",
+        limit, USER_PROFILE_PHOTOS_LIMIT
+      )))?;
+    }
+    let user_id = to_user_id(user_id.into())?;
+    let get_user_profile_photos = self
+      .bot
+      .get_user_profile_photos(user_id)
+      .offset(offset)
+      .limit(limit as u8);
+    let user_profile_photos = self
+      .runtime
+      .block_on(async move { get_user_profile_photos.await })
+      .map_err(|e| {
+        TelegramError::new(format!("failed to get profile photos of user {}", user_id))
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(
+      user_profile_photos
+        .photos
+        .iter()
+        .map(|photo_sizes| PhotoSizes::from(photo_sizes))
+        .collect(),
+    )
+  }
+
+  ///reject the request of a user to join a chat, see [`UpdateKind::ChatJoinRequest`]
+  pub fn decline_chat_join_request<I, U>(&self, chat_id: I, user_id: U) -> Result<()>
+  where
+    I: Into<ChatID>,
+    U: Into<ChatID>,
+  {
+    let user_id = to_user_id(user_id.into())?;
+    self.runtime.block_on(async move {
+      self
+        .bot
+        .decline_chat_join_request(to_tg_chat_id(chat_id.into()), user_id)
+        .await
+        .map_err(|e| {
+          TelegramError::new("failed to decline join request")
+            .of_kind(&e)
+            .with_cause(e)
+        })
+    })?;
+    Ok(())
+  }
+
+  ///revoke an invite link created by this bot, so it can't be used anymore. Revoking the primary
+  ///link of a chat creates a new one.
+  pub fn revoke_chat_invite_link<I, S>(&self, chat_id: I, link: S) -> Result<InviteLink>
+  where
+    I: Into<ChatID>,
+    S: Into<String>,
+  {
+    let revoke_chat_invite_link = self
+      .bot
+      .revoke_chat_invite_link(to_tg_chat_id(chat_id.into()), link);
+    let invite_link = self
+      .runtime
+      .block_on(async move { revoke_chat_invite_link.await })
+      .map_err(|e| {
+        TelegramError::new("failed to revoke invite link")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(InviteLink::from(&invite_link))
+  }
+
+  ///replace the primary invite link of a chat with a new one, revoking the previous one. Returns
+  ///the new link.
+  pub fn export_chat_invite_link<I>(&self, chat_id: I) -> Result<String>
+  where
+    I: Into<ChatID>,
+  {
+    self.runtime.block_on(async move {
+      self
+        .bot
+        .export_chat_invite_link(to_tg_chat_id(chat_id.into()))
+        .await
+        .map_err(|e| {
+          TelegramError::new("failed to export invite link")
+            .of_kind(&e)
+            .with_cause(e)
+        })
+    })
+  }
+
+  ///replace the reactions of this bot on a message with the given emojis, an empty slice removes
+  ///them. Bots can set at most [`request::MESSAGE_REACTION_LIMIT`] reactions, which is checked
+  ///before sending unless disabled with [`Bot::set_reaction_limit`]. Big reactions are shown with a
+  ///large animation.
+  pub fn set_message_reaction<I, M>(
+    &self,
+    chat_id: I,
+    message_id: M,
+    emojis: &[&str],
+    is_big: bool,
+  ) -> Result<()>
+  where
+    I: Into<ChatID>,
+    M: Into<MessageID>,
+  {
+    check_reactions(emojis, self.get_reaction_limit())?;
+    let mut set_message_reaction = self.bot.set_message_reaction(
+      to_tg_chat_id(chat_id.into()),
+      to_tg_message_id(message_id.into()),
+    );
+    set_message_reaction.reaction = Some(
+      emojis
+        .iter()
+        .map(|emoji| tg::ReactionType::Emoji {
+          emoji: emoji.to_string(),
+        })
+        .collect(),
+    );
+    set_message_reaction.is_big = Some(is_big);
+    self
+      .runtime
+      .block_on(async move { set_message_reaction.await })
+      .map_err(|e| {
+        TelegramError::new("failed to set message reaction")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(())
+  }
+
+  ///close a poll sent by this bot, so no more votes are accepted, and return its final results
+  pub fn stop_poll<I>(&self, chat_id: I, message_id: MessageID) -> Result<PollResults>
+  where
+    I: Into<ChatID>,
+  {
+    let stop_poll = self
+      .bot
+      .stop_poll(to_tg_chat_id(chat_id.into()), to_tg_message_id(message_id));
+    let poll = self
+      .runtime
+      .block_on(async move { stop_poll.await })
+      .map_err(|e| {
+        match &e {
+          RequestError::Api(ApiError::PollHasAlreadyClosed) => {
+            TelegramError::new(format!("poll {} has already been closed", message_id))
+          }
+          _ => TelegramError::new("failed to stop poll").of_kind(&e),
+        }
+        .with_cause(e)
+      })?;
+    Ok(PollResults::from(&poll))
+  }
+
+  pub fn send_invoice<I>(&self, chat_id: I, invoice: &Invoice) -> Result<MessageID>
+  where
+    I: Into<ChatID>,
+  {
+    if invoice.prices.is_empty() {
+      Err(TelegramError::new("invoice has no prices"))?;
+    }
+    let mut send_invoice = self.bot.send_invoice(
+      to_tg_chat_id(chat_id.into()),
+      &invoice.title,
+      &invoice.description,
+      &invoice.payload,
+      &invoice.currency,
+      invoice
+        .prices
+        .iter()
+        .map(|price| tg::LabeledPrice::new(&price.label, price.amount)),
+    );
+    send_invoice.provider_token = invoice.provider_token.clone();
+    send_invoice.need_shipping_address = Some(invoice.need_shipping_address);
+    send_invoice.is_flexible = Some(invoice.is_flexible);
+    let message = self
+      .runtime
+      .block_on(async move { send_invoice.await })
+      .map_err(|e| {
+        TelegramError::new("failed to send invoice")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(from_tg_message_id(message.id))
+  }
+
+  ///confirm or decline a [`PreCheckoutQuery`]. Declining requires an error message which is shown
+  ///to the user.
+  pub fn answer_pre_checkout_query<S>(
+    &self,
+    pre_checkout_query_id: S,
+    ok: bool,
+    error_message: Option<&str>,
+  ) -> Result<()>
+  where
+    S: ToString,
+  {
+    let mut answer = self.bot.answer_pre_checkout_query(
+      tg::PreCheckoutQueryId(pre_checkout_query_id.to_string()),
+      ok,
+    );
+    if !ok {
+      answer.error_message = Some(
+        error_message
+          .ok_or(TelegramError::new(
+            "declining a pre checkout query requires an error message",
+          ))?
+          .to_owned(),
+      );
+    }
+    self
+      .runtime
+      .block_on(async move { answer.await })
+      .map_err(|e| {
+        TelegramError::new("failed to answer pre checkout query")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(())
+  }
+
+  ///answer a [`ShippingQuery`] with the options to ship the goods to the address, which requires
+  ///at least one option. Declining requires an error message which is shown to the user, e.g. if
+  ///the address can't be delivered to.
+  pub fn answer_shipping_query<S>(
+    &self,
+    shipping_query_id: S,
+    ok: bool,
+    shipping_options: &[ShippingOption],
+    error_message: Option<&str>,
+  ) -> Result<()>
+  where
+    S: ToString,
+  {
+    let mut answer = self
+      .bot
+      .answer_shipping_query(tg::ShippingQueryId(shipping_query_id.to_string()), ok);
+    if ok {
+      if shipping_options.is_empty() {
+        Err(TelegramError::new(
+          "confirming a shipping query requires shipping options",
+        ))?;
+      }
+      answer.shipping_options = Some(
+        shipping_options
+          .iter()
+          .map(|option| option.to_tg())
+          .collect(),
+      );
+    } else {
+      answer.error_message = Some(
+        error_message
+          .ok_or(TelegramError::new(
+            "declining a shipping query requires an error message",
+          ))?
+          .to_owned(),
+      );
+    }
+    self
+      .runtime
+      .block_on(async move { answer.await })
+      .map_err(|e| {
+        TelegramError::new("failed to answer shipping query")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(())
+  }
+
+  ///answer a [`Query`], which stops the loading spinner on the pressed button. The text is shown
+  ///as a toast, or as an alert the user has to dismiss if `show_alert` is set.
+  ///
+  ///Telegram only accepts one answer per query, so answering a query fails once it has been
+  ///answered automatically, see [`Bot::set_auto_ack_callbacks`].
+  pub fn answer_callback_query<S>(
+    &self,
+    query_id: S,
+    text: Option<&str>,
+    show_alert: bool,
+  ) -> Result<()>
+  where
+    S: ToString,
+  {
+    let mut answer = self
+      .bot
+      .answer_callback_query(tg::CallbackQueryId(self.take_callback_ack(query_id)));
+    answer.text = text.map(str::to_owned);
+    answer.show_alert = Some(show_alert);
+    self
+      .runtime
+      .block_on(async move { answer.await })
+      .map_err(|e| {
+        TelegramError::new("failed to answer callback query")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(())
+  }
+
+  ///answer a [`Query`] with a url to open, which telegram only allows for the url of a game, see
+  ///[`Bot::send_game`], or a `t.me/your_bot?start=...` link to start the bot with a parameter
+  pub fn answer_callback_query_url<S>(&self, query_id: S, url: &str) -> Result<()>
+  where
+    S: ToString,
+  {
+    let url = url::Url::parse(url)
+      .map_err(|e| TelegramError::new(format!("invalid url '{}'", url)).with_cause(e))?;
+    let mut answer = self
+      .bot
+      .answer_callback_query(tg::CallbackQueryId(self.take_callback_ack(query_id)));
+    answer.url = Some(url);
+    self
+      .runtime
+      .block_on(async move { answer.await })
+      .map_err(|e| {
+        TelegramError::new("failed to answer callback query")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(())
+  }
+
+  ///answer a query sent by a web app opened with a web app button, see
+  ///[`request::Button::web_app`], which sends the message of the result to the chat on behalf of
+  ///the user
+  pub fn answer_web_app_query<S>(&self, web_app_query_id: S, result: &InlineResult) -> Result<()>
+  where
+    S: ToString,
+  {
+    let answer = self
+      .bot
+      .answer_web_app_query(web_app_query_id.to_string(), result.to_tg()?);
+    self
+      .runtime
+      .block_on(async move { answer.await })
+      .map_err(|e| {
+        TelegramError::new("failed to answer web app query")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    Ok(())
+  }
+
+  ///stop waiting for the query to be answered, as it is answered manually
+  fn take_callback_ack<S>(&self, query_id: S) -> String
+  where
+    S: ToString,
+  {
+    let query_id = query_id.to_string();
+    self
+      .callback_acks
+      .lock()
+      .expect(MUTEX_POISONED)
+      .take(&query_id);
+    query_id
+  }
+
+  ///fetch the pending updates once, starting with the update with the given id (`0` for the oldest
+  ///unconfirmed one), and return them along with the offset to fetch the next updates with. Passing
+  ///an offset confirms all updates before it, so telegram won't send them again.
+  ///
+  ///This is meant for scripts which check for updates now and then instead of listening to them.
+  ///The polling task of the bot keeps receiving updates concurrently, so mixing both consumes
+  ///updates twice or confirms them before they are fetched. Only updates of the kinds set with
+  ///[`Bot::set_allowed_updates`] are fetched.
+  pub fn fetch_updates(&self, offset: i32) -> Result<(Vec<update::Update>, i32)> {
+    let mut get_updates = self.bot.get_updates();
+    get_updates.offset = Some(offset);
+    get_updates.allowed_updates = Some(Self::allowed_updates_tg(
+      self
+        .allowed_updates
+        .lock()
+        .expect(MUTEX_POISONED)
+        .as_deref(),
+    ));
+    let updates = self
+      .runtime
+      .block_on(async move { get_updates.await })
+      .map_err(|e| {
+        TelegramError::new("failed to fetch updates")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+    let next_offset = updates
+      .last()
+      .map(|update| update.id.0 as i32 + 1)
+      .unwrap_or(offset);
+    Ok((
+      updates
+        .into_iter()
+        .filter_map(|update| Self::wrap_update(update.kind, UpdateSource::Polling))
+        .collect(),
+      next_offset,
+    ))
+  }
+
+  ///discard all pending updates, both the ones telegram hasn't delivered yet and the ones waiting
+  ///to be received from this bot. As the polling task runs concurrently, this waits for the current
+  ///poll to complete (including a network error cooldown) and blocks polling until the backlog has
+  ///been skipped. Updates already received are discarded afterwards, unless another thread waiting
+  ///for updates at the same time receives them first.
+  pub fn flush_pending_updates(&self) -> Result<()> {
+    self.runtime.block_on(async {
+      let mut ack = self.ack.lock().await;
+      let mut get_updates = self.bot.get_updates();
+      get_updates.offset = Some(-1); //only the last pending update
+      get_updates.allowed_updates = Some(Self::allowed_updates_tg(
+        self
+          .allowed_updates
+          .lock()
+          .expect(MUTEX_POISONED)
+          .as_deref(),
+      ));
+      let updates = get_updates.await.map_err(|e| {
+        TelegramError::new("failed to get pending updates")
+          .of_kind(&e)
+          .with_cause(e)
+      })?;
+      if let Some(update) = updates.last() {
+        *ack = (*ack).max(Some(update.id.0 as i32));
+      }
+      if let (Some(store), Some(ack)) = (&self.offset_store, *ack) {
+        store.save(ack)?;
+      }
+      Ok::<_, TelegramError>(())
+    })?;
+    //the polling task has to be able to acknowledge updates while waiting threads are woken up
+    let deadline = Instant::now() + UPDATE_LOOP_INTERVAL;
+    loop {
+      match self.update_receiver.try_lock() {
+        Ok(update_receiver) => {
+          while update_receiver.try_recv().is_ok() {}
+          return Ok(());
+        }
+        Err(TryLockError::Poisoned(_)) => panic!("{}", MUTEX_POISONED),
+        Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+          std::thread::sleep(Duration::from_millis(1))
+        }
+        Err(TryLockError::WouldBlock) => return Ok(()),
+      }
+    }
+  }
+
+  pub fn poll_update(&self) -> Option<Result<update::Update>> {
+    self
+      .update_receiver
+      .lock()
+      .expect(MUTEX_POISONED)
+      .try_recv()
+      .ok()
+  }
+
+  ///wait for the next update. Fails right away if the bot doesn't poll for updates and no updates
+  ///are left, see [`Bot::send_only`], and once the polling task has stopped, see
+  ///[`Bot::restart_polling`].
+  pub fn await_update(&self) -> Result<update::Update> {
+    let update_receiver = self.update_receiver.lock().expect(MUTEX_POISONED);
+    if self.polling.lock().expect(MUTEX_POISONED).is_none() {
+      return update_receiver
+        .try_recv()
+        .map_err(|e| TelegramError::new("the bot doesn't poll for updates").with_cause(e))
+        .and_then(|r| r);
+    }
+    drop(update_receiver);
+    //the bot keeps a sender to restart polling, so a stopped polling task never disconnects. The
+    //receiver is released between waits, so other threads like a flush can get in.
+    loop {
+      let received = self
+        .update_receiver
+        .lock()
+        .expect(MUTEX_POISONED)
+        .recv_timeout(UPDATE_LOOP_INTERVAL);
+      match received {
+        Ok(update) => return update,
+        Err(RecvTimeoutError::Timeout) if self.is_polling() => {}
+        Err(e) => return Err(TelegramError::new("polling has stopped").with_cause(e)),
+      }
+    }
+  }
+
+  #[deprecated = "can't tell a timeout from a stopped polling task, use `Bot::await_update_or_timeout`"]
+  pub fn await_update_with_timeout(&self, time_out: Duration) -> Option<Result<update::Update>> {
+    self.await_update_or_timeout(time_out).ok()
+  }
+
+  ///wait at most `time_out` for an update. Unlike [`Bot::await_update`], a stopped polling task is
+  ///reported as [`RecvState::Disconnected`], after which the bot has to be recreated.
+  pub fn await_update_or_timeout(
+    &self,
+    time_out: Duration,
+  ) -> std::result::Result<Result<update::Update>, RecvState> {
+    self
+      .update_receiver
+      .lock()
+      .expect(MUTEX_POISONED)
+      .recv_timeout(time_out)
+      .map_err(|e| match e {
+        RecvTimeoutError::Timeout if !self.is_polling() => RecvState::Disconnected,
+        RecvTimeoutError::Timeout => RecvState::Timeout,
+        RecvTimeoutError::Disconnected => RecvState::Disconnected,
+      })
+  }
+
+  ///send a message, which may contain format tags, once the given time has come. Telegram can't
+  ///schedule messages for bots, so the message is kept in memory until then and lost if the process
+  ///exits before. The result of sending can be awaited with [`ScheduleHandle::join`].
+  pub fn schedule_message<I, S>(&self, chat_id: I, text: S, at: Instant) -> ScheduleHandle
+  where
+    I: Into<ChatID>,
+    S: ToString,
+  {
+    let send_message = self.send_message(chat_id, text);
+    schedule(&self.runtime, self.clock.clone(), at, move || {
+      send_message.execute()
+    })
+  }
+
+  ///handle each update and each error received instead of an update on a new thread, as an
+  ///alternative to awaiting updates in a loop. The loop ends with [`UpdateLoop::stop`], or when the
+  ///polling task has stopped, which is reported as an error.
+  pub fn on_update_or_error<H, E>(&self, handler: H, error_handler: E) -> UpdateLoop
+  where
+    H: Fn(&Bot, update::Update) + Send + 'static,
+    E: Fn(&Bot, TelegramError) + Send + 'static,
+  {
+    let bot = self.clone();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let stop = stopped.clone();
+    let thread = std::thread::spawn(move || {
+      while !stop.load(Ordering::Relaxed) {
+        match bot.await_update_or_timeout(UPDATE_LOOP_INTERVAL) {
+          Ok(Ok(update)) => handler(&bot, update),
+          Ok(Err(e)) => error_handler(&bot, e),
+          Err(RecvState::Timeout) => {}
+          Err(RecvState::Disconnected) => {
+            error_handler(&bot, TelegramError::new("polling has stopped"));
+            break;
+          }
+        }
+      }
+    });
+    UpdateLoop { stopped, thread }
+  }
+
+  ///like [`Bot::on_update_or_error`], but only the update kinds the given handlers are registered
+  ///for are requested from telegram, see [`UpdateHandlers::allowed_updates`], and each update is
+  ///passed to the handler of its kind. This replaces the allowed updates of the bot.
+  pub fn dispatch<E>(&self, handlers: UpdateHandlers, error_handler: E) -> UpdateLoop
+  where
+    E: Fn(&Bot, TelegramError) + Send + 'static,
+  {
+    self.set_allowed_updates(Some(handlers.allowed_updates()));
+    self.on_update_or_error(
+      move |bot, update| {
+        handlers.handle(bot, update);
+      },
+      error_handler,
+    )
+  }
+
+  ///whether the polling task is still running, see [`Bot::restart_polling`]
+  pub fn is_polling(&self) -> bool {
+    self
+      .polling
+      .lock()
+      .expect(MUTEX_POISONED)
+      .as_ref()
+      .is_some_and(|polling| !polling.is_finished())
+  }
+
+  ///wait for an update until the deadline has passed, returns [`None`] on timeout
+  pub fn await_update_until(&self, deadline: Instant) -> Option<Result<update::Update>> {
+    self
+      .await_update_or_timeout(deadline.saturating_duration_since(Instant::now()))
+      .ok()
+  }
+
+  ///the cooldown after the first of consecutive network errors
+  pub fn get_network_error_cooldown(&self) -> Duration {
+    self.network_errors.lock().unwrap().backoff.base
+  }
+
+  ///use a fixed cooldown after network errors, disabling the exponential backoff. All clones of a
+  ///bot share the same polling task, so this changes the cooldown for the clones as well. To avoid
+  ///the default cooldown being used for errors right after creation, see
+  ///[`Bot::with_network_error_cooldown`].
+  pub fn set_network_error_cooldown(&self, network_error_cooldown: Duration) {
+    self.network_errors.lock().unwrap().backoff =
+      Backoff::new(network_error_cooldown, network_error_cooldown);
+  }
+
+  ///double the cooldown after each consecutive network error, starting at `base` and never
+  ///exceeding `max`. The cooldown is reset after the next successful poll.
+  pub fn set_network_error_backoff(&self, base: Duration, max: Duration) {
+    self.network_errors.lock().unwrap().backoff = Backoff::new(base, max);
+  }
+
+  ///the number of network errors which occurred since the last successful poll
+  pub fn get_consecutive_network_errors(&self) -> u32 {
+    self.network_errors.lock().unwrap().consecutive
+  }
+
+  ///the cooldown which will be waited after the next network error
+  pub fn get_next_network_error_cooldown(&self) -> Duration {
+    let network_errors = self.network_errors.lock().unwrap();
+    network_errors.backoff.cooldown(network_errors.consecutive)
+  }
+
+  pub fn get_default_send_options(&self) -> SendOptions {
+    *self.default_send_options.lock().expect(MUTEX_POISONED)
+  }
+
+  ///the options every message sent by this bot starts with. They can still be overridden for
+  ///single messages. As all clones of a bot share their defaults, they apply to the clones as well.
+  pub fn set_default_send_options(&self, default_send_options: SendOptions) {
+    *self.default_send_options.lock().expect(MUTEX_POISONED) = default_send_options;
+  }
+
+  pub fn get_default_renderer(&self) -> Arc<dyn Renderer> {
+    self.default_renderer.lock().expect(MUTEX_POISONED).clone()
+  }
+
+  ///the renderer formatting the text of every message sent by this bot, unless overridden with
+  ///[`SendMessage::with_renderer`]. Html by default.
+  pub fn set_default_renderer<R>(&self, renderer: R)
+  where
+    R: Renderer + 'static,
+  {
+    *self.default_renderer.lock().expect(MUTEX_POISONED) = Arc::new(renderer);
+  }
+
+  pub fn get_retry_policy(&self) -> RetryPolicy {
+    self.retry_policy.lock().expect(MUTEX_POISONED).clone()
+  }
+
+  ///how sending, editing and deleting messages is retried after failures, unless overridden with
+  ///[`SendMessage::retry_policy`]. Requests are only made once by default. Messages aren't sent
+  ///again after network errors which may have occurred after telegram received them, e.g. timeouts,
+  ///so they aren't duplicated.
+  pub fn set_retry_policy(&self, retry_policy: RetryPolicy) {
+    *self.retry_policy.lock().expect(MUTEX_POISONED) = retry_policy;
+  }
+
+  ///send all messages silently unless overridden, see [`Bot::set_default_send_options`]
+  pub fn set_default_disable_notification(&self, disable_notification: bool) {
+    self
+      .default_send_options
+      .lock()
+      .expect(MUTEX_POISONED)
+      .disable_notification = disable_notification;
+  }
+
+  ///protect all messages from forwarding and saving unless overridden, see
+  ///[`Bot::set_default_send_options`]
+  pub fn set_default_protect_content(&self, protect_content: bool) {
+    self
+      .default_send_options
+      .lock()
+      .expect(MUTEX_POISONED)
+      .protect_content = protect_content;
+  }
+
+  ///if enabled, only the first of consecutive network errors is reported as an update. Whether
+  ///the api is reachable again can be checked with [`Bot::get_consecutive_network_errors`].
+  pub fn set_suppress_repeated_network_errors(&self, suppress: bool) {
+    self.network_errors.lock().unwrap().suppress_repeated = suppress;
+  }
+
+  ///randomize each network error cooldown by up to the given fraction in both directions, e.g.
+  ///`0.25` for ±25%, so many bots don't retry in lockstep after a shared outage. Disabled (`0.0`)
+  ///by default, values are clamped between 0 and 1.
+  pub fn set_network_error_jitter(&self, jitter: f64) {
+    self.network_errors.lock().unwrap().jitter = jitter.clamp(0.0, 1.0);
+  }
+
+  ///the kinds of updates polled for, [`None`] if all updates are received except the ones which have
+  ///to be requested explicitly, see [`Bot::set_allowed_updates`]
+  pub fn get_allowed_updates(&self) -> Option<Vec<UpdateFilter>> {
+    self.allowed_updates.lock().expect(MUTEX_POISONED).clone()
+  }
+
+  ///only receive updates of the given kinds from the next poll on, e.g. the
+  ///[`UpdateFilter::of`] every update kind a bot handles, which [`Bot::dispatch`] derives from its
+  ///handlers. [`None`] receives all updates, except [`UpdateFilter::ChatMember`] which has to be
+  ///requested explicitly.
+  pub fn set_allowed_updates(&self, allowed_updates: Option<Vec<UpdateFilter>>) {
+    *self.allowed_updates.lock().expect(MUTEX_POISONED) = allowed_updates;
+  }
+
+  pub fn get_auto_ack_callbacks(&self) -> bool {
+    self.callback_acks.lock().expect(MUTEX_POISONED).enabled
+  }
+
+  ///if enabled, each [`Query`] not answered with [`Bot::answer_callback_query`] within the
+  ///[`AUTO_ACK_DELAY`] after it has been received is answered without text, so the loading spinner
+  ///on the button doesn't hang until telegram times it out. Answering a query after it has been
+  ///answered automatically fails, so handlers showing a toast have to answer in time. Disabled by
+  ///default.
+  pub fn set_auto_ack_callbacks(&self, enabled: bool) {
+    self.callback_acks.lock().expect(MUTEX_POISONED).enabled = enabled;
+  }
+
+  pub fn get_remember_blocked_chats(&self) -> bool {
+    self.blocked_chats.lock().expect(MUTEX_POISONED).enabled
+  }
+
+  ///if enabled, chats a message couldn't be sent to with [`ErrorKind::BotBlocked`] are remembered,
+  ///and further messages sent to them with [`Bot::send_message`] fail with the same error right away,
+  ///which saves requests during broadcasts. Chats are remembered until
+  ///[`Bot::clear_blocked_chats`] is called. Disabled by default.
+  pub fn set_remember_blocked_chats(&self, enabled: bool) {
+    self.blocked_chats.lock().expect(MUTEX_POISONED).enabled = enabled;
+  }
+
+  ///the chats remembered to have blocked the bot, see [`Bot::set_remember_blocked_chats`]
+  pub fn get_blocked_chats(&self) -> Vec<ChatID> {
+    let mut chats: Vec<ChatID> = self
+      .blocked_chats
+      .lock()
+      .expect(MUTEX_POISONED)
+      .chats
+      .iter()
+      .copied()
+      .collect();
+    chats.sort();
+    chats
+  }
+
+  ///forget the chats which blocked the bot, so messages are sent to them again
+  pub fn clear_blocked_chats(&self) {
+    self
+      .blocked_chats
+      .lock()
+      .expect(MUTEX_POISONED)
+      .chats
+      .clear();
+  }
+
+  pub fn get_rate_limit(&self) -> Option<RateLimit> {
+    self.rate_limiter.global_limit()
+  }
+
+  ///delay sent messages so that at most the given amount of messages is sent to all chats
+  ///together, e.g. [`rate_limit::GLOBAL_RATE_LIMIT`]. Unlimited by default. Messages waiting for the
+  ///limit of their chat (see [`Bot::set_chat_rate_limit`]) count as well, so
+  ///[`request::SendMessage::execute`] waits for whichever limit is more restrictive.
+  pub fn set_rate_limit(&self, rate_limit: Option<RateLimit>) {
+    self.rate_limiter.set_global_limit(rate_limit);
+  }
+
+  pub fn get_chat_rate_limit(&self) -> Option<RateLimit> {
+    self.rate_limiter.chat_limit()
+  }
+
+  ///delay sent messages so that at most the given amount of messages is sent to each chat, e.g.
+  ///[`rate_limit::CHAT_RATE_LIMIT`] or [`rate_limit::GROUP_RATE_LIMIT`]. Unlimited by default.
+  pub fn set_chat_rate_limit(&self, rate_limit: Option<RateLimit>) {
+    self.rate_limiter.set_chat_limit(rate_limit);
+  }
+
+  pub fn get_ignore_own_updates(&self) -> bool {
+    self.ignore_own_updates.load(Ordering::Relaxed)
+  }
+
+  ///if enabled, updates caused by this bot itself are dropped, e.g. its own messages in a group
+  ///where it is an administrator, which could otherwise cause loops. The bot is identified by the
+  ///user of [`Bot::me`], which is requested before the next poll if it isn't cached yet. Telegram
+  ///doesn't tell who posted in a channel, so the bot's own channel posts can't be detected and are
+  ///still received, handlers of channel posts have to avoid loops themselves. Disabled by default.
+  pub fn set_ignore_own_updates(&self, enabled: bool) {
+    self.ignore_own_updates.store(enabled, Ordering::Relaxed);
+  }
+
+  pub fn get_unknown_updates(&self) -> bool {
+    self.unknown_updates.load(Ordering::Relaxed)
+  }
+
+  ///if enabled, updates of kinds this crate doesn't handle are received as [`UpdateKind::Unknown`]
+  ///instead of being dropped, e.g. to log which kinds a bot is missing. Disabled by default.
+  pub fn set_unknown_updates(&self, enabled: bool) {
+    self.unknown_updates.store(enabled, Ordering::Relaxed);
+  }
+
+  pub fn get_reaction_limit(&self) -> bool {
+    self.reaction_limit.load(Ordering::Relaxed)
+  }
+
+  ///if enabled, [`Bot::set_message_reaction`] fails before sending when more than
+  ///[`request::MESSAGE_REACTION_LIMIT`] reactions are given. Disable it if telegram allows this bot
+  ///more reactions, e.g. in chats where it is an administrator, to let telegram decide instead.
+  ///Enabled by default.
+  pub fn set_reaction_limit(&self, enabled: bool) {
+    self.reaction_limit.store(enabled, Ordering::Relaxed);
+  }
+}
+
+///configures a [`Bot`] before it starts polling, see [`Bot::builder`]. Options which can be changed
+///later, like [`BotBuilder::allowed_updates`], already apply to the first poll.
+#[derive(Debug)]
+pub struct BotBuilder {
+  token: String,
+  validate_token: bool,
+  skip_backlog: bool,
+  polling: bool,
+  clock: Arc<dyn Clock>,
+  network_errors: NetworkErrors,
+  offset_store: Option<Arc<dyn OffsetStore>>,
+  allowed_updates: Option<Vec<UpdateFilter>>,
+  default_send_options: SendOptions,
+  default_renderer: Arc<dyn Renderer>,
+  retry_policy: RetryPolicy,
+  auto_ack_callbacks: bool,
+  remember_blocked_chats: bool,
+  ignore_own_updates: bool,
+  unknown_updates: bool,
+  reaction_limit: bool,
+  rate_limit: Option<RateLimit>,
+  chat_rate_limit: Option<RateLimit>,
+  pool_max_idle_per_host: Option<usize>,
+  tcp_keepalive: Option<Duration>,
+  runtime_builder: Option<tokio::runtime::Builder>,
+}
+
+impl BotBuilder {
+  pub fn new<S>(token: S) -> Self
+  where
+    S: Into<String>,
+  {
+    Self {
+      token: token.into(),
+      validate_token: false,
+      skip_backlog: false,
+      polling: true,
+      clock: Arc::new(SystemClock),
+      network_errors: NetworkErrors::default(),
+      offset_store: None,
+      allowed_updates: None,
+      default_send_options: SendOptions::default(),
+      default_renderer: Arc::new(HtmlRenderer),
+      retry_policy: RetryPolicy::none(),
+      auto_ack_callbacks: false,
+      remember_blocked_chats: false,
+      ignore_own_updates: false,
+      unknown_updates: false,
+      reaction_limit: true,
+      rate_limit: None,
+      chat_rate_limit: None,
+      pool_max_idle_per_host: None,
+      tcp_keepalive: None,
+      runtime_builder: None,
+    }
+  }
+
+  ///validate the token by requesting the bot user, see [`Bot::connect`]
+  pub fn validate_token(mut self, validate_token: bool) -> Self {
+    self.validate_token = validate_token;
+    self
+  }
+
+  ///discard all updates sent while the bot was offline, see [`Bot::flush_pending_updates`]
+  pub fn skip_backlog(mut self, skip_backlog: bool) -> Self {
+    self.skip_backlog = skip_backlog;
+    self
+  }
+
+  ///start polling for updates when the bot is built, enabled by default. See [`Bot::send_only`].
+  pub fn polling(mut self, polling: bool) -> Self {
+    self.polling = polling;
+    self
+  }
+
+  ///the clock used for all waiting, e.g. a [`clock::ManualClock`] in tests
+  pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+    self.clock = clock;
+    self
+  }
+
+  ///see [`Bot::set_network_error_cooldown`]
+  pub fn network_error_cooldown(mut self, network_error_cooldown: Duration) -> Self {
+    self.network_errors.backoff = Backoff::new(network_error_cooldown, network_error_cooldown);
+    self
+  }
+
+  ///see [`Bot::set_network_error_backoff`]
+  pub fn network_error_backoff(mut self, base: Duration, max: Duration) -> Self {
+    self.network_errors.backoff = Backoff::new(base, max);
+    self
+  }
+
+  ///see [`Bot::set_suppress_repeated_network_errors`]
+  pub fn suppress_repeated_network_errors(mut self, suppress: bool) -> Self {
+    self.network_errors.suppress_repeated = suppress;
+    self
+  }
+
+  ///see [`Bot::set_network_error_jitter`]
+  pub fn network_error_jitter(mut self, jitter: f64) -> Self {
+    self.network_errors.jitter = jitter.clamp(0.0, 1.0);
+    self
+  }
+
+  ///see [`Bot::with_offset_store`]
+  pub fn offset_store(mut self, offset_store: Arc<dyn OffsetStore>) -> Self {
+    self.offset_store = Some(offset_store);
+    self
+  }
+
+  ///see [`Bot::set_allowed_updates`]
+  pub fn allowed_updates(mut self, allowed_updates: Vec<UpdateFilter>) -> Self {
+    self.allowed_updates = Some(allowed_updates);
+    self
+  }
+
+  ///see [`Bot::set_default_send_options`]
+  pub fn default_send_options(mut self, default_send_options: SendOptions) -> Self {
+    self.default_send_options = default_send_options;
+    self
+  }
+
+  ///see [`Bot::set_default_renderer`]
+  pub fn default_renderer<R>(mut self, renderer: R) -> Self
+  where
+    R: Renderer + 'static,
+  {
+    self.default_renderer = Arc::new(renderer);
+    self
+  }
+
+  ///see [`Bot::set_retry_policy`]
+  pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+    self.retry_policy = retry_policy;
+    self
+  }
+
+  ///see [`Bot::set_auto_ack_callbacks`]
+  pub fn auto_ack_callbacks(mut self, enabled: bool) -> Self {
+    self.auto_ack_callbacks = enabled;
+    self
+  }
+
+  ///see [`Bot::set_remember_blocked_chats`]
+  pub fn remember_blocked_chats(mut self, enabled: bool) -> Self {
+    self.remember_blocked_chats = enabled;
+    self
+  }
+
+  ///see [`Bot::set_rate_limit`]
+  pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+    self.rate_limit = Some(rate_limit);
+    self
+  }
+
+  ///see [`Bot::set_chat_rate_limit`]
+  pub fn chat_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+    self.chat_rate_limit = Some(rate_limit);
+    self
+  }
+
+  ///see [`Bot::set_ignore_own_updates`]
+  pub fn ignore_own_updates(mut self, enabled: bool) -> Self {
+    self.ignore_own_updates = enabled;
+    self
+  }
+
+  ///see [`Bot::set_unknown_updates`]
+  pub fn unknown_updates(mut self, enabled: bool) -> Self {
+    self.unknown_updates = enabled;
+    self
+  }
+
+  ///see [`Bot::set_reaction_limit`]
+  pub fn reaction_limit(mut self, enabled: bool) -> Self {
+    self.reaction_limit = enabled;
+    self
+  }
+
+  ///the maximum amount of idle connections to the telegram api kept open for reuse. Unlimited by
+  ///default, which suits most bots as polling and sending only talk to a single host. Idle
+  ///connections are closed after 90 seconds.
+  pub fn pool_max_idle_per_host(mut self, max_idle: usize) -> Self {
+    self.pool_max_idle_per_host = Some(max_idle);
+    self
+  }
+
+  ///send tcp keep-alive probes on idle connections at the given interval, so connections dropped
+  ///by proxies or NATs are noticed. Disabled by default, around 60 seconds is a sensible value.
+  pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+    self.tcp_keepalive = Some(interval);
+    self
+  }
+
+  ///build the runtime of the bot with the given builder instead of a default multi thread runtime.
+  ///IO and time are enabled on it. Blocking calls like [`SendMessage::execute`] run their request on
+  ///the calling thread either way, but on a current thread runtime, background tasks like polling
+  ///and [`SentMessage::delete_after`] only make progress while such a call runs. Building a bot
+  ///polling on one fails, as does [`Bot::restart_polling`], so [`BotBuilder::polling`] has to be
+  ///disabled and updates have to be fetched with [`Bot::fetch_updates`] instead.
+  pub fn runtime_builder(mut self, runtime_builder: tokio::runtime::Builder) -> Self {
+    self.runtime_builder = Some(runtime_builder);
+    self
+  }
+
+  ///create the bot and start polling for updates
+  pub fn build(self) -> Result<Bot> {
+    let mut client = teloxide::net::default_reqwest_settings();
+    if let Some(max_idle) = self.pool_max_idle_per_host {
+      client = client.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(interval) = self.tcp_keepalive {
+      client = client.tcp_keepalive(interval);
+    }
+    let client = client
+      .build()
+      .map_err(|e| TelegramError::new("failed to create http client").with_cause(e))?;
+    let bot = teloxide::Bot::with_client(self.token, client);
+    let (update_sender, update_receiver) = mpsc::channel();
+    let runtime = match self.runtime_builder {
+      Some(mut runtime_builder) => runtime_builder.enable_all().build(),
+      None => Runtime::new(),
+    };
+    let runtime = Arc::new(
+      runtime.map_err(|e| TelegramError::new("failed to create tokio runtime").with_cause(e))?,
+    );
+    let me = Arc::new(OnceLock::new());
+    if self.validate_token {
+      let user = runtime
+        .block_on(async { bot.get_me().await })
+        .map_err(|e| {
+          match &e {
+            RequestError::Api(ApiError::InvalidToken) => {
+              TelegramError::new("invalid bot token").of_kind(ErrorKind::InvalidToken)
+            }
+            RequestError::Network(_) => {
+              TelegramError::new("failed to validate bot token").of_kind(ErrorKind::Network)
+            }
+            _ => TelegramError::new("failed to validate bot token"),
+          }
+          .with_cause(e)
+        })?;
+      let _ = me.set(bot_user(&user.user)?);
+    }
+    let instance = Bot {
+      update_receiver: Arc::new(Mutex::new(update_receiver)),
+      update_sender,
+      ack: Arc::new(tokio::sync::Mutex::new(
+        self.offset_store.as_ref().and_then(|store| store.load()),
+      )),
+      polling: Arc::new(Mutex::new(None)),
+      network_errors: Arc::new(Mutex::new(self.network_errors)),
+      default_send_options: Arc::new(Mutex::new(self.default_send_options)),
+      default_renderer: Arc::new(Mutex::new(self.default_renderer)),
+      retry_policy: Arc::new(Mutex::new(self.retry_policy)),
+      allowed_updates: Arc::new(Mutex::new(self.allowed_updates)),
+      me,
+      clock: self.clock.clone(),
+      offset_store: self.offset_store,
+      callback_acks: Arc::new(Mutex::new(CallbackAcks {
+        enabled: self.auto_ack_callbacks,
+        ..CallbackAcks::default()
+      })),
+      ignore_own_updates: Arc::new(AtomicBool::new(self.ignore_own_updates)),
+      unknown_updates: Arc::new(AtomicBool::new(self.unknown_updates)),
+      reaction_limit: Arc::new(AtomicBool::new(self.reaction_limit)),
+      sent_keys: Arc::new(SentKeys::new(self.clock.clone())),
+      blocked_chats: Arc::new(Mutex::new(BlockedChats {
+        enabled: self.remember_blocked_chats,
+        ..BlockedChats::default()
+      })),
+      rate_limiter: Arc::new(RateLimiter::new(
+        self.rate_limit,
+        self.chat_rate_limit,
+        self.clock.clone(),
+      )),
+      bot,
+      runtime,
+    };
+    if self.skip_backlog {
+      instance.flush_pending_updates()?;
+    }
+    if self.polling {
+      instance.start_polling()?;
+    }
+    Ok(instance)
+  }
+}
+
+///how often an [`UpdateLoop`] checks whether it has been stopped while no updates arrive
+const UPDATE_LOOP_INTERVAL: Duration = Duration::from_millis(100);
+
+///a thread handling updates, see [`Bot::on_update_or_error`]. Dropping it keeps the thread running.
+#[derive(Debug)]
+pub struct UpdateLoop {
+  stopped: Arc<AtomicBool>,
+  thread: std::thread::JoinHandle<()>,
+}
+
+impl UpdateLoop {
+  ///stop handling updates, waiting for the update currently handled
+  pub fn stop(self) {
+    self.stopped.store(true, Ordering::Relaxed);
+    let _ = self.thread.join();
+  }
+
+  ///whether the thread is still handling updates
+  pub fn is_running(&self) -> bool {
+    !self.thread.is_finished()
+  }
+
+  ///wait until the thread ends, which only happens when polling has stopped
+  pub fn join(self) {
+    let _ = self.thread.join();
+  }
+}
+
+type UpdateHandler = Box<dyn Fn(&Bot, update::Update) + Send>;
+
+///handlers for the kinds of updates a bot is interested in, see [`Bot::dispatch`]
+#[derive(Default)]
+pub struct UpdateHandlers {
+  handlers: Vec<(UpdateFilter, UpdateHandler)>,
+}
+
+impl UpdateHandlers {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  ///handle updates of the given kind, replacing the previous handler for it
+  pub fn on<H>(mut self, filter: UpdateFilter, handler: H) -> Self
+  where
+    H: Fn(&Bot, update::Update) + Send + 'static,
+  {
+    self
+      .handlers
+      .retain(|(registered, _)| *registered != filter);
+    self.handlers.push((filter, Box::new(handler)));
+    self
+  }
+
+  ///the kinds of updates handlers are registered for, in the order of registration
+  pub fn allowed_updates(&self) -> Vec<UpdateFilter> {
+    self.handlers.iter().map(|(filter, _)| *filter).collect()
+  }
+
+  ///pass the update to the handler of its kind, returns false if there is none
+  pub fn handle(&self, bot: &Bot, update: update::Update) -> bool {
+    let Some(filter) = UpdateFilter::of(&update.kind) else {
+      return false;
+    };
+    match self
+      .handlers
+      .iter()
+      .find(|(registered, _)| *registered == filter)
+    {
+      Some((_, handler)) => {
+        handler(bot, update);
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+impl std::fmt::Debug for UpdateHandlers {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("UpdateHandlers")
+      .field("allowed_updates", &self.allowed_updates())
+      .finish()
+  }
+}
+
+///a message waiting to be sent, see [`Bot::schedule_message`], or another action waiting to be
+///run. Dropping it keeps the action scheduled.
+#[derive(Debug)]
+pub struct ScheduleHandle<T = SentMessage> {
+  state: Arc<Mutex<ScheduleState>>,
+  task: JoinHandle<()>,
+  result: Mutex<Receiver<Result<T>>>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum ScheduleState {
+  Pending,
+  Dispatched,
+  Cancelled,
+}
+
+impl<T> ScheduleHandle<T> {
+  ///prevent the message from being sent. Returns false if it is too late, as the message has
+  ///already been sent.
+  pub fn cancel(&self) -> bool {
+    let mut state = self.state.lock().expect(MUTEX_POISONED);
+    match *state {
+      ScheduleState::Pending => {
+        *state = ScheduleState::Cancelled;
+        self.task.abort();
+        true
+      }
+      ScheduleState::Cancelled => true,
+      ScheduleState::Dispatched => false,
+    }
+  }
+
+  ///whether the message is still waiting to be sent
+  pub fn is_pending(&self) -> bool {
+    *self.state.lock().expect(MUTEX_POISONED) == ScheduleState::Pending
+  }
+
+  ///wait until the message has been sent and return the result of sending it. Fails if it has been
+  ///cancelled.
+  pub fn join(self) -> Result<T> {
+    self
+      .result
+      .into_inner()
+      .expect(MUTEX_POISONED)
+      .recv()
+      .map_err(|e| TelegramError::new("the scheduled action has been cancelled").with_cause(e))
+      .and_then(|r| r)
+  }
+}
+
+///run the action on a new thread once the given time has come, unless it has been cancelled
+pub(crate) fn schedule<T, F>(
+  runtime: &Runtime,
+  clock: Arc<dyn Clock>,
+  at: Instant,
+  action: F,
+) -> ScheduleHandle<T>
+where
+  T: Send + 'static,
+  F: FnOnce() -> Result<T> + Send + 'static,
+{
+  let state = Arc::new(Mutex::new(ScheduleState::Pending));
+  let task_state = state.clone();
+  let (result_sender, result_receiver) = mpsc::channel();
+  let task = runtime.spawn(async move {
+    clock.sleep(at.saturating_duration_since(clock.now())).await;
+    {
+      let mut state = task_state.lock().expect(MUTEX_POISONED);
+      if *state != ScheduleState::Pending {
+        return;
+      }
+      *state = ScheduleState::Dispatched;
+    }
+    //requests block on the runtime, which isn't possible from within a task
+    std::thread::spawn(move || {
+      let _ = result_sender.send(action());
+    });
+  });
+  ScheduleHandle {
+    state,
+    task,
+    result: Mutex::new(result_receiver),
+  }
+}
+
+///why no update has been received in time
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum RecvState {
+  ///no update arrived in time, waiting again may still yield updates
+  Timeout,
+  ///the polling task has stopped, no more updates will arrive
+  Disconnected,
+}
+
+///the state of the bot shared with the polling task
+#[derive(Debug)]
+struct Poller {
+  bot: teloxide::Bot,
+  update_sender: Sender<Result<update::Update>>,
+  network_errors: Arc<Mutex<NetworkErrors>>,
+  allowed_updates: Arc<Mutex<Option<Vec<UpdateFilter>>>>,
+  clock: Arc<dyn Clock>,
+  offset_store: Option<Arc<dyn OffsetStore>>,
+  callback_acks: Arc<Mutex<CallbackAcks>>,
+  ignore_own_updates: Arc<AtomicBool>,
+  unknown_updates: Arc<AtomicBool>,
+  me: Arc<OnceLock<User>>,
+}
+
+///the name of the variant of an update kind, e.g. `ChatBoost`
+fn update_kind_name(kind: &tg::UpdateKind) -> String {
+  use tg::UpdateKind::*;
+  match kind {
+    Message(_) => "Message",
+    EditedMessage(_) => "EditedMessage",
+    ChannelPost(_) => "ChannelPost",
+    EditedChannelPost(_) => "EditedChannelPost",
+    BusinessConnection(_) => "BusinessConnection",
+    BusinessMessage(_) => "BusinessMessage",
+    EditedBusinessMessage(_) => "EditedBusinessMessage",
+    DeletedBusinessMessages(_) => "DeletedBusinessMessages",
+    MessageReaction(_) => "MessageReaction",
+    MessageReactionCount(_) => "MessageReactionCount",
+    InlineQuery(_) => "InlineQuery",
+    ChosenInlineResult(_) => "ChosenInlineResult",
+    CallbackQuery(_) => "CallbackQuery",
+    ShippingQuery(_) => "ShippingQuery",
+    PreCheckoutQuery(_) => "PreCheckoutQuery",
+    PurchasedPaidMedia(_) => "PurchasedPaidMedia",
+    Poll(_) => "Poll",
+    PollAnswer(_) => "PollAnswer",
+    MyChatMember(_) => "MyChatMember",
+    ChatMember(_) => "ChatMember",
+    ChatJoinRequest(_) => "ChatJoinRequest",
+    ChatBoost(_) => "ChatBoost",
+    RemovedChatBoost(_) => "RemovedChatBoost",
+    Error(_) => "Error",
+  }
+  .to_owned()
+}
+
+///the value of the first of the environment variables which is set and not empty
+fn token_from_env(names: &[&str]) -> Result<String> {
+  names
+    .iter()
+    .find_map(|name| std::env::var(name).ok().filter(|token| !token.is_empty()))
+    .ok_or_else(|| {
+      TelegramError::new(format!(
+        "no bot token found in the environment, set {}",
+        names.join(" or ")
+      ))
+    })
+}
+
+///the queries to answer automatically, see [`Bot::set_auto_ack_callbacks`]
+#[derive(Debug, Default)]
+struct CallbackAcks {
+  enabled: bool,
+  pending: HashSet<String>,
+}
+
+impl CallbackAcks {
+  ///remember a received query to answer it automatically, returns whether it should be answered
+  fn schedule(&mut self, query_id: &str) -> bool {
+    self.enabled && self.pending.insert(query_id.to_owned())
+  }
+
+  ///forget a query as it is being answered, returns whether it still had to be answered
+  fn take(&mut self, query_id: &str) -> bool {
+    self.pending.remove(query_id)
+  }
+}
+
+#[derive(Debug)]
+struct NetworkErrors {
+  backoff: Backoff,
+  consecutive: u32,
+  suppress_repeated: bool,
+  ///the maximum relative deviation of a cooldown, between 0 and 1
+  jitter: f64,
+}
+
+impl Default for NetworkErrors {
+  fn default() -> Self {
+    Self {
+      backoff: Backoff::new(Duration::from_secs(2), Duration::from_secs(60)),
+      consecutive: 0,
+      suppress_repeated: false,
+      jitter: 0.0,
+    }
+  }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Backoff {
+  base: Duration,
+  max: Duration,
+}
+
+impl Backoff {
+  fn new(base: Duration, max: Duration) -> Self {
+    Self { base, max }
+  }
+
+  fn cooldown(&self, failures: u32) -> Duration {
+    2u32
+      .checked_pow(failures)
+      .and_then(|factor| self.base.checked_mul(factor))
+      .unwrap_or(self.max)
+      .min(self.max)
+  }
+}
+
+///scale the cooldown by `1 + jitter * random`, where random is between -1 and 1
+fn jitter(cooldown: Duration, jitter: f64, random: f64) -> Duration {
+  cooldown.mul_f64((1.0 + jitter * random).max(0.0))
+}
+
+///a random number between -1 and 1. Good enough to spread out retries, not for anything else.
+fn random_signed_unit() -> f64 {
+  use std::hash::{BuildHasher, Hasher};
+
+  let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+  hasher.write_u128(
+    std::time::SystemTime::now()
+      .duration_since(std::time::UNIX_EPOCH)
+      .unwrap_or_default()
+      .as_nanos(),
+  );
+  hasher.finish() as f64 / u64::MAX as f64 * 2.0 - 1.0
+}
+
+///the download url of a file, relative to the api url so custom api servers are respected
+fn file_url(mut api_url: url::Url, token: &str, file_path: &str) -> String {
+  if let Ok(mut segments) = api_url.path_segments_mut() {
+    segments
+      .pop_if_empty()
+      .push("file")
+      .push(&format!("bot{}", token))
+      .extend(file_path.split('/'));
+  }
+  api_url.to_string()
+}
+
+fn chat_photo_error(detail_message: &str, error: RequestError) -> TelegramError {
+  match ErrorKind::from(&error) {
+    ErrorKind::MissingRights => {
+      TelegramError::new("the bot is not allowed to change the chat photo")
+        .of_kind(ErrorKind::MissingRights)
+    }
+    kind => TelegramError::new(detail_message).of_kind(kind),
+  }
+  .with_cause(error)
+}
+
+///check that 1 up to `limit` messages are given, returning the amount
+fn check_message_count(message_ids: &[MessageID], limit: u32) -> Result<usize> {
+  let count = message_ids.len();
+  if count == 0 || count > limit as usize {
+    Err(TelegramError::new(format!(
+      "message count ({}) must be between 1 and {}",
+      count, limit
+    )))?;
+  }
+  Ok(count)
+}
+
+fn bot_user(user: &tg::User) -> Result<User> {
+  User::from(user).ok_or_else(|| {
+    TelegramError::new(format!(
+      "the id of the bot user {} exceeds the chat id range",
+      user.id
+    ))
+  })
+}
+
+///the user id of a private chat, the inverse of [`update::user_chat_id`]
+fn to_user_id(user_chat_id: ChatID) -> Result<UserId> {
+  u64::try_from(user_chat_id.0)
+    .map(UserId)
+    .map_err(|e| TelegramError::new(format!("invalid user id {}", user_chat_id)).with_cause(e))
+}
+
+///convert a message id to the type teloxide uses, the only place relying on its representation
+pub(crate) fn to_tg_message_id(message_id: MessageID) -> MessageId {
+  MessageId(message_id.0)
+}
+
+///convert a message id received from teloxide, see [`to_tg_message_id`]
+pub(crate) fn from_tg_message_id(message_id: MessageId) -> MessageID {
+  MessageID(message_id.0)
+}
+
+pub(crate) fn to_tg_chat_id(chat_id: ChatID) -> ChatId {
+  ChatId(chat_id.0)
+}
+
+///convert a chat id received from teloxide, see [`to_tg_chat_id`]
+pub(crate) fn from_tg_chat_id(chat_id: ChatId) -> ChatID {
+  ChatID(chat_id.0)
+}
+
+pub trait VarArgs<T> {
+  fn args(self) -> Vec<T>;
+}
+
+impl<T> VarArgs<T> for T {
+  fn args(self) -> Vec<T> {
+    vec![self]
+  }
+}
+
+impl<T, const N: usize> VarArgs<T> for [T; N] {
+  fn args(self) -> Vec<T> {
+    self.into_iter().collect()
+  }
+}
+
+impl<T> VarArgs<T> for Vec<T> {
+  fn args(self) -> Vec<T> {
+    self
+  }
+}
+
+impl<T> VarArgs<T> for &[T]
+where
+  T: Clone,
+{
+  fn args(self) -> Vec<T> {
+    self.to_vec()
+  }
+}
+
+///passes the items of an iterator as [`VarArgs`], e.g. `decorate(Iter::new(decorations))`. The
+///item type is part of the type to keep it apart from the implementation for single values.
+#[derive(Debug, Clone)]
+pub struct Iter<T, I> {
+  iter: I,
+  item: PhantomData<T>,
+}
+
+impl<T, I> Iter<T, I>
+where
+  I: IntoIterator<Item = T>,
+{
+  pub fn new(iter: I) -> Self {
+    Self {
+      iter,
+      item: PhantomData,
+    }
+  }
+}
+
+impl<T, I> VarArgs<T> for Iter<T, I>
+where
+  I: IntoIterator<Item = T>,
+{
+  fn args(self) -> Vec<T> {
+    self.iter.into_iter().collect()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::{Arc, Mutex};
+  use std::time::{Duration, Instant, SystemTime};
+
+  use crate::clock::ManualClock;
+  use crate::error::ErrorKind;
+  use crate::format::{Decoration, Style};
+  use crate::render::{HtmlRenderer, MarkdownV2Renderer};
+  use crate::request::{Button, SentMessage, USER_PROFILE_PHOTOS_LIMIT};
+  use teloxide::types::{self as tg, MessageId, UserId};
+  use tokio::runtime::Runtime;
+
+  use crate::{
+    check_message_count, file_url, from_tg_message_id, jitter, random_signed_unit, schedule,
+    to_tg_message_id, to_user_id, token_from_env, Backoff, Bot, BotBuilder, CallbackAcks, ChatID,
+    Iter, MessageID, NetworkErrors, UpdateFilter, UpdateHandlers, UpdateKind, UpdateSource, User,
+    VarArgs,
+  };
+
+  ///build a bot sending its requests to a local port nothing listens on, so tests never reach
+  ///telegram
+  fn offline_bot(builder: BotBuilder) -> Bot {
+    let polling = builder.polling;
+    let mut bot = builder.polling(false).build().unwrap();
+    bot.bot = bot
+      .bot
+      .set_api_url(url::Url::parse("http://127.0.0.1:9").unwrap());
+    if polling {
+      bot.start_polling().unwrap();
+    }
+    bot
+  }
+
+  #[test]
+  fn test_backoff_cooldown() {
+    let backoff = Backoff::new(Duration::from_secs(2), Duration::from_secs(60));
+    assert_eq!(Duration::from_secs(2), backoff.cooldown(0));
+    assert_eq!(Duration::from_secs(4), backoff.cooldown(1));
+    assert_eq!(Duration::from_secs(32), backoff.cooldown(4));
+    assert_eq!(Duration::from_secs(60), backoff.cooldown(5));
+    assert_eq!(Duration::from_secs(60), backoff.cooldown(u32::MAX));
+
+    let fixed = Backoff::new(Duration::from_secs(2), Duration::from_secs(2));
+    assert_eq!(Duration::from_secs(2), fixed.cooldown(3));
+  }
+
+  #[test]
+  fn test_back_off() {
+    let clock = ManualClock::new();
+    let network_errors = Mutex::new(NetworkErrors {
+      suppress_repeated: true,
+      ..NetworkErrors::default()
+    });
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let reports: Vec<bool> = (0..3)
+      .map(|_| runtime.block_on(Bot::back_off(&network_errors, &clock)))
+      .collect();
+    assert_eq!(vec![true, false, false], reports);
+    assert_eq!(
+      vec![
+        Duration::from_secs(2),
+        Duration::from_secs(4),
+        Duration::from_secs(8)
+      ],
+      clock.sleeps()
+    );
+    assert_eq!(Duration::from_secs(14), clock.elapsed());
+  }
+
+  #[test]
+  fn test_jitter() {
+    let cooldown = Duration::from_secs(4);
+    assert_eq!(cooldown, jitter(cooldown, 0.0, 0.7));
+    assert_eq!(Duration::from_secs(5), jitter(cooldown, 0.25, 1.0));
+    assert_eq!(Duration::from_secs(3), jitter(cooldown, 0.25, -1.0));
+    for _ in 0..100 {
+      assert!((-1.0..=1.0).contains(&random_signed_unit()));
+    }
+  }
+
+  #[test]
+  fn test_callback_acks() {
+    let mut callback_acks = CallbackAcks::default();
+    assert!(!callback_acks.schedule("a"));
+    callback_acks.enabled = true;
+    assert!(callback_acks.schedule("a"));
+    assert!(callback_acks.schedule("b"));
+    assert!(callback_acks.take("a"));
+    assert!(!callback_acks.take("a"));
+    assert!(callback_acks.take("b"));
+  }
+
+  #[test]
+  fn test_message_id_conversion() {
+    assert_eq!(MessageId(42), to_tg_message_id(MessageID(42)));
+    assert_eq!(MessageID(42), from_tg_message_id(MessageId(42)));
+    for id in [MessageID(1), MessageID(i32::MAX)] {
+      assert_eq!(id, from_tg_message_id(to_tg_message_id(id)));
+    }
+  }
+
+  #[test]
+  fn test_id_newtypes() {
+    let chat_id = |chat_id: ChatID| chat_id;
+    assert_eq!(ChatID(-100123), chat_id((-100123i64).into()));
+    assert_eq!(ChatID(42), chat_id(42.into()));
+    assert_eq!(42, i64::from(ChatID(42)));
+    assert_eq!(7, i32::from(MessageID(7)));
+    assert_eq!("-100123", ChatID(-100123).to_string());
+    assert_eq!("7", MessageID(7).to_string());
+  }
+
+  #[test]
+  fn test_unknown_update() {
+    let update = serde_json::from_str::<tg::Update>(
+      r#"{
+        "update_id": 1,
+        "message_reaction_count": {
+          "message_id": 2,
+          "date": 1,
+          "chat": {"id": -1005, "type": "channel", "title": "foo"},
+          "reactions": []
+        }
+      }"#,
+    )
+    .unwrap();
+    assert!(!Bot::is_known_kind(&update.kind));
+    let unknown = Bot::wrap_unknown_update(&update, UpdateSource::Polling);
+    assert_eq!(Some(ChatID(-1005)), unknown.chat_id());
+    assert_eq!(ChatID(0), unknown.user.id);
+    match unknown.kind {
+      UpdateKind::Unknown { kind_name } => assert_eq!("MessageReactionCount", kind_name),
+      kind => panic!("unexpected kind {:?}", kind),
+    }
+  }
+
+  #[test]
+  fn test_is_own_update() {
+    let me = User {
+      id: ChatID(42),
+      user_name: Some("foo_bot".to_owned()),
+      first_name: "foo".to_owned(),
+      last_name: None,
+    };
+    let update = |json: &str| serde_json::from_str::<tg::Update>(json).unwrap();
+    let message = update(
+      r#"{
+        "update_id": 1,
+        "message": {
+          "message_id": 2,
+          "date": 1,
+          "chat": {"id": -5, "type": "group", "title": "foo"},
+          "from": {"id": 42, "is_bot": true, "first_name": "foo"},
+          "text": "bar"
+        }
+      }"#,
+    );
+    assert!(Bot::is_own_update(&message, &me));
+    let other = User {
+      id: ChatID(7),
+      ..me.clone()
+    };
+    assert!(!Bot::is_own_update(&message, &other));
+
+    let post = update(
+      r#"{
+        "update_id": 1,
+        "channel_post": {
+          "message_id": 2,
+          "date": 1,
+          "chat": {"id": -1005, "type": "channel", "title": "foo"},
+          "sender_chat": {"id": -1005, "type": "channel", "title": "foo"},
+          "author_signature": "foo",
+          "text": "bar"
+        }
+      }"#,
+    );
+    //a signature matching the name of the bot may belong to an admin
+    assert!(!Bot::is_own_update(&post, &me));
+  }
+
+  #[test]
+  fn test_remember_blocked_chats() {
+    let bot = Bot::builder("token")
+      .polling(false)
+      .remember_blocked_chats(true)
+      .build()
+      .unwrap();
+    assert!(bot.get_remember_blocked_chats());
+    bot.blocked_chats.lock().unwrap().chats.insert(ChatID(2));
+    assert_eq!(vec![ChatID(2)], bot.get_blocked_chats());
+    bot.clear_blocked_chats();
+    assert!(bot.get_blocked_chats().is_empty());
+  }
+
+  #[test]
+  fn test_runtime_builder() {
+    let bot = Bot::builder("token")
+      .polling(false)
+      .runtime_builder(tokio::runtime::Builder::new_current_thread())
+      .build()
+      .unwrap();
+    assert_eq!(
+      tokio::runtime::RuntimeFlavor::CurrentThread,
+      bot.runtime.handle().runtime_flavor()
+    );
+
+    assert!(Bot::builder("token")
+      .runtime_builder(tokio::runtime::Builder::new_current_thread())
+      .build()
+      .is_err());
+    let bot =
+      Bot::with_runtime_builder("token", tokio::runtime::Builder::new_current_thread()).unwrap();
+    assert!(!bot.is_polling());
+    assert!(bot.restart_polling().is_err());
+    assert!(!bot.is_polling());
+  }
+
+  #[test]
+  fn test_allowed_updates() {
+    let bot = Bot::builder("token")
+      .polling(false)
+      .allowed_updates(vec![UpdateFilter::Message])
+      .build()
+      .unwrap();
+    assert_eq!(Some(vec![UpdateFilter::Message]), bot.get_allowed_updates());
+    bot.set_allowed_updates(None);
+    assert_eq!(None, bot.get_allowed_updates());
+    let all = Bot::allowed_updates_tg(bot.get_allowed_updates().as_deref());
+    assert!(all.contains(&tg::AllowedUpdate::Message));
+    assert!(!all.contains(&tg::AllowedUpdate::ChatMember));
+    assert_eq!(
+      vec![tg::AllowedUpdate::Message],
+      Bot::allowed_updates_tg(Some(&[UpdateFilter::Message]))
+    );
+  }
+
+  #[test]
+  fn test_update_handlers() {
+    let handlers = UpdateHandlers::new()
+      .on(UpdateFilter::Message, |_, _| {})
+      .on(UpdateFilter::Query, |_, _| {})
+      .on(UpdateFilter::Message, |_, _| {});
+    assert_eq!(
+      vec![UpdateFilter::Query, UpdateFilter::Message],
+      handlers.allowed_updates()
+    );
+
+    let bot = Bot::builder("token").polling(false).build().unwrap();
+    bot.dispatch(handlers, |_, _| {}).stop();
+    assert_eq!(
+      Some(vec![UpdateFilter::Query, UpdateFilter::Message]),
+      bot.get_allowed_updates()
+    );
+  }
+
+  #[test]
+  fn test_token_from_env() {
+    std::env::set_var("TELEGRAM_BOT_TEST_TOKEN", "123:abc");
+    std::env::set_var("TELEGRAM_BOT_TEST_EMPTY_TOKEN", "");
+    assert_eq!(
+      "123:abc",
+      token_from_env(&["TELEGRAM_BOT_TEST_EMPTY_TOKEN", "TELEGRAM_BOT_TEST_TOKEN"]).unwrap()
+    );
+    assert!(token_from_env(&["TELEGRAM_BOT_TEST_MISSING_TOKEN"])
+      .is_err_and(|e| e.detail_message.contains("TELEGRAM_BOT_TEST_MISSING_TOKEN")));
+  }
+
+  #[test]
+  fn test_edited_channel_post() {
+    let update = serde_json::from_str::<tg::Update>(
+      r#"{
+        "update_id": 1,
+        "edited_channel_post": {
+          "message_id": 2,
+          "date": 1,
+          "edit_date": 3,
+          "chat": {"id": -1005, "type": "channel", "title": "foo", "username": "foo_news"},
+          "sender_chat": {"id": -1005, "type": "channel", "title": "foo", "username": "foo_news"},
+          "text": "bar"
+        }
+      }"#,
+    )
+    .unwrap();
+    assert!(Bot::is_known_kind(&update.kind));
+    let update = Bot::wrap_update(update.kind, UpdateSource::Polling).unwrap();
+    assert_eq!(UpdateSource::Polling, update.source);
+    assert!(update.is_edit());
+    assert_eq!(Some(ChatID(-1005)), update.chat_id());
+    assert_eq!(ChatID(-1005), update.user.id);
+    assert_eq!("foo", update.user.first_name);
+    assert_eq!(Some("foo_news"), update.user.user_name.as_deref());
+    assert_eq!(
+      Some(UpdateFilter::EditedChannelPost),
+      UpdateFilter::of(&update.kind)
+    );
+    let message = update.message().unwrap();
+    assert!(message.is_channel_post);
+    assert_eq!("bar", message.text);
+    assert_eq!(
+      Some(SystemTime::UNIX_EPOCH + Duration::from_secs(3)),
+      message.edit_timestamp
+    );
+  }
+
+  #[test]
+  fn test_var_args() {
+    let decorations = vec![Decoration::Bold, Decoration::Italic];
+    let style = Style::from_decorations(decorations.clone());
+    assert_eq!(style, Style::from_decorations(decorations.as_slice()));
+    assert_eq!(
+      style,
+      Style::from_decorations(Iter::new(decorations.iter().cloned()))
+    );
+    assert_eq!(
+      style,
+      Style::from_decorations(Iter::new([Decoration::Bold, Decoration::Italic]))
+    );
+    assert_eq!(
+      vec![Button::new("foo", "bar")],
+      [Button::new("foo", "bar")].as_slice().args()
+    );
+  }
+
+  #[test]
+  fn test_check_message_count() {
+    assert_eq!(
+      2,
+      check_message_count(&[MessageID(1), MessageID(2)], 100).unwrap()
+    );
+    assert!(check_message_count(&[], 100).is_err());
+    assert!(check_message_count(&[MessageID(1); 101], 100).is_err());
+  }
+
+  #[test]
+  fn test_builder() {
+    let bot = offline_bot(
+      Bot::builder("token")
+        .pool_max_idle_per_host(4)
+        .tcp_keepalive(Duration::from_secs(60))
+        .network_error_cooldown(Duration::from_secs(5))
+        .auto_ack_callbacks(true)
+        .ignore_own_updates(true)
+        .unknown_updates(true)
+        .reaction_limit(false)
+        .chat_rate_limit(crate::rate_limit::CHAT_RATE_LIMIT),
+    );
+    assert!(bot.is_polling());
+    assert_eq!(Duration::from_secs(5), bot.get_network_error_cooldown());
+    assert!(bot.get_auto_ack_callbacks());
+    assert!(bot.get_ignore_own_updates());
+    assert!(bot.get_unknown_updates());
+    assert!(!bot.get_reaction_limit());
+    assert_eq!(None, bot.get_rate_limit());
+    assert_eq!(
+      Some(crate::rate_limit::CHAT_RATE_LIMIT),
+      bot.get_chat_rate_limit()
+    );
+  }
+
+  #[test]
+  fn test_get_user_profile_photos_limit() {
+    let bot = Bot::builder("token").polling(false).build().unwrap();
+    for limit in [0, USER_PROFILE_PHOTOS_LIMIT + 1] {
+      assert!(bot
+        .get_user_profile_photos(1, 0, limit)
+        .is_err_and(|e| e.detail_message.contains("limit")));
+    }
+  }
+
+  #[test]
+  fn test_answer_shipping_query_validation() {
+    let bot = Bot::builder("token").polling(false).build().unwrap();
+    assert!(bot.answer_shipping_query("1", true, &[], None).is_err());
+    assert!(bot
+      .answer_shipping_query("1", false, &[], None)
+      .is_err_and(|e| e.detail_message.contains("error message")));
+  }
+
+  #[test]
+  fn test_default_renderer() {
+    let bot = Bot::builder("token")
+      .polling(false)
+      .default_renderer(MarkdownV2Renderer)
+      .build()
+      .unwrap();
+    let text = "<bold>foo</bold> 1.5";
+    assert_eq!(
+      "*foo* 1\\.5",
+      bot.send_message(1, text).preview().unwrap().text
+    );
+    assert_eq!(
+      "<b>foo</b> 1.5",
+      bot
+        .send_message(1, text)
+        .with_renderer(HtmlRenderer)
+        .preview()
+        .unwrap()
+        .text
+    );
+    bot.set_default_renderer(HtmlRenderer);
+    assert_eq!(
+      "<b>foo</b> 1.5",
+      bot.send_message(1, text).preview().unwrap().text
+    );
+  }
+
+  #[test]
+  fn test_to_user_id() {
+    assert_eq!(UserId(42), to_user_id(ChatID(42)).unwrap());
+    assert!(to_user_id(ChatID(-100123)).is_err());
+  }
+
+  #[test]
+  fn test_file_url() {
+    assert_eq!(
+      "https://api.telegram.org/file/bot1:token/photos/file_0.jpg",
+      file_url(
+        url::Url::parse("https://api.telegram.org").unwrap(),
+        "1:token",
+        "photos/file_0.jpg"
+      )
+    );
+    assert_eq!(
+      "https://example.com/telegram/file/bot1:token/file_0.jpg",
+      file_url(
+        url::Url::parse("https://example.com/telegram/").unwrap(),
+        "1:token",
+        "file_0.jpg"
+      )
+    );
+  }
+
+  #[test]
+  fn test_schedule_message() {
+    let bot = Bot::send_only("token").unwrap();
+    let scheduled = bot.schedule_message(1, "foo", Instant::now() + Duration::from_secs(60));
+    assert!(scheduled.is_pending());
+    assert!(scheduled.cancel());
+    assert!(!scheduled.is_pending());
+    assert!(scheduled.join().is_err());
+
+    //run a scheduled action without sending a message
+    let runtime = Runtime::new().unwrap();
+    let at = Instant::now() + Duration::from_secs(60);
+    let scheduled = schedule(&runtime, Arc::new(ManualClock::new()), at, || Ok(5));
+    while scheduled.is_pending() {
+      std::thread::yield_now();
+    }
+    assert!(!scheduled.cancel());
+    assert_eq!(5, scheduled.join().unwrap());
+  }
+
+  #[test]
+  fn test_delete_after() {
+    let sent = SentMessage {
+      chat_id: ChatID(1),
+      message_id: MessageID(2),
+      date: SystemTime::UNIX_EPOCH,
+      fell_back_to_plain: false,
+    };
+    let bot = offline_bot(Bot::builder("token").polling(false));
+    let deletion = sent.delete_after(&bot, Duration::from_secs(60));
+    assert!(deletion.cancel());
+    assert!(deletion.join().is_err());
+
+    //the bot can't reach telegram, so deleting fails right away with a manual clock
+    let bot = offline_bot(
+      Bot::builder("token")
+        .polling(false)
+        .clock(Arc::new(ManualClock::new())),
+    );
+    let deletion = sent.delete_after(&bot, Duration::from_secs(60));
+    assert_eq!(ErrorKind::Network, deletion.join().unwrap_err().kind);
+  }
+
+  #[test]
+  fn test_update_loop() {
+    let bot = offline_bot(Bot::builder("token"));
+    let update_loop = bot.on_update_or_error(|_, _| {}, |_, _| {});
+    assert!(update_loop.is_running());
+    update_loop.stop();
+  }
+
+  #[test]
+  fn test_send_only() {
+    let bot = offline_bot(Bot::builder("token").polling(false));
+    assert!(!bot.is_polling());
+    assert!(bot.poll_update().is_none());
+    assert!(bot.await_update().is_err());
+    bot.restart_polling().unwrap();
+    assert!(bot.is_polling());
+  }
+
+  #[test]
+  fn test_restart_polling() {
+    let bot = offline_bot(Bot::builder("token"));
+    assert!(bot.is_polling());
+    let previous = bot.polling.lock().unwrap().as_ref().unwrap().id();
+    bot.restart_polling().unwrap();
+    assert!(bot.is_polling());
+    assert_ne!(previous, bot.polling.lock().unwrap().as_ref().unwrap().id());
+  }
+
+  #[test]
+  fn test_await_update_stopped_polling() {
+    let bot = offline_bot(Bot::builder("token"));
+    bot.polling.lock().unwrap().as_ref().unwrap().abort();
+    assert!(bot.await_update().is_err());
+  }
+
+  #[test]
+  fn test_await_update_releases_receiver() {
+    let bot = offline_bot(Bot::builder("token"));
+    let waiting = bot.clone();
+    std::thread::spawn(move || waiting.await_update());
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(bot.poll_update().is_none());
   }
 }
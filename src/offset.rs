@@ -0,0 +1,56 @@
+use std::fmt::Debug;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::TelegramError;
+
+///persists the id of the last received update, so a bot continues polling after it when restarted
+pub trait OffsetStore: Debug + Send + Sync {
+  ///the id of the last received update, if one has been saved
+  fn load(&self) -> Option<i32>;
+
+  ///save the id of the last received update, called after each received batch of updates
+  fn save(&self, offset: i32) -> crate::Result<()>;
+}
+
+///stores the offset as text in a file
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FileOffsetStore {
+  path: PathBuf,
+}
+
+impl FileOffsetStore {
+  pub fn new<P>(path: P) -> Self
+  where
+    P: Into<PathBuf>,
+  {
+    Self { path: path.into() }
+  }
+}
+
+impl OffsetStore for FileOffsetStore {
+  fn load(&self) -> Option<i32> {
+    fs::read_to_string(&self.path).ok()?.trim().parse().ok()
+  }
+
+  fn save(&self, offset: i32) -> crate::Result<()> {
+    fs::write(&self.path, offset.to_string()).map_err(|e| {
+      TelegramError::new(format!("failed to save offset to {}", self.path.display())).with_cause(e)
+    })
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use crate::offset::{FileOffsetStore, OffsetStore};
+
+  #[test]
+  fn test_file_offset_store() {
+    let path = std::env::temp_dir().join(format!("telegram_bot_offset_{}", std::process::id()));
+    let store = FileOffsetStore::new(&path);
+    assert_eq!(None, store.load());
+    store.save(42).unwrap();
+    assert_eq!(Some(42), store.load());
+    std::fs::remove_file(path).unwrap();
+  }
+}
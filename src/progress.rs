@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use crate::error::ignore_not_modified;
+use crate::request::SentMessage;
+use crate::{Bot, ChatID, Result};
+
+///the minimum time between two edits of a [`ProgressMessage`], as telegram rate limits edits
+pub const MIN_EDIT_INTERVAL: Duration = Duration::from_secs(1);
+///the amount of chars of a rendered progress bar
+pub const PROGRESS_BAR_WIDTH: usize = 20;
+
+///a message showing a text and a progress bar, which is edited as the progress advances.
+///
+///Changes made faster than [`MIN_EDIT_INTERVAL`] are coalesced: they are only stored and sent with
+///the next change after the interval has passed, or with [`ProgressMessage::flush`].
+#[derive(Debug)]
+pub struct ProgressMessage {
+  bot: Bot,
+  message: SentMessage,
+  text: String,
+  progress: f32,
+  ///the rendered text of the last edit
+  sent: String,
+  last_edit: Instant,
+}
+
+impl ProgressMessage {
+  ///send a new progress message at 0%. The text may contain format tags.
+  pub fn send<I, S>(bot: &Bot, chat_id: I, text: S) -> Result<Self>
+  where
+    I: Into<ChatID>,
+    S: ToString,
+  {
+    let text = text.to_string();
+    let rendered = render_progress_message(&text, 0.0);
+    let message = bot.send_message(chat_id, &rendered).execute()?;
+    Ok(Self {
+      bot: bot.clone(),
+      message,
+      text,
+      progress: 0.0,
+      sent: rendered,
+      last_edit: Instant::now(),
+    })
+  }
+
+  ///set the progress as a fraction between `0.0` and `1.0`
+  pub fn set_progress(&mut self, progress: f32) -> Result<()> {
+    self.progress = progress.clamp(0.0, 1.0);
+    self.update(false)
+  }
+
+  pub fn set_text<S>(&mut self, text: S) -> Result<()>
+  where
+    S: ToString,
+  {
+    self.text = text.to_string();
+    self.update(false)
+  }
+
+  ///send coalesced changes, waiting for the [`MIN_EDIT_INTERVAL`] if necessary
+  pub fn flush(&mut self) -> Result<()> {
+    self.update(true)
+  }
+
+  ///the message this progress is shown in
+  pub fn message(&self) -> SentMessage {
+    self.message
+  }
+
+  fn update(&mut self, wait: bool) -> Result<()> {
+    let rendered = render_progress_message(&self.text, self.progress);
+    if rendered == self.sent {
+      return Ok(());
+    }
+    let elapsed = self.last_edit.elapsed();
+    if elapsed < MIN_EDIT_INTERVAL {
+      if !wait {
+        return Ok(());
+      }
+      std::thread::sleep(MIN_EDIT_INTERVAL - elapsed);
+    }
+    ignore_not_modified(self.message.edit(&self.bot, &rendered))?;
+    self.sent = rendered;
+    self.last_edit = Instant::now();
+    Ok(())
+  }
+}
+
+///render a progress bar like `██████░░░░ 60%` for a fraction between `0.0` and `1.0`
+pub fn render_progress_bar(progress: f32) -> String {
+  let progress = progress.clamp(0.0, 1.0);
+  let filled = (progress * PROGRESS_BAR_WIDTH as f32).round() as usize;
+  format!(
+    "{}{} {}%",
+    "█".repeat(filled),
+    "░".repeat(PROGRESS_BAR_WIDTH - filled),
+    (progress * 100.0).round()
+  )
+}
+
+fn render_progress_message(text: &str, progress: f32) -> String {
+  format!("{}\n<code>{}", text, render_progress_bar(progress))
+}
+
+#[cfg(test)]
+mod test {
+  use crate::progress::{render_progress_bar, PROGRESS_BAR_WIDTH};
+
+  #[test]
+  fn test_render_progress_bar() {
+    assert_eq!(
+      format!("{} 0%", "░".repeat(PROGRESS_BAR_WIDTH)),
+      render_progress_bar(0.0)
+    );
+    assert_eq!(
+      format!("{}{} 50%", "█".repeat(10), "░".repeat(10)),
+      render_progress_bar(0.5)
+    );
+    assert_eq!(
+      format!("{} 100%", "█".repeat(PROGRESS_BAR_WIDTH)),
+      render_progress_bar(1.5)
+    );
+  }
+}
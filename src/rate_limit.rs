@@ -0,0 +1,233 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+use crate::{ChatID, MUTEX_POISONED};
+
+///the messages telegram allows a bot to send to all chats together
+pub const GLOBAL_RATE_LIMIT: RateLimit = RateLimit::new(30, Duration::from_secs(1));
+///the messages telegram allows a bot to send to a single chat
+pub const CHAT_RATE_LIMIT: RateLimit = RateLimit::new(1, Duration::from_secs(1));
+///the messages telegram allows a bot to send to a single group, which can be used as
+///[`crate::Bot::set_chat_rate_limit`] for bots mostly talking in groups
+pub const GROUP_RATE_LIMIT: RateLimit = RateLimit::new(20, Duration::from_secs(60));
+
+///at most `max` messages within any `period`
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct RateLimit {
+  pub max: u32,
+  pub period: Duration,
+}
+
+impl RateLimit {
+  pub const fn new(max: u32, period: Duration) -> Self {
+    Self { max, period }
+  }
+}
+
+///delays sending messages to stay within a global and a per chat [`RateLimit`], see
+///[`crate::Bot::set_rate_limit`]
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+  state: Mutex<RateLimiterState>,
+  clock: Arc<dyn Clock>,
+}
+
+#[derive(Debug, Default)]
+struct RateLimiterState {
+  global_limit: Option<RateLimit>,
+  chat_limit: Option<RateLimit>,
+  ///the times messages have been or will be sent at, within the period of the limit
+  global: VecDeque<Instant>,
+  chats: HashMap<ChatID, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+  pub(crate) fn new(
+    global_limit: Option<RateLimit>,
+    chat_limit: Option<RateLimit>,
+    clock: Arc<dyn Clock>,
+  ) -> Self {
+    Self {
+      state: Mutex::new(RateLimiterState {
+        global_limit,
+        chat_limit,
+        ..RateLimiterState::default()
+      }),
+      clock,
+    }
+  }
+
+  pub(crate) fn global_limit(&self) -> Option<RateLimit> {
+    self.state.lock().expect(MUTEX_POISONED).global_limit
+  }
+
+  pub(crate) fn set_global_limit(&self, limit: Option<RateLimit>) {
+    let mut state = self.state.lock().expect(MUTEX_POISONED);
+    state.global_limit = limit;
+    state.global.clear();
+  }
+
+  pub(crate) fn chat_limit(&self) -> Option<RateLimit> {
+    self.state.lock().expect(MUTEX_POISONED).chat_limit
+  }
+
+  pub(crate) fn set_chat_limit(&self, limit: Option<RateLimit>) {
+    let mut state = self.state.lock().expect(MUTEX_POISONED);
+    state.chat_limit = limit;
+    state.chats.clear();
+  }
+
+  ///reserve the next slot to send a message to the chat at, which satisfies both limits, and
+  ///return how long to wait for it
+  pub(crate) fn acquire(&self, chat_id: ChatID) -> Duration {
+    let now = self.clock.now();
+    let mut state = self.state.lock().expect(MUTEX_POISONED);
+    let state = &mut *state;
+    if let Some(limit) = state.global_limit {
+      forget_expired(&mut state.global, limit, now);
+    }
+    if let Some(limit) = state.chat_limit {
+      //forget chats which haven't been sent to within the period
+      state
+        .chats
+        .retain(|_, sent| sent.back().is_some_and(|&time| time + limit.period > now));
+    }
+    let global = state.global_limit.map(|limit| (&state.global, limit));
+    let chat = state
+      .chat_limit
+      .map(|limit| (state.chats.get(&chat_id), limit));
+
+    //a slot delayed by one limit may collide with the slots of the other, so look for a slot
+    //satisfying both until it doesn't change anymore
+    let mut slot = now;
+    loop {
+      let mut next = slot;
+      if let Some((sent, limit)) = global {
+        next = earliest_slot(sent, limit, next);
+      }
+      if let Some((Some(sent), limit)) = chat {
+        next = earliest_slot(sent, limit, next);
+      }
+      if next == slot {
+        break;
+      }
+      slot = next;
+    }
+
+    if state.global_limit.is_some() {
+      insert_sorted(&mut state.global, slot);
+    }
+    if state.chat_limit.is_some() {
+      insert_sorted(state.chats.entry(chat_id).or_default(), slot);
+    }
+    slot - now
+  }
+
+  ///wait until a message may be sent to the chat
+  pub(crate) async fn wait(&self, chat_id: ChatID) {
+    let delay = self.acquire(chat_id);
+    if !delay.is_zero() {
+      self.clock.sleep(delay).await;
+    }
+  }
+}
+
+///drop the send times which left the period, as they can't limit messages sent from now on
+fn forget_expired(sent: &mut VecDeque<Instant>, limit: RateLimit, now: Instant) {
+  while sent.front().is_some_and(|&time| time + limit.period <= now) {
+    sent.pop_front();
+  }
+}
+
+///the earliest time from the given one on at which another message can be sent without exceeding
+///the limit in any period, given the sorted times messages have been or will be sent at
+fn earliest_slot(sent: &VecDeque<Instant>, limit: RateLimit, from: Instant) -> Instant {
+  if limit.max == 0 {
+    return from;
+  }
+  //a slot only becomes free when a sent message leaves the period
+  let candidates = std::iter::once(from).chain(
+    sent
+      .iter()
+      .map(|&time| time + limit.period)
+      .filter(|&time| time > from),
+  );
+  let mut candidates: Vec<Instant> = candidates.collect();
+  candidates.sort();
+  candidates
+    .into_iter()
+    .find(|&slot| fits(sent, limit, slot))
+    .unwrap_or(from)
+}
+
+///whether a message sent at the slot keeps every window of `max + 1` consecutive messages spanning
+///at least the period of the limit
+fn fits(sent: &VecDeque<Instant>, limit: RateLimit, slot: Instant) -> bool {
+  let max = limit.max as usize;
+  let index = sent.partition_point(|&time| time <= slot);
+  let times: Vec<Instant> = sent
+    .range(..index)
+    .copied()
+    .chain(std::iter::once(slot))
+    .chain(sent.range(index..).copied())
+    .collect();
+  let first = index.saturating_sub(max);
+  let last = index.min(times.len().saturating_sub(max + 1));
+  (first..=last)
+    .filter(|&start| start + max < times.len())
+    .all(|start| times[start + max] - times[start] >= limit.period)
+}
+
+///insert the time keeping the times sorted
+fn insert_sorted(sent: &mut VecDeque<Instant>, time: Instant) {
+  let index = sent.partition_point(|&other| other <= time);
+  sent.insert(index, time);
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::Arc;
+  use std::time::Duration;
+
+  use crate::clock::{Clock, ManualClock};
+  use crate::rate_limit::{RateLimit, RateLimiter};
+  use crate::ChatID;
+
+  #[test]
+  fn test_rate_limiter() {
+    let clock = Arc::new(ManualClock::new());
+    let limiter = RateLimiter::new(
+      Some(RateLimit::new(3, Duration::from_secs(1))),
+      Some(RateLimit::new(1, Duration::from_secs(1))),
+      clock.clone(),
+    );
+    //the chat limit applies per chat
+    assert_eq!(Duration::ZERO, limiter.acquire(ChatID(1)));
+    assert_eq!(Duration::ZERO, limiter.acquire(ChatID(2)));
+    assert_eq!(Duration::from_secs(1), limiter.acquire(ChatID(1)));
+    //the slot reserved by the chat limit doesn't count against the current period
+    assert_eq!(Duration::ZERO, limiter.acquire(ChatID(3)));
+    //the global limit is reached, the next period has room for two more messages
+    assert_eq!(Duration::from_secs(1), limiter.acquire(ChatID(4)));
+    assert_eq!(Duration::from_secs(1), limiter.acquire(ChatID(5)));
+    assert_eq!(Duration::from_secs(2), limiter.acquire(ChatID(6)));
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(Duration::from_secs(1), limiter.acquire(ChatID(2)));
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let start = clock.now();
+    runtime.block_on(limiter.wait(ChatID(2)));
+    assert_eq!(Duration::from_secs(2), clock.now() - start);
+  }
+
+  #[test]
+  fn test_disabled_rate_limiter() {
+    let limiter = RateLimiter::new(None, None, Arc::new(ManualClock::new()));
+    for _ in 0..100 {
+      assert_eq!(Duration::ZERO, limiter.acquire(ChatID(1)));
+    }
+  }
+}
@@ -0,0 +1,248 @@
+use std::fmt::Debug;
+
+use teloxide::types::{MessageEntity, ParseMode};
+
+use crate::format::{to_ansi, Component, Decoration};
+use crate::request::{text_len, to_entities, to_html};
+
+///converts components to a text telegram can display, see [`crate::request::SendMessage::with_renderer`]
+pub trait Renderer: Debug + Send + Sync {
+  fn render(&self, components: &[Component]) -> crate::Result<RenderedMessage>;
+}
+
+///a rendered text, with the parse mode or entities telegram needs to display its formatting
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RenderedMessage {
+  pub text: String,
+  pub parse_mode: Option<ParseMode>,
+  pub entities: Option<Vec<MessageEntity>>,
+  ///the length of the text as counted for the [`crate::request::MESSAGE_CHAR_LIMIT`]
+  pub char_count: usize,
+}
+
+impl RenderedMessage {
+  ///a text without formatting
+  pub fn plain<S>(text: S) -> Self
+  where
+    S: ToString,
+  {
+    let text = text.to_string();
+    Self {
+      char_count: text.encode_utf16().count(),
+      text,
+      parse_mode: None,
+      entities: None,
+    }
+  }
+}
+
+///renders telegram html, the default
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct HtmlRenderer;
+
+impl Renderer for HtmlRenderer {
+  fn render(&self, components: &[Component]) -> crate::Result<RenderedMessage> {
+    Ok(RenderedMessage {
+      text: to_html(components),
+      parse_mode: Some(ParseMode::Html),
+      entities: None,
+      char_count: text_len(components),
+    })
+  }
+}
+
+///renders telegram markdown v2, see [`to_markdown_v2`]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct MarkdownV2Renderer;
+
+impl Renderer for MarkdownV2Renderer {
+  fn render(&self, components: &[Component]) -> crate::Result<RenderedMessage> {
+    Ok(RenderedMessage {
+      text: to_markdown_v2(components),
+      parse_mode: Some(ParseMode::MarkdownV2),
+      entities: None,
+      char_count: text_len(components),
+    })
+  }
+}
+
+///renders the plain text with message entities, which avoids any escaping issues, see
+///[`to_entities`]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct EntityRenderer;
+
+impl Renderer for EntityRenderer {
+  fn render(&self, components: &[Component]) -> crate::Result<RenderedMessage> {
+    let (text, entities) = to_entities(components)?;
+    Ok(RenderedMessage {
+      text,
+      parse_mode: None,
+      entities: Some(entities),
+      char_count: text_len(components),
+    })
+  }
+}
+
+///renders ANSI escape codes for terminals, see [`to_ansi`]. Telegram shows the escape codes as
+///they are, so this is meant for custom sinks like logs.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct AnsiRenderer;
+
+impl Renderer for AnsiRenderer {
+  fn render(&self, components: &[Component]) -> crate::Result<RenderedMessage> {
+    Ok(RenderedMessage::plain(to_ansi(components)))
+  }
+}
+
+///render components as telegram markdown v2. Code spans can't contain other formatting in markdown,
+///so they are opened last.
+pub fn to_markdown_v2(components: &[Component]) -> String {
+  let mut rendered = MarkdownV2::default();
+  for component in components {
+    if component.is_empty() {
+      continue;
+    }
+    let mut tags = component.style.canonical_tags();
+    tags.sort_by_key(|tag| markdown_precedence(tag));
+    for tag in &tags {
+      let marker = match tag {
+        Decoration::Link(_) => "[",
+        decoration => markdown_marker(decoration),
+      };
+      rendered.push_marker(marker);
+    }
+    let is_code = tags.contains(&&Decoration::MonoSpace);
+    rendered.push_text(&if is_code {
+      escape_markdown_v2_code(&component.text)
+    } else {
+      escape_markdown_v2(&component.text)
+    });
+    for tag in tags.iter().rev() {
+      match tag {
+        Decoration::Link(link) => {
+          rendered.push_marker("](");
+          rendered.push_text(&escape_markdown_v2_link(link));
+          rendered.push_marker(")");
+        }
+        decoration => rendered.push_marker(markdown_marker(decoration)),
+      }
+    }
+  }
+  rendered.text
+}
+
+#[derive(Default)]
+struct MarkdownV2 {
+  text: String,
+  after_underscore: bool,
+}
+
+impl MarkdownV2 {
+  ///telegram reads `___` greedily as underline first, so markers starting with `_` right after one
+  ///ending with `_` are separated with `\r`, as suggested by the telegram docs
+  fn push_marker(&mut self, marker: &str) {
+    if self.after_underscore && marker.starts_with('_') {
+      self.text.push('\r');
+    }
+    self.text.push_str(marker);
+    self.after_underscore = marker.ends_with('_');
+  }
+
+  fn push_text(&mut self, text: &str) {
+    self.text.push_str(text);
+    self.after_underscore = false;
+  }
+}
+
+fn markdown_marker(decoration: &Decoration) -> &'static str {
+  match decoration {
+    Decoration::Bold => "*",
+    Decoration::Italic => "_",
+    Decoration::Underlined => "__",
+    Decoration::Strikethrough => "~",
+    Decoration::MonoSpace => "`",
+    Decoration::Spoiler => "||",
+    Decoration::Link(_) => "",
+  }
+}
+
+///the order decorations are opened in, as links can contain formatting but code can't
+fn markdown_precedence(decoration: &Decoration) -> u8 {
+  match decoration {
+    Decoration::Link(_) => 0,
+    Decoration::Spoiler => 1,
+    Decoration::Bold => 2,
+    Decoration::Underlined => 3,
+    Decoration::Italic => 4,
+    Decoration::Strikethrough => 5,
+    Decoration::MonoSpace => 6,
+  }
+}
+
+///escape all chars markdown v2 reserves for formatting
+pub fn escape_markdown_v2(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  for c in text.chars() {
+    if "_*[]()~`>#+-=|{}.!\\".contains(c) {
+      escaped.push('\\');
+    }
+    escaped.push(c);
+  }
+  escaped
+}
+
+fn escape_markdown_v2_code(text: &str) -> String {
+  text.replace('\\', "\\\\").replace('`', "\\`")
+}
+
+fn escape_markdown_v2_link(link: &str) -> String {
+  link.replace('\\', "\\\\").replace(')', "\\)")
+}
+
+#[cfg(test)]
+mod test {
+  use teloxide::types::ParseMode;
+
+  use crate::format::{parse, Component, Decoration};
+  use crate::render::{
+    escape_markdown_v2, to_markdown_v2, AnsiRenderer, EntityRenderer, HtmlRenderer, Renderer,
+  };
+
+  #[test]
+  fn test_to_markdown_v2() {
+    let markdown = |text: &str| to_markdown_v2(&parse(text).unwrap());
+    assert_eq!("foo *bar* 1\\.5", markdown("foo <bold>bar</bold> 1.5"));
+    assert_eq!("`a\\`b*c`", markdown("<code>a`b*c"));
+    assert_eq!(
+      "[*crab*](https://crab.rs/a\\)b)",
+      markdown("<link:https://crab.rs/a)b><bold>crab")
+    );
+    assert_eq!("__\r_foo_\r__", markdown("<italic><underline>foo"));
+    assert_eq!(
+      "_a_\r_b_",
+      to_markdown_v2(&[
+        Component::from("a").decorate(Decoration::Italic),
+        Component::from("b").decorate(Decoration::Italic),
+      ])
+    );
+    assert_eq!("\\_\\*\\\\", escape_markdown_v2("_*\\"));
+  }
+
+  #[test]
+  fn test_renderers() {
+    let components = parse("<bold>foo</bold> bar").unwrap();
+    let html = HtmlRenderer.render(&components).unwrap();
+    assert_eq!("<b>foo</b> bar", html.text);
+    assert_eq!(Some(ParseMode::Html), html.parse_mode);
+    assert_eq!(7, html.char_count);
+
+    let entities = EntityRenderer.render(&components).unwrap();
+    assert_eq!("foo bar", entities.text);
+    assert_eq!(None, entities.parse_mode);
+    assert_eq!(1, entities.entities.unwrap().len());
+
+    let ansi = AnsiRenderer.render(&components).unwrap();
+    assert_eq!("\x1b[1mfoo\x1b[0m bar", ansi.text);
+    assert_eq!(ansi.text.encode_utf16().count(), ansi.char_count);
+  }
+}
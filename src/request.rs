@@ -1,18 +1,62 @@
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use serde::Serialize;
 use teloxide::prelude::*;
 use teloxide::types::{
-  InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode, ReplyMarkup,
+  self as tg, FileId, InlineKeyboardButton, InlineKeyboardMarkup, MessageEntity, MessageEntityKind,
+  ParseMode, ReplyMarkup, ReplyParameters, ThreadId,
 };
+use teloxide::{ApiError, RequestError};
 use tokio::runtime::Runtime;
+use url::Url;
 
-use crate::error::{ErrorKind, TelegramError};
-use crate::format::{parse, Decoration};
+use crate::clock::{Clock, SystemClock};
+use crate::error::{ignore_message_not_found, is_ambiguous, ErrorKind, TelegramError};
+use crate::format::{
+  coalesce, decorate_links, is_disallowed, lint, normalize_line_breaks, parse, plain_text,
+  sanitize, tag_links, to_tags, Component, Decoration, Style,
+};
+use crate::rate_limit::RateLimiter;
+use crate::render::{EntityRenderer, HtmlRenderer, Renderer};
+use crate::retry::{retry_if, RetryPolicy};
 use crate::update::Message;
-use crate::{ChatID, VarArgs};
+use crate::{
+  from_tg_message_id, schedule, to_tg_chat_id, to_tg_message_id, ChatID, MessageID, ScheduleHandle,
+  VarArgs, MUTEX_POISONED,
+};
 
 pub const MESSAGE_CHAR_LIMIT: u32 = 4096;
 pub const QUERY_BYTE_LIMIT: u32 = 64;
+pub const DELETE_MESSAGES_LIMIT: u32 = 100;
+///the maximum amount of messages forwarded or copied at once
+pub const FORWARD_MESSAGES_LIMIT: u32 = 100;
+pub const PLACEHOLDER_CHAR_LIMIT: u32 = 64;
+pub const CAPTION_CHAR_LIMIT: u32 = 1024;
+pub const TOPIC_NAME_CHAR_LIMIT: u32 = 128;
+pub const POLL_QUESTION_CHAR_LIMIT: u32 = 300;
+pub const POLL_OPTION_CHAR_LIMIT: u32 = 100;
+pub const POLL_EXPLANATION_CHAR_LIMIT: u32 = 200;
+pub const INVITE_LINK_NAME_CHAR_LIMIT: u32 = 32;
+///the maximum amount of members which can join a chat via an invite link
+pub const INVITE_LINK_MEMBER_LIMIT: u32 = 99999;
+pub const INLINE_RESULT_ID_BYTE_LIMIT: u32 = 64;
+///the reactions a bot can set on a single message, as bots count as non-premium users, see
+///[`crate::Bot::set_reaction_limit`]
+pub const MESSAGE_REACTION_LIMIT: usize = 1;
+///the maximum amount of profile photos requested at once, see
+///[`crate::Bot::get_user_profile_photos`]
+pub const USER_PROFILE_PHOTOS_LIMIT: u32 = 100;
+pub const POLL_MIN_OPTIONS: usize = 2;
+pub const POLL_MAX_OPTIONS: usize = 10;
+///the maximum amount of buttons in a row of an inline keyboard
+pub const INLINE_BUTTONS_PER_ROW_LIMIT: usize = 8;
+///the maximum amount of buttons of an inline keyboard, across all rows
+pub const INLINE_BUTTONS_LIMIT: usize = 100;
+///the text appended to shortened messages, see [`truncate`]
+pub const ELLIPSIS: &str = "…";
 
 #[derive(Debug)]
 pub struct SendMessage {
@@ -20,24 +64,358 @@ pub struct SendMessage {
   send_to: ChatID,
   bot: Bot,
   runtime: Arc<Runtime>,
-  reply_to: Option<Message>,
+  reply_to: Option<MessageID>,
+  ///the chat of the message replied to, if it isn't the chat the message is sent to
+  reply_chat_id: Option<ChatID>,
+  allow_sending_without_reply: bool,
   buttons: Vec<Vec<Button>>,
+  options: SendOptions,
+  normalize_line_breaks: bool,
+  collapse_blank_lines: bool,
+  format: TextFormat,
+  ///renders the components of [`TextFormat::Tags`]
+  renderer: Arc<dyn Renderer>,
+  ///explicit entities of the text, bypassing the [`TextFormat`]
+  entities: Option<Vec<Entity>>,
+  coalesce: bool,
+  parse_links: bool,
+  warn_on_unescaped: bool,
+  sanitization: Sanitization,
+  fallback_to_plain: bool,
+  strip_invalid_links: bool,
+  ///pin the message after sending, without notification if true
+  pin: Option<bool>,
+  rate_limiter: Option<Arc<RateLimiter>>,
+  retry_policy: RetryPolicy,
+  ///the clock to wait between retries with
+  clock: Arc<dyn Clock>,
+  idempotency_key: Option<String>,
+  sent_keys: Option<Arc<SentKeys>>,
+  blocked_chats: Option<Arc<Mutex<BlockedChats>>>,
+  thread_id: Option<MessageID>,
+  ///the thread of the message replied to, used unless a thread is set explicitly
+  reply_thread_id: Option<MessageID>,
+  business_connection_id: Option<String>,
+  force_reply: Option<ForceReply>,
+  reply_keyboard: Option<ReplyKeyboard>,
+  link_preview: Option<LinkPreviewOptions>,
 }
 
 impl SendMessage {
-  pub(crate) fn new(text: String, send_to: ChatID, bot: Bot, runtime: Arc<Runtime>) -> Self {
+  pub(crate) fn new(
+    text: String,
+    send_to: ChatID,
+    bot: Bot,
+    runtime: Arc<Runtime>,
+    options: SendOptions,
+  ) -> Self {
     Self {
       text,
       send_to,
       bot,
       runtime,
       reply_to: None,
+      reply_chat_id: None,
+      allow_sending_without_reply: false,
       buttons: vec![],
+      options,
+      normalize_line_breaks: true,
+      collapse_blank_lines: false,
+      format: TextFormat::default(),
+      renderer: Arc::new(HtmlRenderer),
+      entities: None,
+      coalesce: true,
+      parse_links: false,
+      warn_on_unescaped: false,
+      sanitization: Sanitization::default(),
+      fallback_to_plain: false,
+      strip_invalid_links: false,
+      pin: None,
+      rate_limiter: None,
+      retry_policy: RetryPolicy::none(),
+      clock: Arc::new(SystemClock),
+      idempotency_key: None,
+      sent_keys: None,
+      blocked_chats: None,
+      thread_id: None,
+      reply_thread_id: None,
+      business_connection_id: None,
+      force_reply: None,
+      reply_keyboard: None,
+      link_preview: None,
     }
   }
 
+  ///wait for the rate limiter of the bot before sending
+  pub(crate) fn rate_limited(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+    self.rate_limiter = Some(rate_limiter);
+    self
+  }
+
+  ///remember the messages sent with idempotency keys in the given store
+  pub(crate) fn sent_keys(mut self, sent_keys: Arc<SentKeys>) -> Self {
+    self.sent_keys = Some(sent_keys);
+    self
+  }
+
+  ///skip sending to chats which blocked the bot before, and remember chats which do
+  pub(crate) fn blocked_chats(mut self, blocked_chats: Arc<Mutex<BlockedChats>>) -> Self {
+    self.blocked_chats = Some(blocked_chats);
+    self
+  }
+
+  ///skip sending if a message with the same key has been sent by this bot within the
+  ///[`IDEMPOTENCY_KEY_TTL`], returning the message sent before instead. If the response of a send
+  ///got lost, e.g. after a timeout, it is unknown whether the message has been sent, so retrying
+  ///with the key fails within the ttl instead of possibly sending the message twice. The keys are
+  ///only kept in memory of this process, so they are lost on restart and not shared with other
+  ///processes using the same bot.
+  pub fn idempotency_key<S>(mut self, key: S) -> Self
+  where
+    S: ToString,
+  {
+    self.idempotency_key = Some(key.to_string());
+    self
+  }
+
+  ///replace the keyboard of the user with custom buttons. Can't be combined with buttons or force
+  ///reply.
+  pub fn reply_keyboard(mut self, reply_keyboard: ReplyKeyboard) -> Self {
+    self.reply_keyboard = Some(reply_keyboard);
+    self
+  }
+
+  ///send the formatting of [`TextFormat::Tags`] as message entities instead of html, which avoids
+  ///any escaping issues
+  pub fn use_entities(mut self) -> Self {
+    self.renderer = Arc::new(EntityRenderer);
+    self
+  }
+
+  ///render the formatting of [`TextFormat::Tags`] with the given renderer instead of the default
+  ///of the bot, see [`crate::Bot::set_default_renderer`]
+  pub fn with_renderer<R>(mut self, renderer: R) -> Self
+  where
+    R: Renderer + 'static,
+  {
+    self.renderer = Arc::new(renderer);
+    self
+  }
+
+  pub(crate) fn renderer(mut self, renderer: Arc<dyn Renderer>) -> Self {
+    self.renderer = renderer;
+    self
+  }
+
+  ///retry sending with the given policy instead of the one of the bot, see
+  ///[`crate::Bot::set_retry_policy`]. Network errors are only retried if the connection failed, as
+  ///telegram may have received the message otherwise, and retrying would send it twice.
+  pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+    self.retry_policy = retry_policy;
+    self
+  }
+
+  pub(crate) fn retried(mut self, retry_policy: RetryPolicy, clock: Arc<dyn Clock>) -> Self {
+    self.retry_policy = retry_policy;
+    self.clock = clock;
+    self
+  }
+
+  ///replace the text with a plain text formatted by the given entities. The text is sent as it is,
+  ///ignoring the [`TextFormat`] and line break normalization, so the offsets of the entities stay
+  ///valid.
+  pub fn with_entities<S>(mut self, text: S, entities: Vec<Entity>) -> Self
+  where
+    S: ToString,
+  {
+    self.text = text.to_string();
+    self.entities = Some(entities);
+    self
+  }
+
+  ///make the client of the user reply to this message. Can't be combined with buttons or a reply
+  ///keyboard.
+  pub fn force_reply(self) -> Self {
+    self.force_reply_with(ForceReply::default())
+  }
+
+  ///like [`SendMessage::force_reply`], but with additional options
+  pub fn force_reply_with(mut self, force_reply: ForceReply) -> Self {
+    self.force_reply = Some(force_reply);
+    self
+  }
+
+  ///how the text is interpreted, see [`TextFormat`]
+  pub fn format(mut self, format: TextFormat) -> Self {
+    self.format = format;
+    self
+  }
+
+  ///send the text as it is with html parse mode, see [`TextFormat::RawHtml`]
+  pub fn raw_html(self) -> Self {
+    self.format(TextFormat::RawHtml)
+  }
+
+  ///convert `\r\n` and `\r` line endings to `\n`, enabled by default
+  pub fn normalize_line_breaks(mut self, normalize_line_breaks: bool) -> Self {
+    self.normalize_line_breaks = normalize_line_breaks;
+    self
+  }
+
+  ///collapse excessive blank lines when normalizing line breaks, see
+  ///[`crate::format::normalize_line_breaks`]
+  pub fn collapse_blank_lines(mut self, collapse_blank_lines: bool) -> Self {
+    self.collapse_blank_lines = collapse_blank_lines;
+    self
+  }
+
+  ///merge adjacent parts of the text with the same style before rendering, which avoids redundant
+  ///tags. Enabled by default, see [`crate::format::coalesce`].
+  pub fn coalesce(mut self, coalesce: bool) -> Self {
+    self.coalesce = coalesce;
+    self
+  }
+
+  ///turn bare urls in the text into links, see [`crate::format::decorate_links`]. Applies to
+  ///[`TextFormat::Tags`] and to [`TextFormat::Plain`], whose text is escaped so it can't be
+  ///misinterpreted as tags. Disabled by default.
+  pub fn parse_links(mut self, parse_links: bool) -> Self {
+    self.parse_links = parse_links;
+    self
+  }
+
+  ///how control and bidi chars in the text are handled, see [`Sanitization`]. Not applied to texts
+  ///with explicit entities, whose offsets would change.
+  pub fn sanitize(mut self, sanitization: Sanitization) -> Self {
+    self.sanitization = sanitization;
+    self
+  }
+
+  ///collect warnings for tags of [`TextFormat::Tags`] which look like interpolated text missing
+  ///[`crate::format::escape_tags`], see [`crate::format::lint`]. A developer aid for testing, the
+  ///warnings are reported by [`MessagePreview::warnings`] of [`SendMessage::preview`].
+  pub fn warn_on_unescaped(mut self) -> Self {
+    self.warn_on_unescaped = true;
+    self
+  }
+
+  ///if telegram fails to parse the formatting, send the text without formatting instead of failing.
+  ///Raw formats are sent as they are, only without parse mode. Whether this happened is reported by
+  ///[`SentMessage::fell_back_to_plain`].
+  pub fn fallback_to_plain(mut self) -> Self {
+    self.fallback_to_plain = true;
+    self
+  }
+
+  ///remove links of [`TextFormat::Tags`] whose target is invalid, keeping their text, instead of
+  ///failing. The removed targets are reported by [`MessagePreview::stripped_links`].
+  pub fn strip_invalid_links(mut self) -> Self {
+    self.strip_invalid_links = true;
+    self
+  }
+
+  ///pin the message after it has been sent, notifying all members of the chat.
+  ///
+  ///The message is sent first, so if pinning fails, e.g. because the bot lacks the rights to pin
+  ///messages, the message has already been sent. Execution then fails with
+  ///[`ErrorKind::PinFailed`], which contains the id of the sent message.
+  pub fn pin(mut self) -> Self {
+    self.pin = Some(false);
+    self
+  }
+
+  ///like [`SendMessage::pin`], but without notifying the members of the chat
+  pub fn pin_silent(mut self) -> Self {
+    self.pin = Some(true);
+    self
+  }
+
+  ///override all options, including the defaults of the bot
+  pub fn options(mut self, options: SendOptions) -> Self {
+    self.options = options;
+    self
+  }
+
+  pub fn disable_notification(mut self, disable_notification: bool) -> Self {
+    self.options.disable_notification = disable_notification;
+    self
+  }
+
+  pub fn disable_web_page_preview(mut self, disable_web_page_preview: bool) -> Self {
+    self.options.disable_web_page_preview = disable_web_page_preview;
+    self
+  }
+
+  ///control how the link preview is shown, e.g. for another url than the first one of the text.
+  ///Takes precedence over [`SendMessage::disable_web_page_preview`] and the default send options.
+  pub fn link_preview(mut self, link_preview: LinkPreviewOptions) -> Self {
+    self.link_preview = Some(link_preview);
+    self
+  }
+
+  pub fn protect_content(mut self, protect_content: bool) -> Self {
+    self.options.protect_content = protect_content;
+    self
+  }
+
+  ///send the message to a topic of a forum supergroup, or the thread of a channel comment section.
+  ///Takes precedence over the thread inferred by [`SendMessage::reply_to`], regardless of the order
+  ///of the calls.
+  pub fn thread(mut self, message_thread_id: MessageID) -> Self {
+    self.thread_id = Some(message_thread_id);
+    self
+  }
+
+  ///send the message on behalf of a business account, with the id of a business connection like
+  ///[`Message::business_connection_id`]. This requires a Telegram Business subscription of the
+  ///account, which connected the bot in its settings and allowed it to reply. Messages to chats
+  ///the account hasn't been active in for the last 24 hours are rejected.
+  pub fn business_connection<S>(mut self, business_connection_id: S) -> Self
+  where
+    S: ToString,
+  {
+    self.business_connection_id = Some(business_connection_id.to_string());
+    self
+  }
+
+  ///send the message to the same thread as the given message, if it was sent in one. Like
+  ///[`SendMessage::thread`], this takes precedence over the thread inferred by
+  ///[`SendMessage::reply_to`].
+  pub fn in_thread_of(mut self, message: &Message) -> Self {
+    self.thread_id = message.thread_id;
+    self
+  }
+
+  ///reply to a message of the chat the message is sent to. If the message has been sent in a
+  ///topic or thread, the reply is sent there too, unless another thread is set with
+  ///[`SendMessage::thread`] or [`SendMessage::in_thread_of`].
   pub fn reply_to(mut self, message: &Message) -> Self {
-    self.reply_to = Some(message.clone());
+    self.reply_to = Some(message.id);
+    self.reply_chat_id = None;
+    self.reply_thread_id = message.thread_id;
+    self
+  }
+
+  ///the thread the message is sent to, an explicit thread takes precedence over the inferred one
+  fn thread_id(&self) -> Option<MessageID> {
+    self.thread_id.or(self.reply_thread_id)
+  }
+
+  ///reply to a message in another chat, e.g. to quote a discussion from a different group
+  pub fn reply_to_in_chat<I>(mut self, message_id: MessageID, from_chat_id: I) -> Self
+  where
+    I: Into<ChatID>,
+  {
+    self.reply_to = Some(message_id);
+    self.reply_chat_id = Some(from_chat_id.into());
+    self.reply_thread_id = None;
+    self
+  }
+
+  ///send the message even if the message it replies to has been deleted, instead of failing.
+  ///Disabled by default.
+  pub fn allow_sending_without_reply(mut self, allow_sending_without_reply: bool) -> Self {
+    self.allow_sending_without_reply = allow_sending_without_reply;
     self
   }
 
@@ -70,11 +448,94 @@ impl SendMessage {
     self
   }
 
-  pub fn execute(&self) -> crate::Result<()> {
+  ///check the message without sending it: the format has to be parsable, the text, queries and
+  ///placeholders have to fit their limits and at most one kind of markup can be used.
+  ///[`SendMessage::execute`] runs the same checks before any network call.
+  pub fn validate(&self) -> crate::Result<()> {
+    self.preview().map(|_| ())
+  }
+
+  ///run all conversions and checks of [`SendMessage::execute`] without sending the message
+  pub fn preview(&self) -> crate::Result<MessagePreview> {
     //convert message text format
-    let text = to_html(&self.text)?;
+    let text = if self.entities.is_some() {
+      self.text.clone()
+    } else if self.normalize_line_breaks {
+      normalize_line_breaks(&self.text, self.collapse_blank_lines)
+    } else {
+      self.text.clone()
+    };
+    let text = match self.sanitization {
+      _ if self.entities.is_some() => text,
+      Sanitization::Off => text,
+      Sanitization::Strip => sanitize(&text),
+      Sanitization::Reject => {
+        if let Some((position, c)) = text.chars().enumerate().find(|&(_, c)| is_disallowed(c)) {
+          Err(TelegramError::new(format!(
+            "message contains the disallowed char U+{:04X} at char {}",
+            c as u32, position
+          )))?;
+        }
+        text
+      }
+    };
+    let (text, format) = match self.format {
+      TextFormat::Plain if self.parse_links => (tag_links(&text), TextFormat::Tags),
+      format => (text, format),
+    };
+    let mut warnings = vec![];
+    let mut stripped_links = vec![];
+    let (text, plain_text, char_count, parse_mode, entities) = match format {
+      _ if self.entities.is_some() => {
+        let code_units: Vec<u16> = text.encode_utf16().collect();
+        let entities = to_tg_entities(&code_units, self.entities.as_deref().unwrap_or_default())?;
+        (text.clone(), text, code_units.len(), None, Some(entities))
+      }
+      TextFormat::Tags => {
+        if self.warn_on_unescaped {
+          warnings = lint(&text);
+        }
+        let mut components = parse_text(&text)?;
+        if self.parse_links {
+          components = decorate_links(components);
+        }
+        if self.strip_invalid_links {
+          (components, stripped_links) = strip_invalid_links(components);
+        }
+        check_links(&components)?;
+        check_nesting(&components)?;
+        if self.coalesce {
+          components = coalesce(components);
+        }
+        let rendered = self.renderer.render(&components)?;
+        (
+          rendered.text,
+          plain_text(&components),
+          rendered.char_count,
+          rendered.parse_mode,
+          rendered.entities,
+        )
+      }
+      TextFormat::RawHtml => {
+        let char_count = text.encode_utf16().count();
+        (text.clone(), text, char_count, Some(ParseMode::Html), None)
+      }
+      TextFormat::RawMarkdownV2 => {
+        let char_count = text.encode_utf16().count();
+        (
+          text.clone(),
+          text,
+          char_count,
+          Some(ParseMode::MarkdownV2),
+          None,
+        )
+      }
+      TextFormat::Plain => {
+        let char_count = text.encode_utf16().count();
+        (text.clone(), text, char_count, None, None)
+      }
+    };
 
-    let char_count = text.chars().count();
     if char_count > MESSAGE_CHAR_LIMIT as usize {
       Err(
         TelegramError::new(format!(
@@ -84,128 +545,3060 @@ impl SendMessage {
         .of_kind(ErrorKind::MessageCharLimitReached),
       )?;
     }
+
+    if let Some(reply_chat_id) = self.reply_chat_id {
+      if reply_chat_id.0 == 0 || self.reply_to.is_none_or(|id| id.0 <= 0) {
+        Err(TelegramError::new(format!(
+          "invalid reply to message {:?} in chat {}",
+          self.reply_to, reply_chat_id
+        )))?;
+      }
+    }
+
+    let markups = [
+      !self.buttons.is_empty(),
+      self.force_reply.is_some(),
+      self.reply_keyboard.is_some(),
+    ];
+    if markups.into_iter().filter(|&used| used).count() > 1 {
+      Err(TelegramError::new(
+        "only one of buttons, force reply and reply keyboard can be used",
+      ))?;
+    }
+    let reply_markup = if let Some(force_reply) = &self.force_reply {
+      Some(ReplyMarkup::ForceReply(force_reply.to_tg()?))
+    } else if let Some(reply_keyboard) = &self.reply_keyboard {
+      Some(ReplyMarkup::Keyboard(reply_keyboard.to_tg()?))
+    } else if !self.buttons.is_empty() {
+      Some(ReplyMarkup::InlineKeyboard(inline_keyboard(&self.buttons)?))
+    } else {
+      None
+    };
+    let link_preview_options = match &self.link_preview {
+      Some(link_preview) => Some(link_preview.to_tg()?),
+      None if self.options.disable_web_page_preview => Some(
+        LinkPreviewOptions {
+          disabled: true,
+          ..LinkPreviewOptions::default()
+        }
+        .to_tg()?,
+      ),
+      None => None,
+    };
+
+    Ok(MessagePreview {
+      text,
+      plain_text,
+      char_count,
+      parse_mode,
+      entities,
+      reply_markup,
+      link_preview_options,
+      warnings,
+      stripped_links,
+    })
+  }
+
+  pub fn execute(&self) -> crate::Result<SentMessage> {
+    self.send_once().map(|(sent, _)| sent)
+  }
+
+  ///send the message and return it as the bot would receive it, e.g. to cache it with its text and
+  ///date. Fails if a message with the same [`SendMessage::idempotency_key`] has been sent before,
+  ///as only its id is known then.
+  pub fn execute_returning_message(&self) -> crate::Result<Message> {
+    let (sent, message) = self.send_once()?;
+    let message = message.ok_or_else(|| {
+      TelegramError::new(format!(
+        "message {} has been sent before with the same idempotency key, its content is unknown",
+        sent.message_id
+      ))
+    })?;
+    Message::from(&message).ok_or_else(|| {
+      TelegramError::new(format!(
+        "failed to convert sent message {}",
+        sent.message_id
+      ))
+    })
+  }
+
+  ///send the message unless it has been sent with the same idempotency key before, in which case
+  ///only the message sent before is known
+  fn send_once(&self) -> crate::Result<(SentMessage, Option<tg::Message>)> {
+    let reserved = match (&self.idempotency_key, &self.sent_keys) {
+      (Some(key), Some(sent_keys)) => match sent_keys.reserve(key)? {
+        Some(sent) => return Ok((sent, None)),
+        None => Some((key, sent_keys)),
+      },
+      _ => None,
+    };
+    let result = self.send().map(|(message, fell_back_to_plain)| {
+      let sent = SentMessage {
+        fell_back_to_plain,
+        ..self.sent_message(&message)
+      };
+      (sent, message)
+    });
+    if let Some((key, sent_keys)) = reserved {
+      sent_keys.complete(key, result.as_ref().map(|(sent, _)| *sent));
+    }
+    let (sent, message) = result?;
+    //the key is recorded before pinning, as the message has been sent even if pinning fails
+    self.pin_message(&message)?;
+    Ok((sent, Some(message)))
+  }
+
+  fn sent_message(&self, message: &tg::Message) -> SentMessage {
+    SentMessage {
+      chat_id: self.send_to,
+      message_id: from_tg_message_id(message.id),
+      date: to_system_time(message.date.timestamp()),
+      fell_back_to_plain: false,
+    }
+  }
+
+  ///send the message, returning whether it has been sent without formatting, see
+  ///[`SendMessage::fallback_to_plain`]
+  fn send(&self) -> crate::Result<(tg::Message, bool)> {
+    let preview = self.preview()?;
     let mut send_message = self
       .bot
-      .send_message(ChatId(self.send_to), &text)
-      .parse_mode(ParseMode::Html);
+      .send_message(to_tg_chat_id(self.send_to), preview.text);
+    send_message.parse_mode = preview.parse_mode;
+    send_message.entities = preview.entities;
+    send_message.reply_markup = preview.reply_markup;
+    send_message.message_thread_id = self.thread_id().map(|id| ThreadId(to_tg_message_id(id)));
+    send_message.business_connection_id = self
+      .business_connection_id
+      .clone()
+      .map(tg::BusinessConnectionId);
 
-    if let Some(reply_to) = &self.reply_to {
-      send_message.reply_to_message_id = Some(MessageId(reply_to.id));
+    if self.options.disable_notification {
+      send_message.disable_notification = Some(true);
+    }
+    send_message.link_preview_options = preview.link_preview_options;
+    if self.options.protect_content {
+      send_message.protect_content = Some(true);
     }
 
-    for button in self.buttons.iter().flat_map(|row| row) {
-      let bytes = button.query.len();
-      if bytes > QUERY_BYTE_LIMIT as usize {
-        Err(TelegramError::new(format!(
-          "query size ({} bytes) for button {:?} exceeds limit ({} bytes)",
-          bytes, button, QUERY_BYTE_LIMIT
-        )))?;
+    if let Some(reply_to) = self.reply_to {
+      let mut reply_parameters = ReplyParameters::new(to_tg_message_id(reply_to));
+      reply_parameters.chat_id = self
+        .reply_chat_id
+        .map(|chat_id| tg::Recipient::Id(to_tg_chat_id(chat_id)));
+      if self.allow_sending_without_reply {
+        reply_parameters.allow_sending_without_reply = Some(true);
       }
+      send_message.reply_parameters = Some(reply_parameters);
     }
-    send_message.reply_markup = Some(ReplyMarkup::InlineKeyboard(InlineKeyboardMarkup {
-      inline_keyboard: self
-        .buttons
-        .iter()
-        .map(|row| {
-          row
-            .iter()
-            .map(|button| InlineKeyboardButton::callback(&button.text, &button.query))
-            .collect()
-        })
-        .collect(),
-    }));
 
-    self
+    if let Some(blocked_chats) = &self.blocked_chats {
+      blocked_chats
+        .lock()
+        .expect(MUTEX_POISONED)
+        .check(self.send_to)?;
+    }
+    //a message which may have reached telegram is never sent again, as it would be duplicated
+    let (message, fell_back_to_plain) = self
       .runtime
-      .block_on(async move { send_message.await })
-      .map_err(|e| TelegramError::new("failed to send message").with_cause(e))?;
+      .block_on(retry_if(
+        &self.retry_policy,
+        &*self.clock,
+        || {
+          let send_message = &send_message;
+          let plain_text = &preview.plain_text;
+          async move {
+            if let Some(rate_limiter) = &self.rate_limiter {
+              rate_limiter.wait(self.send_to).await;
+            }
+            match send_message.clone().await {
+              Err(RequestError::Api(ApiError::CantParseEntities(_))) if self.fallback_to_plain => {
+                let mut plain_message = send_message.clone();
+                plain_message.text = plain_text.clone();
+                plain_message.parse_mode = None;
+                plain_message.entities = None;
+                plain_message.await.map(|message| (message, true))
+              }
+              result => result.map(|message| (message, false)),
+            }
+            .map_err(|e| {
+              TelegramError::new("failed to send message")
+                .of_kind(&e)
+                .with_cause(e)
+            })
+          }
+        },
+        |e| !is_ambiguous(e),
+      ))
+      .inspect_err(|e| match &self.blocked_chats {
+        Some(blocked_chats) if e.kind == ErrorKind::BotBlocked => blocked_chats
+          .lock()
+          .expect(MUTEX_POISONED)
+          .record(self.send_to),
+        _ => {}
+      })?;
+    Ok((message, fell_back_to_plain))
+  }
 
+  ///pin the sent message if requested, see [`SendMessage::pin`]
+  fn pin_message(&self, message: &tg::Message) -> crate::Result<()> {
+    if let Some(silent) = self.pin {
+      let message_id = from_tg_message_id(message.id);
+      let mut pin_message = self
+        .bot
+        .pin_chat_message(to_tg_chat_id(self.send_to), message.id);
+      pin_message.business_connection_id = self
+        .business_connection_id
+        .clone()
+        .map(tg::BusinessConnectionId);
+      if silent {
+        pin_message.disable_notification = Some(true);
+      }
+      self
+        .runtime
+        .block_on(async move { pin_message.await })
+        .map_err(|e| {
+          TelegramError::new(format!("sent message {} but failed to pin it", message_id))
+            .of_kind(ErrorKind::PinFailed(message_id))
+            .with_cause(e)
+        })?;
+    }
     Ok(())
   }
 }
 
-fn to_html(text: &str) -> Result<String, TelegramError> {
-  Ok(
-    parse(text)
-      .map_err(|e| TelegramError::new("invalid format tag").with_cause(e))?
-      .into_iter()
-      .map(|component| {
-        let mut opened_html_tags = vec![];
-        let mut part = String::new();
-        for tag in component.style.tags() {
-          part += &format!(
-            "<{}>",
-            match tag {
-              Decoration::Bold => {
-                opened_html_tags.push("b");
-                "b".to_owned()
-              }
-              Decoration::Italic => {
-                opened_html_tags.push("i");
-                "i".to_owned()
-              }
-              Decoration::Underlined => {
-                opened_html_tags.push("u");
-                "u".to_owned()
-              }
-              Decoration::MonoSpace => {
-                opened_html_tags.push("code");
-                "code".to_owned()
-              }
-              Decoration::Spoiler => {
-                opened_html_tags.push("tg-spoiler");
-                "tg-spoiler".to_owned()
-              }
-              Decoration::Link(link) => {
-                opened_html_tags.push("a");
-                format!("a href=\"{}\"", link)
-              }
-            }
-          );
-        }
-        part += &component
-          .text
-          .replace('&', "&amp;")
-          .replace('<', "&lt;")
-          .replace('>', "&gt;");
-        for tag in opened_html_tags {
-          part += &format!("</{}>", tag);
-        }
-        part
-      })
-      .collect::<Vec<_>>()
-      .join(""),
-  )
+///how long a message sent with an idempotency key is remembered, see
+///[`SendMessage::idempotency_key`]
+pub const IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(10 * 60);
+
+///the messages sent with idempotency keys within the [`IDEMPOTENCY_KEY_TTL`]
+#[derive(Debug)]
+pub(crate) struct SentKeys {
+  ///the state of each key with the time it has been reserved or completed
+  keys: Mutex<HashMap<String, (KeyState, Instant)>>,
+  clock: Arc<dyn Clock>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-pub struct Button {
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum KeyState {
+  Sending,
+  Sent(SentMessage),
+  ///sending failed, but telegram may have received the message anyway, see [`is_ambiguous`]
+  Unknown,
+}
+
+impl SentKeys {
+  pub(crate) fn new(clock: Arc<dyn Clock>) -> Self {
+    Self {
+      keys: Mutex::new(HashMap::new()),
+      clock,
+    }
+  }
+
+  ///the message sent with the key before, or [`None`] if the key has been reserved for sending.
+  ///Fails while another message with the key is being sent, and if it is unknown whether a message
+  ///with the key has been sent. Keys of sends which never completed, e.g. because the sending
+  ///thread panicked, are released after the [`IDEMPOTENCY_KEY_TTL`] like all others.
+  fn reserve(&self, key: &str) -> crate::Result<Option<SentMessage>> {
+    let now = self.clock.now();
+    let mut keys = self.keys.lock().expect(MUTEX_POISONED);
+    keys.retain(|_, (_, time)| *time + IDEMPOTENCY_KEY_TTL > now);
+    match keys.get(key) {
+      Some((KeyState::Sent(sent), _)) => Ok(Some(*sent)),
+      Some((KeyState::Sending, _)) => Err(TelegramError::new(format!(
+        "a message with idempotency key '{}' is already being sent",
+        key
+      ))),
+      Some((KeyState::Unknown, _)) => Err(TelegramError::new(format!(
+        "telegram may have received the message with idempotency key '{}' before sending it failed",
+        key
+      ))),
+      None => {
+        keys.insert(key.to_owned(), (KeyState::Sending, now));
+        Ok(None)
+      }
+    }
+  }
+
+  ///record the message sent with a reserved key, or release the key if sending failed before
+  ///telegram received the message. Otherwise the key stays reserved until the
+  ///[`IDEMPOTENCY_KEY_TTL`] has passed.
+  fn complete(&self, key: &str, result: Result<SentMessage, &TelegramError>) {
+    let mut keys = self.keys.lock().expect(MUTEX_POISONED);
+    let state = match result {
+      Ok(sent) => KeyState::Sent(sent),
+      Err(e) if is_ambiguous(e) => KeyState::Unknown,
+      Err(_) => {
+        keys.remove(key);
+        return;
+      }
+    };
+    keys.insert(key.to_owned(), (state, self.clock.now()));
+  }
+}
+
+///the chats which blocked the bot, see [`crate::Bot::set_remember_blocked_chats`]
+#[derive(Debug, Default)]
+pub(crate) struct BlockedChats {
+  pub(crate) enabled: bool,
+  pub(crate) chats: HashSet<ChatID>,
+}
+
+impl BlockedChats {
+  ///fail with [`ErrorKind::BotBlocked`] if the chat is known to have blocked the bot
+  fn check(&self, chat_id: ChatID) -> crate::Result<()> {
+    if self.enabled && self.chats.contains(&chat_id) {
+      Err(
+        TelegramError::new(format!(
+          "chat {} has blocked the bot before, the message hasn't been sent",
+          chat_id
+        ))
+        .of_kind(ErrorKind::BotBlocked),
+      )?;
+    }
+    Ok(())
+  }
+
+  fn record(&mut self, chat_id: ChatID) {
+    if self.enabled {
+      self.chats.insert(chat_id);
+    }
+  }
+}
+
+///a message as it would be sent by [`SendMessage::execute`], see [`SendMessage::preview`]
+#[derive(Debug, Clone)]
+pub struct MessagePreview {
+  ///the converted text, e.g. html for [`TextFormat::Tags`]
   pub text: String,
-  pub query: String,
+  ///the length of the text as counted for the [`MESSAGE_CHAR_LIMIT`]
+  pub char_count: usize,
+  pub parse_mode: Option<ParseMode>,
+  pub entities: Option<Vec<MessageEntity>>,
+  ///the text without any formatting, see [`SendMessage::fallback_to_plain`]
+  pub plain_text: String,
+  ///the validated buttons, force reply or reply keyboard
+  pub reply_markup: Option<ReplyMarkup>,
+  pub link_preview_options: Option<tg::LinkPreviewOptions>,
+  ///tags which possibly should have been escaped, see [`SendMessage::warn_on_unescaped`]
+  pub warnings: Vec<String>,
+  ///the invalid link targets removed, see [`SendMessage::strip_invalid_links`]
+  pub stripped_links: Vec<String>,
 }
 
-impl Button {
-  pub fn new<T, Q>(text: T, query: Q) -> Self
+///a handle to a message sent by this bot, which can be used to edit or delete the message later
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct SentMessage {
+  pub chat_id: ChatID,
+  pub message_id: MessageID,
+  ///when telegram received the message, with a precision of seconds
+  pub date: SystemTime,
+  ///whether the message has been sent without formatting as telegram failed to parse it, see
+  ///[`SendMessage::fallback_to_plain`]
+  pub fell_back_to_plain: bool,
+}
+
+impl SentMessage {
+  ///replace the text of the message, which may contain format tags
+  pub fn edit<S>(&self, bot: &crate::Bot, text: S) -> crate::Result<()>
   where
-    T: ToString,
-    Q: ToString,
+    S: AsRef<str>,
   {
-    Self {
-      text: text.to_string(),
-      query: query.to_string(),
+    bot.edit_message_text(self.chat_id, self.message_id, text)
+  }
+
+  pub fn delete(&self, bot: &crate::Bot) -> crate::Result<()> {
+    bot.delete_message(self.chat_id, self.message_id)
+  }
+
+  ///delete the message once the given time has passed, e.g. for short notices. The deletion is
+  ///scheduled like [`crate::Bot::schedule_message`], so the message persists if the process exits
+  ///before, and can be cancelled with the returned handle. Messages which have already been deleted
+  ///otherwise count as deleted.
+  pub fn delete_after(&self, bot: &crate::Bot, ttl: Duration) -> ScheduleHandle<()> {
+    let sent = *self;
+    let deleting_bot = bot.clone();
+    schedule(
+      &bot.runtime,
+      bot.clock.clone(),
+      bot.clock.now() + ttl,
+      move || ignore_message_not_found(sent.delete(&deleting_bot)),
+    )
+  }
+}
+
+///convert a unix timestamp as sent by telegram
+pub(crate) fn to_system_time(timestamp: i64) -> SystemTime {
+  UNIX_EPOCH + Duration::from_secs(timestamp.max(0) as u64)
+}
+
+fn parse_text(text: &str) -> Result<Vec<Component>, TelegramError> {
+  parse(text).map_err(|e| TelegramError::new("invalid format tag").with_cause(e))
+}
+
+///render a media caption containing format tags to html, checking the [`CAPTION_CHAR_LIMIT`]
+pub fn caption_html(caption: &str) -> crate::Result<String> {
+  render_html(caption, CAPTION_CHAR_LIMIT, "caption")
+}
+
+///render a message text containing format tags to html, checking the [`MESSAGE_CHAR_LIMIT`]
+pub fn message_html(text: &str) -> crate::Result<String> {
+  render_html(text, MESSAGE_CHAR_LIMIT, "message")
+}
+
+fn render_html(text: &str, char_limit: u32, name: &str) -> crate::Result<String> {
+  let components = parse_text(&normalize_line_breaks(text, false))?;
+  check_links(&components)?;
+  check_nesting(&components)?;
+  let char_count = text_len(&components);
+  if char_count > char_limit as usize {
+    Err(
+      TelegramError::new(format!(
+        "{} char count ({}) exceeds limit ({})",
+        name, char_count, char_limit
+      ))
+      .of_kind(ErrorKind::MessageCharLimitReached),
+    )?;
+  }
+  Ok(to_html(&components))
+}
+
+///a caption ready to send, formatted with html or with entities
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct RenderedCaption {
+  pub(crate) text: String,
+  pub(crate) parse_mode: Option<ParseMode>,
+  pub(crate) entities: Option<Vec<MessageEntity>>,
+}
+
+///render a media caption containing format tags, or a plain caption formatted by explicit
+///entities, checking the [`CAPTION_CHAR_LIMIT`]
+pub(crate) fn render_caption(
+  caption: &str,
+  entities: Option<&[Entity]>,
+) -> crate::Result<RenderedCaption> {
+  let Some(entities) = entities else {
+    return Ok(RenderedCaption {
+      text: caption_html(caption)?,
+      parse_mode: Some(ParseMode::Html),
+      entities: None,
+    });
+  };
+  let code_units: Vec<u16> = caption.encode_utf16().collect();
+  if code_units.len() > CAPTION_CHAR_LIMIT as usize {
+    Err(
+      TelegramError::new(format!(
+        "caption char count ({}) exceeds limit ({})",
+        code_units.len(),
+        CAPTION_CHAR_LIMIT
+      ))
+      .of_kind(ErrorKind::MessageCharLimitReached),
+    )?;
+  }
+  Ok(RenderedCaption {
+    text: caption.to_owned(),
+    parse_mode: None,
+    entities: Some(to_tg_entities(&code_units, entities)?),
+  })
+}
+
+///convert explicit entities of a text given as utf-16 code units, see [`Entity::to_tg`]
+fn to_tg_entities(text: &[u16], entities: &[Entity]) -> crate::Result<Vec<MessageEntity>> {
+  entities
+    .iter()
+    .enumerate()
+    .map(|(index, entity)| {
+      entity
+        .to_tg(text)
+        .map_err(|e| TelegramError::new(format!("invalid entity at index {}", index)).with_cause(e))
+    })
+    .collect()
+}
+
+///split a text with format tags into texts with format tags, each not exceeding `limit` chars as
+///counted by [`text_len`]. Decorations are closed at the end of each part and reopened in the next
+///one. Styled spans are moved to the next part instead of being split if they fit into it, and
+///texts are split after whitespace where possible. Fails if a single char is longer than the
+///limit.
+pub fn split_message(text: &str, limit: usize) -> crate::Result<Vec<String>> {
+  if limit == 0 {
+    Err(TelegramError::new(
+      "the limit to split a message has to be positive",
+    ))?;
+  }
+  let mut parts: Vec<Vec<Component>> = vec![];
+  let mut part: Vec<Component> = vec![];
+  let mut part_len = 0;
+  for mut component in coalesce(parse_text(text)?) {
+    loop {
+      let len = text_len(std::slice::from_ref(&component));
+      if part_len + len <= limit {
+        part_len += len;
+        part.push(component);
+        break;
+      }
+      if !part.is_empty() && len <= limit && component.is_styled() {
+        parts.push(std::mem::take(&mut part));
+        part_len = 0;
+        continue;
+      }
+      let split_at = split_index(&component.text, limit - part_len);
+      if split_at == 0 {
+        if part.is_empty() {
+          Err(TelegramError::new(format!(
+            "the limit ({}) to split a message is less than the length of '{}'",
+            limit,
+            component.text.chars().next().unwrap_or_default()
+          )))?;
+        }
+        parts.push(std::mem::take(&mut part));
+        part_len = 0;
+        continue;
+      }
+      let (first, rest) = component.text.split_at(split_at);
+      part.push(Component::from(first).style(component.style.clone()));
+      parts.push(std::mem::take(&mut part));
+      part_len = 0;
+      component.text = rest.to_owned();
     }
   }
+  if !part.is_empty() || parts.is_empty() {
+    parts.push(part);
+  }
+  Ok(parts.iter().map(|part| to_tags(part)).collect())
 }
 
-#[cfg(test)]
-mod test {
-  use crate::request::to_html;
+///shorten a text containing format tags to at most `max_len` chars as counted by [`text_len`],
+///including the appended [`ELLIPSIS`]. The text is cut after the last whitespace or line break
+///that fits, or within a word if there is none, and decorations open at the cut are closed before
+///the ellipsis. Texts which fit are returned unshortened, in the syntax of [`to_tags`].
+pub fn truncate(text: &str, max_len: usize) -> crate::Result<String> {
+  let components = coalesce(parse_text(text)?);
+  if text_len(&components) <= max_len {
+    return Ok(to_tags(&components));
+  }
+  let mut remaining =
+    max_len
+      .checked_sub(ELLIPSIS.encode_utf16().count())
+      .ok_or(TelegramError::new(format!(
+        "the limit to truncate a message ({}) is too small for an ellipsis",
+        max_len
+      )))?;
+  let mut truncated: Vec<Component> = vec![];
+  for mut component in components {
+    let len = text_len(std::slice::from_ref(&component));
+    if len <= remaining {
+      remaining -= len;
+      truncated.push(component);
+      continue;
+    }
+    let split_at = split_index(&component.text, remaining);
+    component.text.truncate(split_at);
+    truncated.push(component);
+    break;
+  }
+  if let Some(last) = truncated.last_mut() {
+    last.text.truncate(last.text.trim_end().len());
+  }
+  truncated.push(Component::from(ELLIPSIS));
+  Ok(to_tags(&coalesce(truncated)))
+}
+
+///the byte index to split a text at so the first part has at most `max_len` utf-16 code units,
+///preferably after whitespace. Only 0 if not even the first char fits.
+fn split_index(text: &str, max_len: usize) -> usize {
+  let mut len = 0;
+  let mut end = 0;
+  let mut after_whitespace = None;
+  for (index, c) in text.char_indices() {
+    len += c.len_utf16();
+    if len > max_len {
+      break;
+    }
+    end = index + c.len_utf8();
+    if c.is_whitespace() {
+      after_whitespace = Some(end);
+    }
+  }
+  after_whitespace.unwrap_or(end)
+}
+
+///the length of the text as counted by telegram for the [`MESSAGE_CHAR_LIMIT`]: formatting is not
+///included and the length is measured in utf-16 code units.
+pub fn text_len(components: &[Component]) -> usize {
+  components
+    .iter()
+    .map(|component| component.text.encode_utf16().count())
+    .sum()
+}
+
+///estimate the length of the html the components are rendered to. Like telegram, the length is
+///measured in utf-16 code units.
+pub fn html_len(components: &[Component]) -> usize {
+  to_html(components).encode_utf16().count()
+}
+
+///convert the components to their plain text and the message entities describing their
+///formatting. Offsets and lengths of entities are measured in utf-16 code units.
+pub fn to_entities(components: &[Component]) -> crate::Result<(String, Vec<MessageEntity>)> {
+  let mut text = String::new();
+  let mut entities: Vec<MessageEntity> = vec![];
+  let mut offset = 0;
+  for component in components {
+    let length = component.text.encode_utf16().count();
+    if length == 0 {
+      continue;
+    }
+    for decoration in component.style.canonical_tags() {
+      let kind = match decoration {
+        Decoration::Bold => MessageEntityKind::Bold,
+        Decoration::Italic => MessageEntityKind::Italic,
+        Decoration::Underlined => MessageEntityKind::Underline,
+        Decoration::Strikethrough => MessageEntityKind::Strikethrough,
+        Decoration::MonoSpace => MessageEntityKind::Code,
+        Decoration::Spoiler => MessageEntityKind::Spoiler,
+        Decoration::Link(link) => MessageEntityKind::TextLink {
+          url: Url::parse(link).map_err(|e| {
+            TelegramError::new(format!("invalid link target '{}'", link)).with_cause(e)
+          })?,
+        },
+      };
+      //continue entities of the previous component instead of starting a new one
+      match entities
+        .iter_mut()
+        .find(|entity| entity.kind == kind && entity.offset + entity.length == offset)
+      {
+        Some(entity) => entity.length += length,
+        None => entities.push(MessageEntity::new(kind, offset, length)),
+      }
+    }
+    text += &component.text;
+    offset += length;
+  }
+  Ok((text, entities))
+}
+
+///convert a text with entities received from telegram to components, the reverse of
+///[`to_entities`]. Entities which can't be expressed with a [`Decoration`] are left out.
+pub fn from_entities(text: &str, entities: &[MessageEntity]) -> Vec<Component> {
+  let decorations: Vec<(usize, usize, Decoration)> = entities
+    .iter()
+    .filter_map(|entity| {
+      let decoration = match &entity.kind {
+        MessageEntityKind::Bold => Decoration::Bold,
+        MessageEntityKind::Italic => Decoration::Italic,
+        MessageEntityKind::Underline => Decoration::Underlined,
+        MessageEntityKind::Strikethrough => Decoration::Strikethrough,
+        MessageEntityKind::Code | MessageEntityKind::Pre { .. } => Decoration::MonoSpace,
+        MessageEntityKind::Spoiler => Decoration::Spoiler,
+        MessageEntityKind::TextLink { url } => Decoration::link(url),
+        _ => return None,
+      };
+      Some((entity.offset, entity.offset + entity.length, decoration))
+    })
+    .collect();
+  let mut components: Vec<Component> = vec![];
+  let mut offset = 0;
+  for c in text.chars() {
+    let style = Style::from_decorations(
+      decorations
+        .iter()
+        .filter(|(start, end, _)| (*start..*end).contains(&offset))
+        .map(|(_, _, decoration)| decoration.clone())
+        .collect::<Vec<_>>(),
+    );
+    match components.last_mut() {
+      Some(last) if last.style == style => last.text.push(c),
+      _ => components.push(Component::from(c).style(style)),
+    }
+    offset += c.len_utf16();
+  }
+  components
+}
+
+///check that the link targets of all components are valid urls, as telegram rejects the whole
+///message otherwise. The error names the index of the offending component.
+pub fn check_links(components: &[Component]) -> crate::Result<()> {
+  for (index, component) in components.iter().enumerate() {
+    for decoration in component.style.tags() {
+      if let Decoration::Link(link) = decoration {
+        Url::parse(link).map_err(|e| {
+          TelegramError::new(format!(
+            "invalid link target '{}' of component {}",
+            link, index
+          ))
+          .with_cause(e)
+        })?;
+      }
+    }
+  }
+  Ok(())
+}
+
+///remove the link decorations [`check_links`] would reject from the components, keeping their text,
+///and return the removed targets
+fn strip_invalid_links(components: Vec<Component>) -> (Vec<Component>, Vec<String>) {
+  let mut stripped = vec![];
+  let components = components
+    .into_iter()
+    .map(|component| {
+      let is_invalid = |decoration: &Decoration| match decoration {
+        Decoration::Link(link) => Url::parse(link).is_err(),
+        _ => false,
+      };
+      if !component.style.tags().iter().any(is_invalid) {
+        return component;
+      }
+      let mut decorations = vec![];
+      for decoration in component.style.tags() {
+        match decoration {
+          Decoration::Link(link) if is_invalid(decoration) => stripped.push(link.clone()),
+          decoration => decorations.push(decoration.clone()),
+        }
+      }
+      component.style(Style::from_decorations(decorations))
+    })
+    .collect();
+  (components, stripped)
+}
+
+///check that no component combines decorations telegram can't nest. Mono-space text can't contain
+///or be part of any other formatting, which telegram would drop or reject otherwise. The error
+///names the index of the offending component.
+pub fn check_nesting(components: &[Component]) -> crate::Result<()> {
+  for (index, component) in components.iter().enumerate() {
+    let tags = component.style.tags();
+    if component.is_empty() || !tags.contains(&Decoration::MonoSpace) {
+      continue;
+    }
+    if let Some(other) = tags.iter().find(|tag| **tag != Decoration::MonoSpace) {
+      Err(TelegramError::new(format!(
+        "component {} combines {} with {}, which telegram can't nest",
+        index,
+        Decoration::MonoSpace.name(),
+        other.name()
+      )))?;
+    }
+  }
+  Ok(())
+}
+
+pub(crate) fn to_html(components: &[Component]) -> String {
+  components
+    .iter()
+    .map(|component| {
+      let mut opened_html_tags = vec![];
+      let mut part = String::new();
+      for tag in component.style.canonical_tags() {
+        part += &format!(
+          "<{}>",
+          match tag {
+            Decoration::Bold => {
+              opened_html_tags.push("b");
+              "b".to_owned()
+            }
+            Decoration::Italic => {
+              opened_html_tags.push("i");
+              "i".to_owned()
+            }
+            Decoration::Underlined => {
+              opened_html_tags.push("u");
+              "u".to_owned()
+            }
+            Decoration::Strikethrough => {
+              opened_html_tags.push("s");
+              "s".to_owned()
+            }
+            Decoration::MonoSpace => {
+              opened_html_tags.push("code");
+              "code".to_owned()
+            }
+            Decoration::Spoiler => {
+              opened_html_tags.push("tg-spoiler");
+              "tg-spoiler".to_owned()
+            }
+            Decoration::Link(link) => {
+              opened_html_tags.push("a");
+              format!("a href=\"{}\"", link)
+            }
+          }
+        );
+      }
+      part += &component
+        .text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+      for tag in opened_html_tags.into_iter().rev() {
+        part += &format!("</{}>", tag);
+      }
+      part
+    })
+    .collect::<Vec<_>>()
+    .join("")
+}
+
+///formatting of a part of a text, see [`SendMessage::with_entities`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Entity {
+  ///the start of the entity in utf-16 code units, as counted by telegram
+  pub offset: usize,
+  ///the length of the entity in utf-16 code units
+  pub length: usize,
+  pub kind: EntityKind,
+}
+
+impl Entity {
+  pub fn new(offset: usize, length: usize, kind: EntityKind) -> Self {
+    Self {
+      offset,
+      length,
+      kind,
+    }
+  }
+
+  ///convert the entity of a text given as utf-16 code units, checking that it is within the text
+  ///and doesn't split a char encoded as surrogate pair, like most emojis
+  fn to_tg(&self, text: &[u16]) -> crate::Result<MessageEntity> {
+    let end = self.offset + self.length;
+    if self.length == 0 || end > text.len() {
+      Err(TelegramError::new(format!(
+        "entity {:?} covering the utf-16 code units {}..{} exceeds the text of {} code units",
+        self,
+        self.offset,
+        end,
+        text.len()
+      )))?;
+    }
+    let splits_char = |index: usize| {
+      text
+        .get(index)
+        .is_some_and(|unit| (0xDC00..=0xDFFF).contains(unit))
+    };
+    if splits_char(self.offset) || splits_char(end) {
+      Err(TelegramError::new(format!(
+        "entity {:?} covering the utf-16 code units {}..{} splits a char",
+        self, self.offset, end
+      )))?;
+    }
+    let kind = match &self.kind {
+      EntityKind::Bold => MessageEntityKind::Bold,
+      EntityKind::Italic => MessageEntityKind::Italic,
+      EntityKind::Underlined => MessageEntityKind::Underline,
+      EntityKind::Strikethrough => MessageEntityKind::Strikethrough,
+      EntityKind::Spoiler => MessageEntityKind::Spoiler,
+      EntityKind::MonoSpace => MessageEntityKind::Code,
+      EntityKind::Pre { language } => MessageEntityKind::Pre {
+        language: language.clone(),
+      },
+      EntityKind::Blockquote => MessageEntityKind::Blockquote,
+      EntityKind::Link(url) => MessageEntityKind::TextLink {
+        url: Url::parse(url).map_err(|e| {
+          TelegramError::new(format!("invalid link target '{}'", url)).with_cause(e)
+        })?,
+      },
+    };
+    Ok(MessageEntity::new(kind, self.offset, self.length))
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum EntityKind {
+  Bold,
+  Italic,
+  Underlined,
+  Strikethrough,
+  Spoiler,
+  MonoSpace,
+  ///a code block, optionally highlighted for the given programming language
+  Pre {
+    language: Option<String>,
+  },
+  Blockquote,
+  ///a link to the given url, e.g. `tg://user?id=<user id>` to mention a user without a username
+  Link(String),
+}
+
+///how [`SendMessage`] handles chars removed by [`crate::format::sanitize`], see
+///[`crate::format::is_disallowed`]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum Sanitization {
+  ///send the text as it is
+  #[default]
+  Off,
+  ///remove the chars
+  Strip,
+  ///fail if the text contains any of the chars
+  Reject,
+}
+
+///how the text of a message is interpreted
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum TextFormat {
+  ///the tag syntax of this crate, see [`crate::format::parse`]
+  #[default]
+  Tags,
+  ///telegram html, sent as it is. The char limit is checked against the text including markup.
+  RawHtml,
+  ///telegram markdown v2, sent as it is. The char limit is checked against the text including
+  ///markup.
+  RawMarkdownV2,
+  ///text without any formatting
+  Plain,
+}
+
+///options applicable to each sent message, see [`crate::Bot::set_default_send_options`]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct SendOptions {
+  ///send the message silently, so users receive a notification without sound
+  pub disable_notification: bool,
+  ///don't show a preview for links in the message
+  pub disable_web_page_preview: bool,
+  ///protect the message from being forwarded and saved
+  pub protect_content: bool,
+}
+
+///a file to send, either already known to telegram or uploaded by the bot
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum InputFile {
+  ///the id of a file already stored on the telegram servers
+  Id(String),
+  ///an http url telegram downloads the file from
+  Url(String),
+  ///a local file which gets uploaded
+  Path(PathBuf),
+  Memory(Vec<u8>),
+}
+
+impl InputFile {
+  pub fn id<S>(file_id: S) -> Self
+  where
+    S: ToString,
+  {
+    Self::Id(file_id.to_string())
+  }
+
+  pub fn to_tg(&self) -> crate::Result<tg::InputFile> {
+    Ok(match self {
+      Self::Id(id) => tg::InputFile::file_id(FileId(id.clone())),
+      Self::Url(url) => tg::InputFile::url(
+        Url::parse(url).map_err(|e| TelegramError::new("invalid file url").with_cause(e))?,
+      ),
+      Self::Path(path) => tg::InputFile::file(path.clone()),
+      Self::Memory(data) => tg::InputFile::memory(data.clone()),
+    })
+  }
+}
+
+///an audio file displayed in the music player, see [`crate::Bot::send_audio`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Audio {
+  pub file: InputFile,
+  ///a caption containing format tags, see [`caption_html`]
+  pub caption: Option<String>,
+  ///explicit entities of the caption, which is sent as plain text then
+  pub caption_entities: Option<Vec<Entity>>,
+  pub title: Option<String>,
+  pub performer: Option<String>,
+  pub duration: Option<Duration>,
+}
+
+impl Audio {
+  ///an audio file in `.mp3` or `.m4a` format
+  pub fn new(file: InputFile) -> Self {
+    Self {
+      file,
+      caption: None,
+      caption_entities: None,
+      title: None,
+      performer: None,
+      duration: None,
+    }
+  }
+
+  pub fn caption<S>(mut self, caption: S) -> Self
+  where
+    S: ToString,
+  {
+    self.caption = Some(caption.to_string());
+    self
+  }
+
+  ///replace the caption with a plain text formatted by the given entities, like
+  ///[`SendMessage::with_entities`]
+  pub fn with_entities<S>(mut self, caption: S, entities: Vec<Entity>) -> Self
+  where
+    S: ToString,
+  {
+    self.caption = Some(caption.to_string());
+    self.caption_entities = Some(entities);
+    self
+  }
+
+  pub fn title<S>(mut self, title: S) -> Self
+  where
+    S: ToString,
+  {
+    self.title = Some(title.to_string());
+    self
+  }
+
+  pub fn performer<S>(mut self, performer: S) -> Self
+  where
+    S: ToString,
+  {
+    self.performer = Some(performer.to_string());
+    self
+  }
+
+  pub fn duration(mut self, duration: Duration) -> Self {
+    self.duration = Some(duration);
+    self
+  }
+}
+
+///a video, see [`crate::Bot::send_video`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Video {
+  pub file: InputFile,
+  ///a caption containing format tags, see [`caption_html`]
+  pub caption: Option<String>,
+  ///explicit entities of the caption, which is sent as plain text then
+  pub caption_entities: Option<Vec<Entity>>,
+  pub width: Option<u32>,
+  pub height: Option<u32>,
+  pub duration: Option<Duration>,
+  ///cover the video with a spoiler animation
+  pub spoiler: bool,
+  ///a jpeg thumbnail of at most 320x320 pixels, ignored for [`InputFile::Id`] videos
+  pub thumbnail: Option<InputFile>,
+}
+
+impl Video {
+  ///a video in `.mp4` format
+  pub fn new(file: InputFile) -> Self {
+    Self {
+      file,
+      caption: None,
+      caption_entities: None,
+      width: None,
+      height: None,
+      duration: None,
+      spoiler: false,
+      thumbnail: None,
+    }
+  }
+
+  pub fn caption<S>(mut self, caption: S) -> Self
+  where
+    S: ToString,
+  {
+    self.caption = Some(caption.to_string());
+    self
+  }
+
+  ///replace the caption with a plain text formatted by the given entities, like
+  ///[`SendMessage::with_entities`]
+  pub fn with_entities<S>(mut self, caption: S, entities: Vec<Entity>) -> Self
+  where
+    S: ToString,
+  {
+    self.caption = Some(caption.to_string());
+    self.caption_entities = Some(entities);
+    self
+  }
+
+  pub fn size(mut self, width: u32, height: u32) -> Self {
+    self.width = Some(width);
+    self.height = Some(height);
+    self
+  }
+
+  pub fn duration(mut self, duration: Duration) -> Self {
+    self.duration = Some(duration);
+    self
+  }
+
+  pub fn spoiler(mut self, spoiler: bool) -> Self {
+    self.spoiler = spoiler;
+    self
+  }
+
+  pub fn thumbnail(mut self, thumbnail: InputFile) -> Self {
+    self.thumbnail = Some(thumbnail);
+    self
+  }
+}
+
+///a photo, video or document replacing the media of a message, see
+///[`crate::Bot::edit_message_media`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct MediaItem {
+  pub kind: MediaKind,
+  pub file: InputFile,
+  ///a caption containing format tags, see [`caption_html`]
+  pub caption: Option<String>,
+  ///explicit entities of the caption, which is sent as plain text then
+  pub caption_entities: Option<Vec<Entity>>,
+  ///cover the media with a spoiler animation, ignored for documents
+  pub spoiler: bool,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum MediaKind {
+  Photo,
+  Video,
+  Document,
+}
+
+impl MediaItem {
+  pub fn new(kind: MediaKind, file: InputFile) -> Self {
+    Self {
+      kind,
+      file,
+      caption: None,
+      caption_entities: None,
+      spoiler: false,
+    }
+  }
+
+  pub fn photo(file: InputFile) -> Self {
+    Self::new(MediaKind::Photo, file)
+  }
+
+  pub fn video(file: InputFile) -> Self {
+    Self::new(MediaKind::Video, file)
+  }
+
+  pub fn document(file: InputFile) -> Self {
+    Self::new(MediaKind::Document, file)
+  }
+
+  pub fn caption<S>(mut self, caption: S) -> Self
+  where
+    S: ToString,
+  {
+    self.caption = Some(caption.to_string());
+    self
+  }
+
+  ///replace the caption with a plain text formatted by the given entities, like
+  ///[`SendMessage::with_entities`]
+  pub fn with_entities<S>(mut self, caption: S, entities: Vec<Entity>) -> Self
+  where
+    S: ToString,
+  {
+    self.caption = Some(caption.to_string());
+    self.caption_entities = Some(entities);
+    self
+  }
+
+  pub fn spoiler(mut self, spoiler: bool) -> Self {
+    self.spoiler = spoiler;
+    self
+  }
+
+  pub(crate) fn to_tg(&self) -> crate::Result<tg::InputMedia> {
+    let file = self.file.to_tg()?;
+    let caption = self
+      .caption
+      .as_deref()
+      .map(|caption| render_caption(caption, self.caption_entities.as_deref()))
+      .transpose()?;
+    let parse_mode = caption.as_ref().and_then(|caption| caption.parse_mode);
+    let (caption, caption_entities) = match caption {
+      Some(caption) => (Some(caption.text), caption.entities),
+      None => (None, None),
+    };
+    Ok(match self.kind {
+      MediaKind::Photo => {
+        let mut photo = tg::InputMediaPhoto::new(file);
+        photo.caption = caption;
+        photo.parse_mode = parse_mode;
+        photo.caption_entities = caption_entities;
+        photo.has_spoiler = self.spoiler;
+        tg::InputMedia::Photo(photo)
+      }
+      MediaKind::Video => {
+        let mut video = tg::InputMediaVideo::new(file);
+        video.caption = caption;
+        video.parse_mode = parse_mode;
+        video.caption_entities = caption_entities;
+        video.has_spoiler = self.spoiler;
+        tg::InputMedia::Video(video)
+      }
+      MediaKind::Document => {
+        let mut document = tg::InputMediaDocument::new(file);
+        document.caption = caption;
+        document.parse_mode = parse_mode;
+        document.caption_entities = caption_entities;
+        tg::InputMedia::Document(document)
+      }
+    })
+  }
+}
+
+///the file extensions accepted for voice messages, which have to be encoded with OPUS in an OGG
+///container
+pub const VOICE_FILE_EXTENSIONS: [&str; 3] = ["ogg", "oga", "opus"];
+
+///check that a local voice file has one of the [`VOICE_FILE_EXTENSIONS`]. Other sources can't be
+///checked before sending.
+pub fn check_voice_file(file: &InputFile) -> crate::Result<()> {
+  if let InputFile::Path(path) = file {
+    let extension = path
+      .extension()
+      .and_then(|extension| extension.to_str())
+      .map(str::to_lowercase);
+    if !extension.is_some_and(|extension| VOICE_FILE_EXTENSIONS.contains(&extension.as_str())) {
+      Err(TelegramError::new(format!(
+        "voice messages have to be OGG files encoded with OPUS, got {}",
+        path.display()
+      )))?;
+    }
+  }
+  Ok(())
+}
+
+///what members without administrator rights are allowed to do in a group, see
+///[`crate::Bot::set_chat_permissions`]. The default allows nothing.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct ChatPermissions {
+  pub send_messages: bool,
+  ///send audios, documents, photos, videos, video notes and voice notes
+  pub send_media: bool,
+  pub send_polls: bool,
+  ///send stickers, animations and games and use inline bots
+  pub send_other_messages: bool,
+  pub add_web_page_previews: bool,
+  pub change_info: bool,
+  pub invite_users: bool,
+  pub pin_messages: bool,
+  pub manage_topics: bool,
+}
+
+impl ChatPermissions {
+  pub fn all() -> Self {
+    Self {
+      send_messages: true,
+      send_media: true,
+      send_polls: true,
+      send_other_messages: true,
+      add_web_page_previews: true,
+      change_info: true,
+      invite_users: true,
+      pin_messages: true,
+      manage_topics: true,
+    }
+  }
+
+  pub fn to_tg(self) -> tg::ChatPermissions {
+    let mut permissions = tg::ChatPermissions::empty();
+    for (allowed, permission) in [
+      (self.send_messages, tg::ChatPermissions::SEND_MESSAGES),
+      (self.send_media, tg::ChatPermissions::SEND_MEDIA_MESSAGES),
+      (self.send_polls, tg::ChatPermissions::SEND_POLLS),
+      (
+        self.send_other_messages,
+        tg::ChatPermissions::SEND_OTHER_MESSAGES,
+      ),
+      (
+        self.add_web_page_previews,
+        tg::ChatPermissions::ADD_WEB_PAGE_PREVIEWS,
+      ),
+      (self.change_info, tg::ChatPermissions::CHANGE_INFO),
+      (self.invite_users, tg::ChatPermissions::INVITE_USERS),
+      (self.pin_messages, tg::ChatPermissions::PIN_MESSAGES),
+      (self.manage_topics, tg::ChatPermissions::MANAGE_TOPICS),
+    ] {
+      permissions.set(permission, allowed);
+    }
+    permissions
+  }
+}
+
+///the colors telegram allows for the icon of a forum topic
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum TopicColor {
+  #[default]
+  Blue,
+  Yellow,
+  Violet,
+  Green,
+  Rose,
+  Red,
+}
+
+impl TopicColor {
+  pub fn rgb(self) -> u32 {
+    match self {
+      Self::Blue => 0x6FB9F0,
+      Self::Yellow => 0xFFD67E,
+      Self::Violet => 0xCB86DB,
+      Self::Green => 0x8EEE98,
+      Self::Rose => 0xFF93B2,
+      Self::Red => 0xFB6F5F,
+    }
+  }
+}
+
+///check that a topic name has 1 up to [`TOPIC_NAME_CHAR_LIMIT`] chars
+pub fn check_topic_name(name: &str) -> crate::Result<()> {
+  let char_count = name.chars().count();
+  if char_count == 0 || char_count > TOPIC_NAME_CHAR_LIMIT as usize {
+    Err(TelegramError::new(format!(
+      "topic name char count ({}) has to be between 1 and {}",
+      char_count, TOPIC_NAME_CHAR_LIMIT
+    )))?;
+  }
+  Ok(())
+}
+
+///check that none of the reactions is empty and, if `limit` is set, at most
+///[`MESSAGE_REACTION_LIMIT`] reactions are set, see [`crate::Bot::set_message_reaction`]
+pub fn check_reactions(emojis: &[&str], limit: bool) -> crate::Result<()> {
+  if limit && emojis.len() > MESSAGE_REACTION_LIMIT {
+    Err(TelegramError::new(format!(
+      "bots can set at most {} reaction(s) per message, got {}",
+      MESSAGE_REACTION_LIMIT,
+      emojis.len()
+    )))?;
+  }
+  if emojis.iter().any(|emoji| emoji.is_empty()) {
+    Err(TelegramError::new("reaction emoji is empty"))?;
+  }
+  Ok(())
+}
+
+///options of a poll, see [`crate::Bot::send_poll`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PollConfig {
+  ///hide who voted for which option, enabled by default
+  pub is_anonymous: bool,
+  pub kind: PollKind,
+  ///the index of the correct option, required for quizzes
+  pub correct_option_id: Option<u8>,
+  ///shown when a user picks a wrong answer in a quiz, may contain format tags. At most
+  ///[`POLL_EXPLANATION_CHAR_LIMIT`] chars.
+  pub explanation: Option<String>,
+}
+
+impl Default for PollConfig {
+  fn default() -> Self {
+    Self {
+      is_anonymous: true,
+      kind: PollKind::default(),
+      correct_option_id: None,
+      explanation: None,
+    }
+  }
+}
+
+impl PollConfig {
+  ///a quiz with the given correct option
+  pub fn quiz(correct_option_id: u8) -> Self {
+    Self {
+      kind: PollKind::Quiz,
+      correct_option_id: Some(correct_option_id),
+      ..Self::default()
+    }
+  }
+
+  pub fn explanation<S>(mut self, explanation: S) -> Self
+  where
+    S: ToString,
+  {
+    self.explanation = Some(explanation.to_string());
+    self
+  }
+
+  pub fn is_anonymous(mut self, is_anonymous: bool) -> Self {
+    self.is_anonymous = is_anonymous;
+    self
+  }
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum PollKind {
+  #[default]
+  Regular,
+  ///a poll with exactly one correct option
+  Quiz,
+}
+
+impl PollKind {
+  pub(crate) fn to_tg(self) -> tg::PollType {
+    match self {
+      PollKind::Regular => tg::PollType::Regular,
+      PollKind::Quiz => tg::PollType::Quiz,
+    }
+  }
+}
+
+///check the question, the amount and length of the options and the config of a poll, see
+///[`crate::Bot::send_poll`]
+pub fn check_poll<O>(question: &str, options: &[O], config: &PollConfig) -> crate::Result<()>
+where
+  O: AsRef<str>,
+{
+  let char_count = question.chars().count();
+  if char_count == 0 || char_count > POLL_QUESTION_CHAR_LIMIT as usize {
+    Err(TelegramError::new(format!(
+      "poll question char count ({}) has to be between 1 and {}",
+      char_count, POLL_QUESTION_CHAR_LIMIT
+    )))?;
+  }
+  if options.len() < POLL_MIN_OPTIONS || options.len() > POLL_MAX_OPTIONS {
+    Err(TelegramError::new(format!(
+      "poll option count ({}) has to be between {} and {}",
+      options.len(),
+      POLL_MIN_OPTIONS,
+      POLL_MAX_OPTIONS
+    )))?;
+  }
+  for option in options {
+    let char_count = option.as_ref().chars().count();
+    if char_count == 0 || char_count > POLL_OPTION_CHAR_LIMIT as usize {
+      Err(TelegramError::new(format!(
+        "char count ({}) of poll option '{}' has to be between 1 and {}",
+        char_count,
+        option.as_ref(),
+        POLL_OPTION_CHAR_LIMIT
+      )))?;
+    }
+  }
+  match (config.kind, config.correct_option_id) {
+    (PollKind::Quiz, None) => Err(TelegramError::new("a quiz requires a correct option"))?,
+    (PollKind::Regular, Some(_)) => {
+      Err(TelegramError::new("only quizzes can have a correct option"))?
+    }
+    (_, Some(id)) if id as usize >= options.len() => Err(TelegramError::new(format!(
+      "correct option {} of a quiz with {} options is out of range",
+      id,
+      options.len()
+    )))?,
+    _ => {}
+  }
+  Ok(())
+}
+
+///render a poll explanation containing format tags to html, checking the
+///[`POLL_EXPLANATION_CHAR_LIMIT`]
+pub fn explanation_html(explanation: &str) -> crate::Result<String> {
+  render_html(explanation, POLL_EXPLANATION_CHAR_LIMIT, "poll explanation")
+}
+
+///options of an invite link, see [`crate::Bot::create_chat_invite_link`]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct InviteLinkConfig {
+  ///shown to the administrators of the chat, at most [`INVITE_LINK_NAME_CHAR_LIMIT`] chars
+  pub name: Option<String>,
+  pub expire_date: Option<SystemTime>,
+  ///how many users can join via the link, 1 up to [`INVITE_LINK_MEMBER_LIMIT`]
+  pub member_limit: Option<u32>,
+  ///whether users joining via the link have to be approved by an administrator, which can't be
+  ///combined with a member limit
+  pub creates_join_request: bool,
+}
+
+impl InviteLinkConfig {
+  pub fn name<S>(mut self, name: S) -> Self
+  where
+    S: ToString,
+  {
+    self.name = Some(name.to_string());
+    self
+  }
+
+  pub fn expire_date(mut self, expire_date: SystemTime) -> Self {
+    self.expire_date = Some(expire_date);
+    self
+  }
+
+  pub fn member_limit(mut self, member_limit: u32) -> Self {
+    self.member_limit = Some(member_limit);
+    self
+  }
+
+  pub fn creates_join_request(mut self, creates_join_request: bool) -> Self {
+    self.creates_join_request = creates_join_request;
+    self
+  }
+}
+
+///check the name and member limit of an invite link, and that it expires after `now`, see
+///[`crate::Bot::create_chat_invite_link`]
+pub fn check_invite_link(config: &InviteLinkConfig, now: SystemTime) -> crate::Result<()> {
+  if let Some(name) = &config.name {
+    let char_count = name.chars().count();
+    if char_count > INVITE_LINK_NAME_CHAR_LIMIT as usize {
+      Err(TelegramError::new(format!(
+        "invite link name char count ({}) exceeds limit of {}",
+        char_count, INVITE_LINK_NAME_CHAR_LIMIT
+      )))?;
+    }
+  }
+  if let Some(member_limit) = config.member_limit {
+    if member_limit == 0 || member_limit > INVITE_LINK_MEMBER_LIMIT {
+      Err(TelegramError::new(format!(
+        "invite link member limit ({}) has to be between 1 and {}",
+        member_limit, INVITE_LINK_MEMBER_LIMIT
+      )))?;
+    }
+    if config.creates_join_request {
+      Err(TelegramError::new(
+        "invite links creating join requests can't have a member limit",
+      ))?;
+    }
+  }
+  if config
+    .expire_date
+    .is_some_and(|expire_date| expire_date <= now)
+  {
+    Err(TelegramError::new(
+      "invite link expire date has to be in the future",
+    ))?;
+  }
+  Ok(())
+}
+
+///a result of an inline or web app query, shown as an article which sends a message when chosen,
+///see [`crate::Bot::answer_web_app_query`]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct InlineResult {
+  ///identifies the result, 1 up to [`INLINE_RESULT_ID_BYTE_LIMIT`] bytes
+  pub id: String,
+  pub title: String,
+  ///the text of the sent message, which may contain format tags
+  pub text: String,
+  pub description: Option<String>,
+  pub thumbnail_url: Option<String>,
+  ///the buttons attached to the sent message
+  pub buttons: Vec<Vec<Button>>,
+}
+
+impl InlineResult {
+  pub fn new<I, T, S>(id: I, title: T, text: S) -> Self
+  where
+    I: ToString,
+    T: ToString,
+    S: ToString,
+  {
+    Self {
+      id: id.to_string(),
+      title: title.to_string(),
+      text: text.to_string(),
+      ..Self::default()
+    }
+  }
+
+  ///shown below the title
+  pub fn description<S>(mut self, description: S) -> Self
+  where
+    S: ToString,
+  {
+    self.description = Some(description.to_string());
+    self
+  }
+
+  pub fn thumbnail_url<S>(mut self, thumbnail_url: S) -> Self
+  where
+    S: ToString,
+  {
+    self.thumbnail_url = Some(thumbnail_url.to_string());
+    self
+  }
+
+  pub fn buttons(mut self, buttons: Vec<Vec<Button>>) -> Self {
+    self.buttons = buttons;
+    self
+  }
+
+  ///check the id, title, text and buttons of the result and convert it
+  pub(crate) fn to_tg(&self) -> crate::Result<tg::InlineQueryResult> {
+    if self.id.is_empty() || self.id.len() > INLINE_RESULT_ID_BYTE_LIMIT as usize {
+      Err(TelegramError::new(format!(
+        "inline result id byte count ({}) has to be 1 up to {}",
+        self.id.len(),
+        INLINE_RESULT_ID_BYTE_LIMIT
+      )))?;
+    }
+    if self.title.trim().is_empty() {
+      Err(TelegramError::new("inline result title is empty"))?;
+    }
+    let text = message_html(&self.text)?;
+    if text.trim().is_empty() {
+      Err(TelegramError::new("inline result text is empty"))?;
+    }
+    let mut article = tg::InlineQueryResultArticle::new(
+      &self.id,
+      &self.title,
+      tg::InputMessageContent::Text(
+        tg::InputMessageContentText::new(text).parse_mode(ParseMode::Html),
+      ),
+    );
+    article.description = self.description.clone();
+    if let Some(thumbnail_url) = &self.thumbnail_url {
+      article.thumbnail_url = Some(Url::parse(thumbnail_url).map_err(|e| {
+        TelegramError::new(format!("invalid thumbnail url '{}'", thumbnail_url)).with_cause(e)
+      })?);
+    }
+    if !self.buttons.is_empty() {
+      article.reply_markup = Some(inline_keyboard(&self.buttons)?);
+    }
+    Ok(tg::InlineQueryResult::Article(article))
+  }
+}
+
+///an invoice for telegram payments, see [`crate::Bot::send_invoice`]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Invoice {
+  pub title: String,
+  pub description: String,
+  ///bot defined payload which is not shown to the user, at most 128 bytes
+  pub payload: String,
+  ///the payment provider token, not required for payments in telegram stars
+  pub provider_token: Option<String>,
+  ///three letter ISO 4217 currency code, or `XTR` for telegram stars
+  pub currency: String,
+  pub prices: Vec<Price>,
+  ///request the shipping address of the user
+  pub need_shipping_address: bool,
+  ///whether the final price depends on the shipping method. Telegram then sends a
+  ///[`crate::update::UpdateKind::ShippingQuery`] for the entered address.
+  pub is_flexible: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Price {
+  pub label: String,
+  ///the price in the smallest units of the currency
+  pub amount: u32,
+}
+
+impl Price {
+  pub fn new<S>(label: S, amount: u32) -> Self
+  where
+    S: ToString,
+  {
+    Self {
+      label: label.to_string(),
+      amount,
+    }
+  }
+}
+
+///a way of shipping the goods of an invoice, see [`crate::Bot::answer_shipping_query`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ShippingOption {
+  pub id: String,
+  pub title: String,
+  ///the costs of this option, which are added to the prices of the invoice
+  pub prices: Vec<Price>,
+}
+
+impl ShippingOption {
+  pub fn new<S, T>(id: S, title: T, prices: Vec<Price>) -> Self
+  where
+    S: ToString,
+    T: ToString,
+  {
+    Self {
+      id: id.to_string(),
+      title: title.to_string(),
+      prices,
+    }
+  }
+
+  pub(crate) fn to_tg(&self) -> tg::ShippingOption {
+    tg::ShippingOption::new(
+      &self.id,
+      &self.title,
+      self
+        .prices
+        .iter()
+        .map(|price| tg::LabeledPrice::new(&price.label, price.amount)),
+    )
+  }
+}
+
+///how the link preview of a message is shown, see [`SendMessage::link_preview`]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct LinkPreviewOptions {
+  pub disabled: bool,
+  ///the url to preview instead of the first link of the text
+  pub url: Option<String>,
+  ///shrink the media of the preview, can't be combined with
+  ///[`LinkPreviewOptions::prefer_large_media`]
+  pub prefer_small_media: bool,
+  pub prefer_large_media: bool,
+  pub show_above_text: bool,
+}
+
+impl LinkPreviewOptions {
+  fn to_tg(&self) -> crate::Result<tg::LinkPreviewOptions> {
+    if self.prefer_small_media && self.prefer_large_media {
+      Err(TelegramError::new(
+        "a link preview can't prefer both small and large media",
+      ))?;
+    }
+    if let Some(url) = &self.url {
+      Url::parse(url).map_err(|e| {
+        TelegramError::new(format!("invalid link preview url '{}'", url)).with_cause(e)
+      })?;
+    }
+    Ok(tg::LinkPreviewOptions {
+      is_disabled: self.disabled,
+      url: self.url.clone(),
+      prefer_small_media: self.prefer_small_media,
+      prefer_large_media: self.prefer_large_media,
+      show_above_text: self.show_above_text,
+    })
+  }
+}
+
+///options for [`SendMessage::force_reply_with`]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct ForceReply {
+  ///shown in the input field while replying, at most [`PLACEHOLDER_CHAR_LIMIT`] chars
+  pub input_field_placeholder: Option<String>,
+  ///only force users mentioned in the text or the sender of the replied message to reply
+  pub selective: bool,
+}
+
+impl ForceReply {
+  fn to_tg(&self) -> crate::Result<tg::ForceReply> {
+    let mut force_reply = tg::ForceReply::new();
+    if let Some(placeholder) = &self.input_field_placeholder {
+      check_placeholder(placeholder)?;
+      force_reply = force_reply.input_field_placeholder(placeholder.clone());
+    }
+    if self.selective {
+      force_reply = force_reply.selective();
+    }
+    Ok(force_reply)
+  }
+}
+
+///a custom keyboard replacing the keyboard of the user, see [`SendMessage::reply_keyboard`]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct ReplyKeyboard {
+  buttons: Vec<Vec<ReplyButton>>,
+  input_field_placeholder: Option<String>,
+  selective: bool,
+  resize: bool,
+  one_time: bool,
+}
+
+impl ReplyKeyboard {
+  pub fn new<B>(buttons: Vec<B>) -> Self
+  where
+    B: VarArgs<ReplyButton>,
+  {
+    Self {
+      buttons: buttons.into_iter().map(|line| line.args()).collect(),
+      ..Self::default()
+    }
+  }
+
+  pub fn add_button_row<B>(mut self, buttons: B) -> Self
+  where
+    B: VarArgs<ReplyButton>,
+  {
+    self.buttons.push(buttons.args());
+    self
+  }
+
+  ///shown in the input field while the keyboard is active, at most [`PLACEHOLDER_CHAR_LIMIT`]
+  ///chars
+  pub fn input_field_placeholder<S>(mut self, input_field_placeholder: S) -> Self
+  where
+    S: ToString,
+  {
+    self.input_field_placeholder = Some(input_field_placeholder.to_string());
+    self
+  }
+
+  ///only show the keyboard to users mentioned in the text or the sender of the replied message
+  pub fn selective(mut self, selective: bool) -> Self {
+    self.selective = selective;
+    self
+  }
+
+  ///fit the size of the keyboard to its buttons instead of the size of the standard keyboard
+  pub fn resize(mut self, resize: bool) -> Self {
+    self.resize = resize;
+    self
+  }
+
+  ///hide the keyboard after one of its buttons has been used
+  pub fn one_time(mut self, one_time: bool) -> Self {
+    self.one_time = one_time;
+    self
+  }
+
+  fn to_tg(&self) -> crate::Result<tg::KeyboardMarkup> {
+    let mut keyboard = tg::KeyboardMarkup::new(
+      self
+        .buttons
+        .iter()
+        .map(|row| row.iter().map(|button| button.to_tg()).collect())
+        .collect::<crate::Result<Vec<Vec<_>>>>()?,
+    );
+    if let Some(placeholder) = &self.input_field_placeholder {
+      check_placeholder(placeholder)?;
+      keyboard = keyboard.input_field_placeholder(placeholder.clone());
+    }
+    keyboard.selective = self.selective;
+    keyboard.resize_keyboard = self.resize;
+    keyboard.one_time_keyboard = self.one_time;
+    Ok(keyboard)
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct ReplyButton {
+  pub text: String,
+  pub kind: ReplyButtonKind,
+}
+
+impl ReplyButton {
+  ///a button sending its text as message when pressed
+  pub fn new<T>(text: T) -> Self
+  where
+    T: ToString,
+  {
+    Self {
+      text: text.to_string(),
+      kind: ReplyButtonKind::Text,
+    }
+  }
+
+  ///a button launching the mini app at the given https url
+  pub fn web_app<T, U>(text: T, url: U) -> Self
+  where
+    T: ToString,
+    U: ToString,
+  {
+    Self {
+      text: text.to_string(),
+      kind: ReplyButtonKind::WebApp(url.to_string()),
+    }
+  }
+
+  fn to_tg(&self) -> crate::Result<tg::KeyboardButton> {
+    let button = tg::KeyboardButton::new(&self.text);
+    Ok(match &self.kind {
+      ReplyButtonKind::Text => button,
+      ReplyButtonKind::WebApp(url) => button.request(tg::ButtonRequest::WebApp(tg::WebAppInfo {
+        url: parse_https_url(url)?,
+      })),
+    })
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ReplyButtonKind {
+  Text,
+  WebApp(String),
+}
+
+fn check_placeholder(placeholder: &str) -> crate::Result<()> {
+  let char_count = placeholder.chars().count();
+  if char_count > PLACEHOLDER_CHAR_LIMIT as usize {
+    Err(TelegramError::new(format!(
+      "input field placeholder char count ({}) exceeds limit ({})",
+      char_count, PLACEHOLDER_CHAR_LIMIT
+    )))?;
+  }
+  Ok(())
+}
+
+fn parse_https_url(url: &str) -> crate::Result<Url> {
+  let parsed = Url::parse(url)
+    .map_err(|e| TelegramError::new(format!("invalid url '{}'", url)).with_cause(e))?;
+  if parsed.scheme() != "https" {
+    Err(TelegramError::new(format!(
+      "url '{}' doesn't use https",
+      url
+    )))?;
+  }
+  Ok(parsed)
+}
+
+fn parse_button_url(url: &str) -> crate::Result<Url> {
+  let parsed = Url::parse(url)
+    .map_err(|e| TelegramError::new(format!("invalid url '{}'", url)).with_cause(e))?;
+  if !matches!(parsed.scheme(), "http" | "https" | "tg") {
+    Err(TelegramError::new(format!(
+      "url '{}' doesn't use http(s) or tg",
+      url
+    )))?;
+  }
+  Ok(parsed)
+}
+
+///a button of an inline keyboard, prefer the constructors over struct literals, which have to set
+///the [`ButtonKind`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Button {
+  pub text: String,
+  pub kind: ButtonKind,
+}
+
+impl Button {
+  ///a button sending a [`crate::update::Query`] with the given text when pressed
+  pub fn new<T, Q>(text: T, query: Q) -> Self
+  where
+    T: ToString,
+    Q: ToString,
+  {
+    Self::of_kind(text, ButtonKind::Callback(query.to_string()))
+  }
+
+  ///a button sending a [`crate::update::Query`] with the given data serialized as json when
+  ///pressed, which can be read with [`crate::update::Query::data`]. Fails if the serialized data
+  ///exceeds the [`QUERY_BYTE_LIMIT`].
+  pub fn callback_data<T, D>(text: T, data: &D) -> crate::Result<Self>
+  where
+    T: ToString,
+    D: Serialize,
+  {
+    let query = serde_json::to_string(data)
+      .map_err(|e| TelegramError::new("failed to serialize callback data").with_cause(e))?;
+    if query.len() > QUERY_BYTE_LIMIT as usize {
+      Err(
+        TelegramError::new(format!(
+          "serialized callback data ({} bytes) exceeds limit ({} bytes)",
+          query.len(),
+          QUERY_BYTE_LIMIT
+        ))
+        .of_kind(ErrorKind::QueryByteLimitReached),
+      )?;
+    }
+    Ok(Self::new(text, query))
+  }
+
+  ///a button opening the given http(s) or `tg://` url
+  pub fn url<T, U>(text: T, url: U) -> Self
+  where
+    T: ToString,
+    U: ToString,
+  {
+    Self::of_kind(text, ButtonKind::Url(url.to_string()))
+  }
+
+  ///a button launching the mini app at the given https url
+  pub fn web_app<T, U>(text: T, url: U) -> Self
+  where
+    T: ToString,
+    U: ToString,
+  {
+    Self::of_kind(text, ButtonKind::WebApp(url.to_string()))
+  }
+
+  ///a button letting the user pick a chat and inserting the bot's username and the given inline
+  ///query into its input field
+  pub fn switch_inline_query<T, Q>(text: T, query: Q) -> Self
+  where
+    T: ToString,
+    Q: ToString,
+  {
+    Self::of_kind(text, ButtonKind::SwitchInlineQuery(query.to_string()))
+  }
+
+  ///like [`Button::switch_inline_query`], but inserts the inline query into the input field of the
+  ///current chat
+  pub fn switch_inline_query_current_chat<T, Q>(text: T, query: Q) -> Self
+  where
+    T: ToString,
+    Q: ToString,
+  {
+    Self::of_kind(
+      text,
+      ButtonKind::SwitchInlineQueryCurrentChat(query.to_string()),
+    )
+  }
+
+  ///a button authorizing the user on the website at the https url of the [`LoginUrl`]
+  pub fn login_url<T>(text: T, login_url: LoginUrl) -> Self
+  where
+    T: ToString,
+  {
+    Self::of_kind(text, ButtonKind::LoginUrl(login_url))
+  }
+
+  fn of_kind<T>(text: T, kind: ButtonKind) -> Self
+  where
+    T: ToString,
+  {
+    Self {
+      text: text.to_string(),
+      kind,
+    }
+  }
+
+  ///the query of a callback button, which used to be the `query` field. It is changed through
+  ///[`Button::kind`].
+  pub fn query(&self) -> Option<&str> {
+    match &self.kind {
+      ButtonKind::Callback(query) => Some(query),
+      _ => None,
+    }
+  }
+
+  ///the button of an inline keyboard received from telegram, `None` for kinds which can't be
+  ///created with this crate, like pay buttons
+  pub(crate) fn from_tg(button: &InlineKeyboardButton) -> Option<Self> {
+    let kind = match &button.kind {
+      tg::InlineKeyboardButtonKind::CallbackData(query) => ButtonKind::Callback(query.clone()),
+      tg::InlineKeyboardButtonKind::Url(url) => ButtonKind::Url(url.to_string()),
+      tg::InlineKeyboardButtonKind::WebApp(web_app) => ButtonKind::WebApp(web_app.url.to_string()),
+      tg::InlineKeyboardButtonKind::SwitchInlineQuery(query) => {
+        ButtonKind::SwitchInlineQuery(query.clone())
+      }
+      tg::InlineKeyboardButtonKind::SwitchInlineQueryCurrentChat(query) => {
+        ButtonKind::SwitchInlineQueryCurrentChat(query.clone())
+      }
+      tg::InlineKeyboardButtonKind::LoginUrl(login_url) => ButtonKind::LoginUrl(LoginUrl {
+        url: login_url.url.to_string(),
+        forward_text: login_url.forward_text.clone(),
+        bot_username: login_url.bot_username.clone(),
+        request_write_access: login_url.request_write_access.unwrap_or_default(),
+      }),
+      _ => return None,
+    };
+    Some(Self::of_kind(&button.text, kind))
+  }
+
+  fn to_tg(&self) -> crate::Result<InlineKeyboardButton> {
+    Ok(match &self.kind {
+      ButtonKind::Callback(query) => {
+        let bytes = query.len();
+        if bytes > QUERY_BYTE_LIMIT as usize {
+          Err(
+            TelegramError::new(format!(
+              "query size ({} bytes) for button {:?} exceeds limit ({} bytes)",
+              bytes, self, QUERY_BYTE_LIMIT
+            ))
+            .of_kind(ErrorKind::QueryByteLimitReached),
+          )?;
+        }
+        InlineKeyboardButton::callback(&self.text, query)
+      }
+      ButtonKind::Url(url) => InlineKeyboardButton::url(&self.text, parse_button_url(url)?),
+      ButtonKind::WebApp(url) => InlineKeyboardButton::web_app(
+        &self.text,
+        tg::WebAppInfo {
+          url: parse_https_url(url)?,
+        },
+      ),
+      ButtonKind::SwitchInlineQuery(query) => {
+        InlineKeyboardButton::switch_inline_query(&self.text, query)
+      }
+      ButtonKind::SwitchInlineQueryCurrentChat(query) => {
+        InlineKeyboardButton::switch_inline_query_current_chat(&self.text, query)
+      }
+      ButtonKind::LoginUrl(login_url) => InlineKeyboardButton::login(
+        &self.text,
+        tg::LoginUrl {
+          url: parse_https_url(&login_url.url)?,
+          forward_text: login_url.forward_text.clone(),
+          bot_username: login_url.bot_username.clone(),
+          request_write_access: Some(login_url.request_write_access),
+        },
+      ),
+    })
+  }
+}
+
+///builds the rows of an inline keyboard step by step, e.g. a grid depending on runtime values, see
+///[`SendMessage::buttons`]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct KeyboardBuilder {
+  rows: Vec<Vec<Button>>,
+}
+
+impl KeyboardBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  ///start a new row, the following buttons are added to it
+  pub fn row(mut self) -> Self {
+    self.rows.push(vec![]);
+    self
+  }
+
+  ///add a button to the current row, starting the first row if there is none
+  pub fn button(mut self, button: Button) -> Self {
+    match self.rows.last_mut() {
+      Some(row) => row.push(button),
+      None => self.rows.push(vec![button]),
+    }
+    self
+  }
+
+  ///add all buttons to the current row, see [`KeyboardBuilder::button`]
+  pub fn buttons<I>(self, buttons: I) -> Self
+  where
+    I: IntoIterator<Item = Button>,
+  {
+    buttons.into_iter().fold(self, Self::button)
+  }
+
+  ///the rows of buttons, without empty rows
+  pub fn build(self) -> Vec<Vec<Button>> {
+    self
+      .rows
+      .into_iter()
+      .filter(|row| !row.is_empty())
+      .collect()
+  }
+}
+
+///convert rows of buttons to an inline keyboard, checking the [`INLINE_BUTTONS_PER_ROW_LIMIT`]
+///and the [`INLINE_BUTTONS_LIMIT`]
+pub(crate) fn inline_keyboard(buttons: &[Vec<Button>]) -> crate::Result<InlineKeyboardMarkup> {
+  if let Some(row) = buttons
+    .iter()
+    .find(|row| row.len() > INLINE_BUTTONS_PER_ROW_LIMIT)
+  {
+    Err(TelegramError::new(format!(
+      "a row of {} buttons exceeds the limit of {} buttons per row",
+      row.len(),
+      INLINE_BUTTONS_PER_ROW_LIMIT
+    )))?;
+  }
+  let count = buttons.iter().map(Vec::len).sum::<usize>();
+  if count > INLINE_BUTTONS_LIMIT {
+    Err(TelegramError::new(format!(
+      "{} buttons exceed the limit of {} buttons",
+      count, INLINE_BUTTONS_LIMIT
+    )))?;
+  }
+  Ok(InlineKeyboardMarkup {
+    inline_keyboard: buttons
+      .iter()
+      .map(|row| row.iter().map(|button| button.to_tg()).collect())
+      .collect::<crate::Result<_>>()?,
+  })
+}
+
+///what happens when a [`Button`] is pressed
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum ButtonKind {
+  ///the query sent to the bot, at most [`QUERY_BYTE_LIMIT`] bytes
+  Callback(String),
+  ///the http(s) or `tg://` url to open
+  Url(String),
+  ///the https url of a mini app
+  WebApp(String),
+  ///the inline query inserted in a chat picked by the user
+  SwitchInlineQuery(String),
+  ///the inline query inserted in the current chat
+  SwitchInlineQueryCurrentChat(String),
+  ///the login url the user is authorized on
+  LoginUrl(LoginUrl),
+}
+
+///a login url for the telegram login widget, see [`Button::login_url`]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct LoginUrl {
+  ///the https url the user is authorized on
+  pub url: String,
+  ///the text of the button in forwarded messages
+  pub forward_text: Option<String>,
+  ///the username of the bot used for authorization, defaults to this bot
+  pub bot_username: Option<String>,
+  ///request permission for the bot to send messages to the user
+  pub request_write_access: bool,
+}
+
+impl LoginUrl {
+  pub fn new<U>(url: U) -> Self
+  where
+    U: ToString,
+  {
+    Self {
+      url: url.to_string(),
+      ..Self::default()
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::sync::{Arc, Mutex, OnceLock};
+  use std::time::{Duration, UNIX_EPOCH};
+
+  use serde::{Deserialize, Serialize};
+  use teloxide::types::{InputMedia, MessageEntity, ParseMode, ReplyMarkup};
+  use tokio::runtime::Runtime;
+  use url::Url;
+
+  use crate::clock::ManualClock;
+  use crate::error::{ErrorKind, TelegramError};
+  use crate::format::{coalesce, Component, Decoration};
+  use crate::render::MarkdownV2Renderer;
+  use crate::request::{
+    caption_html, check_invite_link, check_links, check_nesting, check_poll, check_reactions,
+    check_topic_name, check_voice_file, explanation_html, from_entities, html_len, inline_keyboard,
+    message_html, parse_text, render_caption, split_message, text_len, to_entities, to_html,
+    to_system_time, truncate, BlockedChats, Button, ChatPermissions, Entity, EntityKind,
+    ForceReply, InlineResult, InputFile, InviteLinkConfig, KeyboardBuilder, LinkPreviewOptions,
+    LoginUrl, MediaItem, PollConfig, PollKind, ReplyButton, ReplyKeyboard, Sanitization,
+    SendMessage, SendOptions, SentKeys, SentMessage, TextFormat, CAPTION_CHAR_LIMIT,
+    IDEMPOTENCY_KEY_TTL, INLINE_BUTTONS_LIMIT, INLINE_BUTTONS_PER_ROW_LIMIT,
+    INLINE_RESULT_ID_BYTE_LIMIT, INVITE_LINK_MEMBER_LIMIT, INVITE_LINK_NAME_CHAR_LIMIT,
+    MESSAGE_CHAR_LIMIT, MESSAGE_REACTION_LIMIT, PLACEHOLDER_CHAR_LIMIT,
+    POLL_EXPLANATION_CHAR_LIMIT, POLL_MAX_OPTIONS, QUERY_BYTE_LIMIT, TOPIC_NAME_CHAR_LIMIT,
+  };
+  use crate::update::Message;
+  use crate::{ChatID, MessageID};
+
+  ///a message to chat 1, whose requests go to a local port nothing listens on so they never reach
+  ///telegram. All messages share one runtime.
+  fn send_message(text: &str) -> SendMessage {
+    static RUNTIME: OnceLock<Arc<Runtime>> = OnceLock::new();
+    SendMessage::new(
+      text.to_owned(),
+      ChatID(1),
+      teloxide::Bot::new("token").set_api_url(Url::parse("http://127.0.0.1:9").unwrap()),
+      RUNTIME
+        .get_or_init(|| Arc::new(Runtime::new().unwrap()))
+        .clone(),
+      SendOptions::default(),
+    )
+  }
+
+  #[test]
+  fn test_check_links() {
+    let link = |target: &str| Component::from("foo").decorate(Decoration::link(target));
+    assert!(check_links(&[Component::from("foo"), link("https://crab.rs")]).is_ok());
+    let error = check_links(&[Component::from("foo"), link("")]).unwrap_err();
+    assert!(error.detail_message.contains("component 1"));
+    assert!(check_links(&[link("not a url")]).is_err());
+    assert!(message_html("<link:>foo</link>").is_err());
+  }
+
+  #[test]
+  fn test_check_nesting() {
+    let code = Component::from("foo").decorate(Decoration::MonoSpace);
+    assert!(check_nesting(&[
+      code.clone(),
+      Component::from("bar").decorate(Decoration::Bold)
+    ])
+    .is_ok());
+    let error = check_nesting(&[
+      Component::from("bar"),
+      code.clone().decorate(Decoration::Bold),
+    ])
+    .unwrap_err();
+    assert!(error.detail_message.contains("component 1"));
+    assert!(error.detail_message.contains("bold"));
+    assert!(check_nesting(&[code.decorate(Decoration::link("https://crab.rs"))]).is_err());
+    assert!(message_html("<bold>foo <code>bar").is_err());
+    assert!(message_html("<bold>foo</bold> <code>bar").is_ok());
+  }
 
   #[test]
   fn test_to_html() {
     assert_eq!(
       "foo <b>bar</b> buzz",
-      to_html("foo <bold>bar</bold> buzz").expect("format error")
+      to_html(&parse_text("foo <bold>bar</bold> buzz").expect("format error"))
+    );
+    assert_eq!(
+      "<b><i>foo</i></b>",
+      to_html(&parse_text("<italic><bold>foo").expect("format error"))
+    );
+    //combined decorations are always rendered in the same order, regardless of the tag order
+    for text in [
+      "<strikethrough><underline><italic><bold>foo",
+      "<bold><italic><underline><strikethrough>foo",
+      "<underline><strike><bold><italic>foo",
+    ] {
+      assert_eq!(
+        "<b><i><u><s>foo</s></u></i></b>",
+        to_html(&parse_text(text).expect("format error"))
+      );
+    }
+  }
+
+  #[test]
+  fn test_text_len() {
+    let components = parse_text("<bold>🦀</bold> bar").expect("format error");
+    assert_eq!(6, text_len(&components));
+
+    //fits the limit when counting chars, but not when counting utf-16 code units
+    let crabs = vec![Component::from("🦀".repeat(MESSAGE_CHAR_LIMIT as usize))];
+    assert_eq!(MESSAGE_CHAR_LIMIT as usize, crabs[0].len_chars());
+    assert_eq!(2 * MESSAGE_CHAR_LIMIT as usize, text_len(&crabs));
+  }
+
+  #[test]
+  fn test_html_len() {
+    let components = vec![
+      Component::from("🦀 "),
+      Component::from("bar").decorate(Decoration::Bold),
+    ];
+    //the crab takes two utf-16 code units
+    assert_eq!("🦀 <b>bar</b>".len() - 2, html_len(&components));
+  }
+
+  #[test]
+  fn test_link_preview() {
+    let link_message = || send_message("https://crab.rs");
+    assert!(link_message()
+      .preview()
+      .unwrap()
+      .link_preview_options
+      .is_none());
+    let link_preview = LinkPreviewOptions {
+      url: Some("https://crab.rs/news".to_owned()),
+      show_above_text: true,
+      ..LinkPreviewOptions::default()
+    };
+    let options = link_message()
+      .disable_web_page_preview(true)
+      .link_preview(link_preview.clone())
+      .preview()
+      .unwrap()
+      .link_preview_options
+      .unwrap();
+    assert!(!options.is_disabled);
+    assert!(options.show_above_text);
+    assert_eq!(Some("https://crab.rs/news"), options.url.as_deref());
+    assert!(link_message()
+      .disable_web_page_preview(true)
+      .preview()
+      .unwrap()
+      .link_preview_options
+      .is_some_and(|options| options.is_disabled));
+    assert!(link_message()
+      .link_preview(LinkPreviewOptions {
+        prefer_small_media: true,
+        prefer_large_media: true,
+        ..link_preview
+      })
+      .validate()
+      .is_err());
+  }
+
+  #[test]
+  fn test_force_reply_placeholder_limit() {
+    let mut force_reply = ForceReply {
+      input_field_placeholder: Some("a".repeat(PLACEHOLDER_CHAR_LIMIT as usize)),
+      selective: true,
+    };
+    assert!(force_reply.to_tg().is_ok());
+    force_reply.input_field_placeholder = Some("a".repeat(PLACEHOLDER_CHAR_LIMIT as usize + 1));
+    assert!(force_reply.to_tg().is_err());
+  }
+
+  #[test]
+  fn test_reply_keyboard() {
+    let keyboard = ReplyKeyboard::new(vec![[ReplyButton::new("yes"), ReplyButton::new("no")]])
+      .input_field_placeholder("yes or no?")
+      .selective(true)
+      .to_tg()
+      .expect("invalid keyboard");
+    assert_eq!(2, keyboard.keyboard[0].len());
+    assert_eq!("yes or no?", keyboard.input_field_placeholder);
+    assert!(keyboard.selective);
+
+    let too_long = "a".repeat(PLACEHOLDER_CHAR_LIMIT as usize + 1);
+    assert!(ReplyKeyboard::default()
+      .input_field_placeholder(too_long)
+      .to_tg()
+      .is_err());
+  }
+
+  #[test]
+  fn test_button_from_tg() {
+    let buttons = [
+      Button::new("foo", "bar"),
+      Button::url("foo", "https://papermc.io/"),
+      Button::web_app("foo", "https://papermc.io/"),
+      Button::switch_inline_query("foo", "bar"),
+      Button::login_url("foo", LoginUrl::new("https://papermc.io/")),
+    ];
+    for button in buttons {
+      assert_eq!(
+        Some(&button),
+        Button::from_tg(&button.to_tg().unwrap()).as_ref()
+      );
+    }
+  }
+
+  #[test]
+  fn test_button_validation() {
+    assert!(Button::new("foo", "a".repeat(QUERY_BYTE_LIMIT as usize))
+      .to_tg()
+      .is_ok());
+    assert!(
+      Button::new("foo", "a".repeat(QUERY_BYTE_LIMIT as usize + 1))
+        .to_tg()
+        .is_err()
+    );
+
+    let long_url = format!(
+      "https://papermc.io/{}",
+      "a".repeat(QUERY_BYTE_LIMIT as usize)
+    );
+    assert!(Button::web_app("foo", long_url).to_tg().is_ok());
+    assert!(Button::web_app("foo", "http://papermc.io").to_tg().is_err());
+    assert!(ReplyButton::web_app("foo", "https://papermc.io")
+      .to_tg()
+      .is_ok());
+    assert!(ReplyButton::web_app("foo", "papermc.io").to_tg().is_err());
+
+    assert_eq!(Some("bar"), Button::new("foo", "bar").query());
+    assert_eq!(None, Button::url("foo", "https://papermc.io").query());
+    assert!(Button::url("foo", "tg://user?id=5").to_tg().is_ok());
+    assert!(Button::url("foo", "javascript:alert(1)").to_tg().is_err());
+    assert!(Button::url("foo", "http://papermc.io").to_tg().is_ok());
+    assert!(Button::url("foo", "papermc.io").to_tg().is_err());
+
+    let long_query = "a".repeat(QUERY_BYTE_LIMIT as usize + 1);
+    assert!(Button::switch_inline_query("foo", &long_query)
+      .to_tg()
+      .is_ok());
+    assert!(Button::switch_inline_query_current_chat("foo", &long_query)
+      .to_tg()
+      .is_ok());
+
+    assert!(
+      Button::login_url("foo", LoginUrl::new("https://papermc.io"))
+        .to_tg()
+        .is_ok()
+    );
+    assert!(Button::login_url("foo", LoginUrl::new("http://papermc.io"))
+      .to_tg()
+      .is_err());
+  }
+
+  #[test]
+  fn test_keyboard_builder() {
+    let mut keyboard = KeyboardBuilder::new();
+    for row in 0..3 {
+      keyboard = keyboard
+        .row()
+        .buttons((1..=3).map(|column| Button::new(row * 3 + column, row * 3 + column)));
+    }
+    let grid = keyboard.build();
+    assert_eq!(3, grid.len());
+    assert!(grid.iter().all(|row| row.len() == 3));
+    assert_eq!(Some("6"), grid[1][2].query());
+
+    let buttons = KeyboardBuilder::new()
+      .button(Button::new("a", "a"))
+      .row()
+      .row()
+      .button(Button::new("b", "b"))
+      .build();
+    assert_eq!(
+      vec![vec![Button::new("a", "a")], vec![Button::new("b", "b")]],
+      buttons
+    );
+
+    let preview = send_message("foo").buttons(grid).preview().unwrap();
+    assert!(matches!(
+      preview.reply_markup,
+      Some(ReplyMarkup::InlineKeyboard(markup)) if markup.inline_keyboard.len() == 3
+    ));
+  }
+
+  #[test]
+  fn test_inline_keyboard_limits() {
+    let row = |len: usize| vec![Button::new("foo", "bar"); len];
+    assert!(inline_keyboard(&[row(INLINE_BUTTONS_PER_ROW_LIMIT)]).is_ok());
+    assert!(inline_keyboard(&[row(INLINE_BUTTONS_PER_ROW_LIMIT + 1)]).is_err());
+
+    let rows = INLINE_BUTTONS_LIMIT / INLINE_BUTTONS_PER_ROW_LIMIT;
+    let mut buttons = vec![row(INLINE_BUTTONS_PER_ROW_LIMIT); rows];
+    buttons.push(row(INLINE_BUTTONS_LIMIT % INLINE_BUTTONS_PER_ROW_LIMIT));
+    assert!(inline_keyboard(&buttons).is_ok());
+    buttons.push(row(1));
+    assert!(inline_keyboard(&buttons).is_err());
+  }
+
+  #[test]
+  fn test_to_entities() {
+    let components =
+      parse_text("🦀 <bold>bar <italic>buzz</bold></italic> <link:https://papermc.io/>fee")
+        .expect("format error");
+    let (text, entities) = to_entities(&components).expect("invalid components");
+    assert_eq!("🦀 bar buzz fee", text);
+    assert_eq!(
+      vec![
+        MessageEntity::bold(3, 8),
+        MessageEntity::italic(7, 4),
+        MessageEntity::text_link(Url::parse("https://papermc.io/").unwrap(), 12, 3),
+      ],
+      entities
+    );
+  }
+
+  #[test]
+  fn test_from_entities() {
+    let components =
+      parse_text("🦀 <bold>bar <italic>buzz</bold></italic> <link:https://papermc.io/>fee")
+        .expect("format error");
+    let (text, entities) = to_entities(&components).expect("invalid components");
+    assert_eq!(coalesce(components), from_entities(&text, &entities));
+    assert!(from_entities("", &[]).is_empty());
+  }
+
+  #[test]
+  fn test_input_file_url() {
+    assert!(InputFile::Url("https://papermc.io/sticker.webp".to_owned())
+      .to_tg()
+      .is_ok());
+    assert!(InputFile::Url("papermc.io".to_owned()).to_tg().is_err());
+    assert!(InputFile::id("CAACAgIAAxkBAAE").to_tg().is_ok());
+  }
+
+  #[test]
+  fn test_caption_html() {
+    assert_eq!("<b>foo</b>", caption_html("<bold>foo").unwrap());
+    assert!(caption_html(&"a".repeat(CAPTION_CHAR_LIMIT as usize)).is_ok());
+    assert!(caption_html(&"a".repeat(CAPTION_CHAR_LIMIT as usize + 1)).is_err());
+  }
+
+  #[test]
+  fn test_check_voice_file() {
+    assert!(check_voice_file(&InputFile::Path("note.OGG".into())).is_ok());
+    assert!(check_voice_file(&InputFile::Path("note.mp3".into())).is_err());
+    assert!(check_voice_file(&InputFile::Path("note".into())).is_err());
+    assert!(check_voice_file(&InputFile::id("AwACAgIAAxkBAAE")).is_ok());
+  }
+
+  #[test]
+  fn test_preview() {
+    let preview = send_message("<bold>foo</bold><bold>bar")
+      .add_button(Button::new("button", "query"))
+      .preview()
+      .expect("invalid message");
+    assert_eq!("<b>foobar</b>", preview.text);
+    assert_eq!("foobar", preview.plain_text);
+    assert_eq!(6, preview.char_count);
+    assert_eq!(Some(ParseMode::Html), preview.parse_mode);
+    assert!(matches!(
+      preview.reply_markup,
+      Some(ReplyMarkup::InlineKeyboard(_))
+    ));
+
+    assert!(send_message(&"a".repeat(MESSAGE_CHAR_LIMIT as usize + 1))
+      .preview()
+      .is_err());
+    assert!(send_message("foo")
+      .add_button(Button::new(
+        "button",
+        "q".repeat(QUERY_BYTE_LIMIT as usize + 1)
+      ))
+      .preview()
+      .is_err());
+  }
+
+  #[test]
+  fn test_chat_permissions_to_tg() {
+    assert!(ChatPermissions::default().to_tg().is_empty());
+    assert_eq!(
+      teloxide::types::ChatPermissions::all(),
+      ChatPermissions::all().to_tg()
+    );
+    let permissions = ChatPermissions {
+      send_messages: true,
+      ..ChatPermissions::default()
+    }
+    .to_tg();
+    assert_eq!(teloxide::types::ChatPermissions::SEND_MESSAGES, permissions);
+  }
+
+  #[test]
+  fn test_callback_data() {
+    #[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+    enum Action {
+      Increase(u32),
+      Reset,
+    }
+
+    let button = Button::callback_data("increase", &Action::Increase(2)).unwrap();
+    assert_eq!(
+      Action::Increase(2),
+      serde_json::from_str(button.query().unwrap()).unwrap()
+    );
+    assert!(Button::callback_data("reset", &Action::Reset).is_ok());
+    assert_eq!(
+      ErrorKind::QueryByteLimitReached,
+      Button::callback_data("foo", &"a".repeat(QUERY_BYTE_LIMIT as usize))
+        .unwrap_err()
+        .kind
+    );
+  }
+
+  #[test]
+  fn test_validate() {
+    assert!(send_message("<bold>foo").validate().is_ok());
+    assert!(send_message("<bold foo").validate().is_err());
+    assert!(send_message("foo")
+      .add_button(Button::new("button", "query"))
+      .force_reply()
+      .validate()
+      .is_err());
+    assert!(send_message("foo")
+      .reply_to_in_chat(MessageID(5), -100)
+      .validate()
+      .is_ok());
+    assert!(send_message("foo")
+      .reply_to_in_chat(MessageID(5), 0)
+      .validate()
+      .is_err());
+    assert!(send_message("foo")
+      .reply_to_in_chat(MessageID(0), -100)
+      .validate()
+      .is_err());
+  }
+
+  #[test]
+  fn test_check_poll() {
+    let options = ["yes", "no"];
+    assert!(check_poll("ok?", &options, &PollConfig::default()).is_ok());
+    assert!(check_poll("ok?", &options, &PollConfig::quiz(1)).is_ok());
+    assert!(check_poll("", &options, &PollConfig::default()).is_err());
+    assert!(check_poll("ok?", &["yes"], &PollConfig::default()).is_err());
+    assert!(check_poll("ok?", &["a"; POLL_MAX_OPTIONS + 1], &PollConfig::default()).is_err());
+    assert!(check_poll("ok?", &["yes", ""], &PollConfig::default()).is_err());
+    assert!(check_poll("ok?", &options, &PollConfig::quiz(2)).is_err());
+    let regular_with_answer = PollConfig {
+      correct_option_id: Some(0),
+      ..PollConfig::default()
+    };
+    assert!(check_poll("ok?", &options, &regular_with_answer).is_err());
+    let quiz_without_answer = PollConfig {
+      kind: PollKind::Quiz,
+      ..PollConfig::default()
+    };
+    assert!(check_poll("ok?", &options, &quiz_without_answer).is_err());
+    assert!(explanation_html(&"a".repeat(POLL_EXPLANATION_CHAR_LIMIT as usize + 1)).is_err());
+  }
+
+  #[test]
+  fn test_check_invite_link() {
+    let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let check = |config: InviteLinkConfig| check_invite_link(&config, now);
+    assert!(check(InviteLinkConfig::default()).is_ok());
+    assert!(check(
+      InviteLinkConfig::default()
+        .name("a".repeat(INVITE_LINK_NAME_CHAR_LIMIT as usize))
+        .member_limit(INVITE_LINK_MEMBER_LIMIT)
+        .expire_date(now + Duration::from_secs(60))
+    )
+    .is_ok());
+    assert!(check(InviteLinkConfig::default().name("a".repeat(33))).is_err());
+    assert!(check(InviteLinkConfig::default().member_limit(0)).is_err());
+    assert!(check(InviteLinkConfig::default().member_limit(100_000)).is_err());
+    assert!(check(
+      InviteLinkConfig::default()
+        .member_limit(10)
+        .creates_join_request(true)
+    )
+    .is_err());
+    assert!(check(InviteLinkConfig::default().expire_date(now)).is_err());
+  }
+
+  #[test]
+  fn test_to_system_time() {
+    assert_eq!(UNIX_EPOCH, to_system_time(0));
+    assert_eq!(UNIX_EPOCH, to_system_time(-1));
+    assert_eq!(
+      UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+      to_system_time(1_700_000_000)
+    );
+  }
+
+  #[test]
+  fn test_inline_result() {
+    let result = InlineResult::new("1", "foo", "<bold>bar")
+      .description("baz")
+      .buttons(vec![vec![Button::new("qux", "quux")]]);
+    let teloxide::types::InlineQueryResult::Article(article) = result.to_tg().unwrap() else {
+      panic!("expected an article");
+    };
+    assert_eq!(Some("baz"), article.description.as_deref());
+    assert!(article.reply_markup.is_some());
+    match article.input_message_content {
+      teloxide::types::InputMessageContent::Text(content) => {
+        assert_eq!("<b>bar</b>", content.message_text)
+      }
+      content => panic!("unexpected content {:?}", content),
+    }
+
+    assert!(InlineResult::new("", "foo", "bar").to_tg().is_err());
+    assert!(InlineResult::new(
+      "a".repeat(INLINE_RESULT_ID_BYTE_LIMIT as usize + 1),
+      "foo",
+      "bar"
+    )
+    .to_tg()
+    .is_err());
+    assert!(InlineResult::new("1", " ", "bar").to_tg().is_err());
+    assert!(InlineResult::new("1", "foo", "").to_tg().is_err());
+    assert!(InlineResult::new("1", "foo", "bar")
+      .thumbnail_url("no url")
+      .to_tg()
+      .is_err());
+  }
+
+  #[test]
+  fn test_check_reactions() {
+    assert!(check_reactions(&[], true).is_ok());
+    assert!(check_reactions(&["👍"], true).is_ok());
+    assert!(check_reactions(&[""], true).is_err());
+    assert!(check_reactions(&[""], false).is_err());
+    assert!(check_reactions(&["👍"; MESSAGE_REACTION_LIMIT + 1], true).is_err());
+    assert!(check_reactions(&["👍"; MESSAGE_REACTION_LIMIT + 1], false).is_ok());
+  }
+
+  #[test]
+  fn test_sent_keys() {
+    let clock = Arc::new(ManualClock::new());
+    let sent_keys = SentKeys::new(clock.clone());
+    let sent = SentMessage {
+      chat_id: ChatID(1),
+      message_id: MessageID(2),
+      date: UNIX_EPOCH,
+      fell_back_to_plain: false,
+    };
+    assert_eq!(None, sent_keys.reserve("foo").unwrap());
+    assert!(sent_keys.reserve("foo").is_err());
+    sent_keys.complete("foo", Ok(sent));
+    assert_eq!(Some(sent), sent_keys.reserve("foo").unwrap());
+
+    //failed sends release the key
+    assert_eq!(None, sent_keys.reserve("bar").unwrap());
+    let error = TelegramError::new("failed to send message").of_kind(ErrorKind::ChatNotFound);
+    sent_keys.complete("bar", Err(&error));
+    assert_eq!(None, sent_keys.reserve("bar").unwrap());
+
+    //sends which may have reached telegram keep the key
+    assert_eq!(None, sent_keys.reserve("buzz").unwrap());
+    let error = TelegramError::new("failed to send message").of_kind(ErrorKind::Network);
+    sent_keys.complete("buzz", Err(&error));
+    assert!(sent_keys.reserve("buzz").is_err());
+
+    //sends which never complete keep the key until it expires
+    assert_eq!(None, sent_keys.reserve("fizz").unwrap());
+    assert!(sent_keys.reserve("fizz").is_err());
+
+    clock.advance(IDEMPOTENCY_KEY_TTL);
+    assert_eq!(None, sent_keys.reserve("foo").unwrap());
+    assert_eq!(None, sent_keys.reserve("buzz").unwrap());
+    assert_eq!(None, sent_keys.reserve("fizz").unwrap());
+  }
+
+  #[test]
+  fn test_execute_returning_message_sent_before() {
+    let sent_keys = Arc::new(SentKeys::new(Arc::new(ManualClock::new())));
+    let sent = SentMessage {
+      chat_id: ChatID(1),
+      message_id: MessageID(2),
+      date: UNIX_EPOCH,
+      fell_back_to_plain: false,
+    };
+    sent_keys.reserve("foo").unwrap();
+    sent_keys.complete("foo", Ok(sent));
+    let send_message = send_message("foo")
+      .sent_keys(sent_keys)
+      .idempotency_key("foo");
+    assert_eq!(sent, send_message.execute().unwrap());
+    //only the id of the message sent before is known
+    assert!(send_message
+      .execute_returning_message()
+      .is_err_and(|e| e.detail_message.contains("idempotency key")));
+  }
+
+  #[test]
+  fn test_blocked_chats() {
+    let blocked_chats = Arc::new(Mutex::new(BlockedChats::default()));
+    blocked_chats.lock().unwrap().record(ChatID(1));
+    assert!(blocked_chats.lock().unwrap().chats.is_empty());
+    blocked_chats.lock().unwrap().enabled = true;
+    blocked_chats.lock().unwrap().record(ChatID(1));
+    let send_message = send_message("foo").blocked_chats(blocked_chats.clone());
+    //fails without a request
+    assert!(send_message
+      .execute()
+      .is_err_and(|e| e.kind == ErrorKind::BotBlocked));
+    assert!(blocked_chats.lock().unwrap().check(ChatID(2)).is_ok());
+  }
+
+  #[test]
+  fn test_warn_on_unescaped() {
+    let send_message = send_message("<bold>hi <bold>user</bold></bold>");
+    assert!(send_message.preview().unwrap().warnings.is_empty());
+    assert_eq!(
+      vec!["tag '<bold>' at char 9 is already open".to_owned()],
+      send_message.warn_on_unescaped().preview().unwrap().warnings
+    );
+  }
+
+  #[test]
+  fn test_strip_invalid_links() {
+    let send_message =
+      send_message("<link:https://crab.rs>valid</link> <link:not a url><bold>invalid");
+    assert!(send_message.validate().is_err());
+    let preview = send_message.strip_invalid_links().preview().unwrap();
+    assert_eq!(
+      "<a href=\"https://crab.rs\">valid</a> <b>invalid</b>",
+      preview.text
+    );
+    assert_eq!(vec!["not a url".to_owned()], preview.stripped_links);
+  }
+
+  #[test]
+  fn test_with_renderer() {
+    let preview = send_message("<bold>foo</bold> 1.5")
+      .with_renderer(MarkdownV2Renderer)
+      .preview()
+      .unwrap();
+    assert_eq!("*foo* 1\\.5", preview.text);
+    assert_eq!(Some(ParseMode::MarkdownV2), preview.parse_mode);
+    assert_eq!(7, preview.char_count);
+  }
+
+  #[test]
+  fn test_media_item_to_tg() {
+    let photo = MediaItem::photo(InputFile::id("foo"))
+      .caption("<bold>bar")
+      .spoiler(true);
+    match photo.to_tg().unwrap() {
+      InputMedia::Photo(photo) => {
+        assert_eq!(Some("<b>bar</b>"), photo.caption.as_deref());
+        assert_eq!(Some(ParseMode::Html), photo.parse_mode);
+        assert!(photo.has_spoiler);
+      }
+      media => panic!("expected a photo, got {:?}", media),
+    }
+    match MediaItem::document(InputFile::id("foo")).to_tg().unwrap() {
+      InputMedia::Document(document) => {
+        assert_eq!(None, document.caption);
+        assert_eq!(None, document.parse_mode);
+      }
+      media => panic!("expected a document, got {:?}", media),
+    }
+    let long_caption = "a".repeat(CAPTION_CHAR_LIMIT as usize + 1);
+    assert!(MediaItem::video(InputFile::id("foo"))
+      .caption(long_caption)
+      .to_tg()
+      .is_err());
+  }
+
+  #[test]
+  fn test_caption_entities() {
+    let photo = MediaItem::photo(InputFile::id("foo"))
+      .with_entities("bar <baz>", vec![Entity::new(0, 3, EntityKind::Bold)]);
+    match photo.to_tg().unwrap() {
+      InputMedia::Photo(photo) => {
+        assert_eq!(Some("bar <baz>"), photo.caption.as_deref());
+        assert_eq!(None, photo.parse_mode);
+        assert_eq!(
+          Some(vec![MessageEntity::bold(0, 3)]),
+          photo.caption_entities
+        );
+      }
+      media => panic!("expected a photo, got {:?}", media),
+    }
+    let caption = render_caption("bar", Some(&[Entity::new(1, 3, EntityKind::Bold)]));
+    assert!(caption.is_err());
+    let long_caption = "a".repeat(CAPTION_CHAR_LIMIT as usize + 1);
+    assert!(render_caption(&long_caption, Some(&[])).is_err());
+  }
+
+  #[test]
+  fn test_reply_thread() {
+    let message = serde_json::from_str::<teloxide::types::Message>(
+      r#"{
+        "message_id": 3,
+        "message_thread_id": 2,
+        "is_topic_message": true,
+        "date": 1,
+        "chat": {"id": -5, "type": "supergroup", "title": "foo", "is_forum": true},
+        "from": {"id": 6, "is_bot": false, "first_name": "bar"},
+        "text": "baz"
+      }"#,
+    )
+    .unwrap();
+    let message = Message::from(&message).unwrap();
+    let group_message = || SendMessage {
+      send_to: ChatID(-5),
+      ..send_message("foo")
+    };
+    assert_eq!(None, group_message().thread_id());
+    assert_eq!(
+      Some(MessageID(2)),
+      group_message().reply_to(&message).thread_id()
+    );
+    assert_eq!(
+      Some(MessageID(7)),
+      group_message()
+        .thread(MessageID(7))
+        .reply_to(&message)
+        .thread_id()
+    );
+    assert_eq!(
+      None,
+      group_message()
+        .reply_to(&message)
+        .reply_to_in_chat(MessageID(3), -6)
+        .thread_id()
+    );
+  }
+
+  #[test]
+  fn test_sanitization() {
+    let preview = |sanitization| {
+      send_message("<bold>evil\u{202e}txt.exe")
+        .sanitize(sanitization)
+        .preview()
+    };
+    assert_eq!(
+      "<b>evil\u{202e}txt.exe</b>",
+      preview(Sanitization::Off).unwrap().text
+    );
+    assert_eq!(
+      "<b>eviltxt.exe</b>",
+      preview(Sanitization::Strip).unwrap().text
+    );
+    assert!(
+      preview(Sanitization::Reject).is_err_and(|e| e.detail_message.contains("U+202E at char 10"))
+    );
+  }
+
+  #[test]
+  fn test_parse_links() {
+    let preview = |text: &str, format: TextFormat, parse_links: bool| {
+      send_message(text)
+        .format(format)
+        .parse_links(parse_links)
+        .preview()
+        .unwrap()
+        .text
+    };
+    let text = "<bold>see</bold> https://papermc.io";
+    assert_eq!(
+      "<b>see</b> https://papermc.io",
+      preview(text, TextFormat::Tags, false)
+    );
+    assert_eq!(
+      "<b>see</b> <a href=\"https://papermc.io\">https://papermc.io</a>",
+      preview(text, TextFormat::Tags, true)
+    );
+    assert_eq!(
+      "&lt;bold&gt;see&lt;/bold&gt; <a href=\"https://papermc.io\">https://papermc.io</a>",
+      preview(text, TextFormat::Plain, true)
+    );
+  }
+
+  #[test]
+  fn test_with_entities() {
+    let entity_message = |text: &str, entities| send_message("").with_entities(text, entities);
+    let preview = entity_message(
+      "🦀 <b>\r\n",
+      vec![
+        Entity::new(0, 2, EntityKind::Bold),
+        Entity::new(3, 3, EntityKind::Link("tg://user?id=42".to_owned())),
+      ],
+    )
+    .preview()
+    .unwrap();
+    assert_eq!("🦀 <b>\r\n", preview.text);
+    assert_eq!(None, preview.parse_mode);
+    assert_eq!(
+      Some(vec![
+        MessageEntity::bold(0, 2),
+        MessageEntity::text_link(Url::parse("tg://user?id=42").unwrap(), 3, 3),
+      ]),
+      preview.entities
+    );
+    assert!(
+      entity_message("foo", vec![Entity::new(1, 3, EntityKind::Italic)])
+        .preview()
+        .is_err()
+    );
+    //the crab is 2 utf-16 code units, so the text is 5 units long
+    let error = entity_message(
+      "foo🦀",
+      vec![
+        Entity::new(0, 3, EntityKind::Bold),
+        Entity::new(3, 3, EntityKind::Italic),
+      ],
+    )
+    .preview()
+    .unwrap_err();
+    assert_eq!("invalid entity at index 1", error.detail_message);
+    assert!(error
+      .to_string()
+      .contains("covering the utf-16 code units 3..6 exceeds the text of 5 code units"));
+    assert!(
+      entity_message("foo🦀", vec![Entity::new(3, 2, EntityKind::Italic)])
+        .preview()
+        .is_ok()
+    );
+    assert!(
+      entity_message("foo🦀", vec![Entity::new(0, 4, EntityKind::Italic)])
+        .preview()
+        .is_err_and(|e| e.to_string().contains("splits a char"))
+    );
+  }
+
+  #[test]
+  fn test_check_topic_name() {
+    assert!(check_topic_name("support").is_ok());
+    assert!(check_topic_name("").is_err());
+    assert!(check_topic_name(&"🦀".repeat(TOPIC_NAME_CHAR_LIMIT as usize)).is_ok());
+    assert!(check_topic_name(&"a".repeat(TOPIC_NAME_CHAR_LIMIT as usize + 1)).is_err());
+  }
+
+  #[test]
+  fn test_truncate() {
+    assert_eq!(
+      "foo <bold>bar</bold>…",
+      truncate("foo <bold>bar buzz</bold> fee", 10).unwrap()
+    );
+    assert_eq!("<bold>foo</bold>", truncate("<bold>foo", 10).unwrap());
+    assert_eq!(
+      "<italic>abcd</italic>…",
+      truncate("<italic>abcdefgh", 5).unwrap()
+    );
+    assert_eq!("…", truncate("foo bar", 1).unwrap());
+    assert!(truncate("foo bar", 0).is_err());
+  }
+
+  #[test]
+  fn test_split_message() {
+    assert_eq!(
+      vec!["foo bar ", "<bold>buzz fee</bold>"],
+      split_message("foo bar <bold>buzz fee", 10).unwrap()
+    );
+    assert_eq!(
+      vec!["foo bar ", "buzz fee"],
+      split_message("foo bar buzz fee", 10).unwrap()
+    );
+    assert_eq!(
+      vec![
+        "<bold>foo </bold>",
+        "<bold>barbuzz</bold>",
+        "<bold>fee</bold>"
+      ],
+      split_message("<bold>foo barbuzzfee", 7).unwrap()
     );
+    assert_eq!(vec![""], split_message("", 10).unwrap());
+    assert!(split_message("foo", 0).is_err());
+    //a char longer than the limit can't be split
+    assert!(split_message("🦀", 1).is_err());
+    assert!(split_message("a🦀", 1).is_err());
+    assert_eq!(vec!["a", "🦀"], split_message("a🦀", 2).unwrap());
   }
 }
@@ -0,0 +1,189 @@
+use std::future::Future;
+use std::time::Duration;
+
+use crate::clock::Clock;
+use crate::error::{ErrorKind, TelegramError};
+use crate::{jitter, random_signed_unit, Backoff};
+
+///how often and how long to wait before repeating a failed request, see
+///[`crate::Bot::set_retry_policy`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+  ///the attempts made in total, so `1` disables retrying
+  pub max_attempts: u32,
+  ///the delay before the first retry, doubled for every further one
+  pub base_delay: Duration,
+  pub max_delay: Duration,
+  ///the maximum relative deviation of a delay, between 0 and 1
+  pub jitter: f64,
+  ///the errors worth retrying, others fail right away
+  pub retryable: Vec<ErrorKind>,
+}
+
+impl RetryPolicy {
+  ///retry requests failing with [`ErrorKind::Network`] until the given attempts are made
+  pub fn new(max_attempts: u32) -> Self {
+    Self {
+      max_attempts: max_attempts.max(1),
+      base_delay: Duration::from_secs(1),
+      max_delay: Duration::from_secs(30),
+      jitter: 0.0,
+      retryable: vec![ErrorKind::Network],
+    }
+  }
+
+  ///make every request only once, the default
+  pub fn none() -> Self {
+    Self::new(1)
+  }
+
+  pub fn base_delay(mut self, base_delay: Duration) -> Self {
+    self.base_delay = base_delay;
+    self
+  }
+
+  pub fn max_delay(mut self, max_delay: Duration) -> Self {
+    self.max_delay = max_delay;
+    self
+  }
+
+  ///randomize each delay by up to the given fraction in both directions, clamped between 0 and 1
+  pub fn jitter(mut self, jitter: f64) -> Self {
+    self.jitter = jitter.clamp(0.0, 1.0);
+    self
+  }
+
+  pub fn retryable(mut self, retryable: Vec<ErrorKind>) -> Self {
+    self.retryable = retryable;
+    self
+  }
+
+  pub fn is_retryable(&self, kind: ErrorKind) -> bool {
+    self.retryable.contains(&kind)
+  }
+
+  ///the delay before the retry following the given amount of failures, without jitter
+  pub fn delay(&self, failures: u32) -> Duration {
+    Backoff::new(self.base_delay, self.max_delay).cooldown(failures.saturating_sub(1))
+  }
+}
+
+impl Default for RetryPolicy {
+  fn default() -> Self {
+    Self::none()
+  }
+}
+
+///run the operation until it succeeds, fails with an error the policy doesn't retry or runs out of
+///attempts, sleeping with the clock in between. The last error is returned.
+pub async fn retry<T, F, Fut>(policy: &RetryPolicy, clock: &dyn Clock, op: F) -> crate::Result<T>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = crate::Result<T>>,
+{
+  retry_if(policy, clock, op, |_| true).await
+}
+
+///like [`retry`], but errors are only retried if they are retryable by the policy and the given
+///check
+pub(crate) async fn retry_if<T, F, Fut, R>(
+  policy: &RetryPolicy,
+  clock: &dyn Clock,
+  mut op: F,
+  retryable: R,
+) -> crate::Result<T>
+where
+  F: FnMut() -> Fut,
+  Fut: Future<Output = crate::Result<T>>,
+  R: Fn(&TelegramError) -> bool,
+{
+  let mut failures = 0;
+  loop {
+    match op().await {
+      Err(e)
+        if failures + 1 < policy.max_attempts && policy.is_retryable(e.kind) && retryable(&e) =>
+      {
+        failures += 1;
+        let delay = jitter(policy.delay(failures), policy.jitter, random_signed_unit());
+        clock.sleep(delay).await;
+      }
+      result => return result,
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use std::time::Duration;
+
+  use crate::clock::ManualClock;
+  use crate::error::{ErrorKind, TelegramError};
+  use crate::retry::{retry, retry_if, RetryPolicy};
+
+  #[test]
+  fn test_retry_delay() {
+    let policy = RetryPolicy::new(5)
+      .base_delay(Duration::from_secs(1))
+      .max_delay(Duration::from_secs(3));
+    assert_eq!(Duration::from_secs(1), policy.delay(1));
+    assert_eq!(Duration::from_secs(2), policy.delay(2));
+    assert_eq!(Duration::from_secs(3), policy.delay(3));
+  }
+
+  #[test]
+  fn test_retry() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let clock = ManualClock::new();
+    let policy = RetryPolicy::new(3);
+    let fail = |kind| TelegramError::new("failed").of_kind(kind);
+
+    //succeeds on the last attempt
+    let mut attempts = 0;
+    let result = runtime.block_on(retry(&policy, &clock, || {
+      attempts += 1;
+      let result = if attempts < 3 {
+        Err(fail(ErrorKind::Network))
+      } else {
+        Ok(attempts)
+      };
+      async move { result }
+    }));
+    assert_eq!(3, result.unwrap());
+    assert_eq!(
+      vec![Duration::from_secs(1), Duration::from_secs(2)],
+      clock.sleeps()
+    );
+
+    //gives up after the max attempts
+    let mut attempts = 0;
+    let result: crate::Result<()> = runtime.block_on(retry(&policy, &clock, || {
+      attempts += 1;
+      async { Err(fail(ErrorKind::Network)) }
+    }));
+    assert_eq!(ErrorKind::Network, result.unwrap_err().kind);
+    assert_eq!(3, attempts);
+
+    //errors which aren't retryable fail right away
+    let mut attempts = 0;
+    let result: crate::Result<()> = runtime.block_on(retry(&policy, &clock, || {
+      attempts += 1;
+      async { Err(fail(ErrorKind::ChatNotFound)) }
+    }));
+    assert_eq!(ErrorKind::ChatNotFound, result.unwrap_err().kind);
+    assert_eq!(1, attempts);
+
+    //errors failing the check fail right away
+    let mut attempts = 0;
+    let result: crate::Result<()> = runtime.block_on(retry_if(
+      &policy,
+      &clock,
+      || {
+        attempts += 1;
+        async { Err(fail(ErrorKind::Network)) }
+      },
+      |e| e.detail_message != "failed",
+    ));
+    assert_eq!(ErrorKind::Network, result.unwrap_err().kind);
+    assert_eq!(1, attempts);
+  }
+}
@@ -1,71 +1,860 @@
-use std::ops::Add;
+use std::ops::{Add, Range};
 use std::time::{Duration, SystemTime};
 
+use serde::de::DeserializeOwned;
 use teloxide::dispatching::dialogue::GetChatId;
 use teloxide::prelude::*;
+use teloxide::types::{
+  AllowedUpdate, ChatInviteLink, ChatMemberUpdated, MessageEntity, MessageEntityKind,
+  MessageEntityRef, MessageKind, Poll, ShippingAddress as TgShippingAddress, SuccessfulPayment,
+  TextQuote, UserId,
+};
+use teloxide::types::{
+  ChatJoinRequest as TgChatJoinRequest, PhotoSize as TgPhotoSize, Sticker as TgSticker,
+  StickerSet as TgStickerSet,
+};
 
-use crate::{ChatID, MessageID};
+use crate::error::{is_query_answered, ErrorKind, TelegramError};
+use crate::format::Component;
+use crate::request::{from_entities, to_system_time, Button};
+use crate::{from_tg_chat_id, from_tg_message_id, ChatID, MessageID};
 
 #[derive(Debug)]
 pub struct Update {
+  ///the chat the update was sent in, or the private chat with the user for updates sent outside of
+  ///chats, see [`Update::chat_id`]
   pub chat_id: ChatID,
   pub user: User,
   pub kind: UpdateKind,
+  ///how the update has been received
+  pub source: UpdateSource,
+}
+
+///the transport an [`Update`] has been received through. Only polling is implemented so far,
+///webhooks are reserved for running both transports side by side.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub enum UpdateSource {
+  #[default]
+  Polling,
+  Webhook,
+}
+
+impl Update {
+  ///the chat the update was sent in, [`None`] for updates sent outside of chats. Of the current
+  ///kinds [`UpdateKind::PreCheckoutQuery`] and [`UpdateKind::ShippingQuery`] lack a chat, as do
+  ///queries of buttons of messages sent in inline mode, see [`Query::inline_message_id`].
+  pub fn chat_id(&self) -> Option<ChatID> {
+    match &self.kind {
+      UpdateKind::PreCheckoutQuery(_) | UpdateKind::ShippingQuery(_) => None,
+      UpdateKind::Query(query) if query.inline_message_id.is_some() => None,
+      //telegram never uses 0 as chat id
+      UpdateKind::Unknown { .. } => Some(self.chat_id).filter(|chat_id| chat_id.0 != 0),
+      UpdateKind::Message { .. }
+      | UpdateKind::Query(_)
+      | UpdateKind::MyChatMember(_)
+      | UpdateKind::ChatMember(_)
+      | UpdateKind::ChatJoinRequest(_) => Some(self.chat_id),
+    }
+  }
+
+  ///the user who caused the update
+  pub fn user(&self) -> &User {
+    &self.user
+  }
+
+  ///the message the update is about: the received or edited message, or the message with the button
+  ///of a [`UpdateKind::Query`] if available. [`None`] for the other kinds, which aren't tied to a
+  ///message.
+  pub fn message(&self) -> Option<&Message> {
+    match &self.kind {
+      UpdateKind::Message { message, .. } => Some(message),
+      UpdateKind::Query(query) => query.message.as_ref(),
+      UpdateKind::PreCheckoutQuery(_)
+      | UpdateKind::ShippingQuery(_)
+      | UpdateKind::MyChatMember(_)
+      | UpdateKind::ChatMember(_)
+      | UpdateKind::ChatJoinRequest(_)
+      | UpdateKind::Unknown { .. } => None,
+    }
+  }
+
+  ///whether this update is an edit of a previously received message
+  pub fn is_edit(&self) -> bool {
+    matches!(self.kind, UpdateKind::Message { edit: true, .. })
+  }
+
+  ///whether this update is a newly received message, as opposed to an edit or a query
+  pub fn is_new_message(&self) -> bool {
+    matches!(self.kind, UpdateKind::Message { edit: false, .. })
+  }
 }
 
 #[derive(Debug)]
 pub enum UpdateKind {
-  Message { message: Message, edit: bool },
+  Message {
+    message: Message,
+    edit: bool,
+  },
   Query(Query),
+  ///a user confirmed the payment of an invoice, which has to be answered with
+  ///[`crate::Bot::answer_pre_checkout_query`] within 10 seconds. As these queries aren't sent in a
+  ///chat, [`Update::chat_id()`] is [`None`] and the field is the id of the private chat with the
+  ///user.
+  PreCheckoutQuery(PreCheckoutQuery),
+  ///a user entered the shipping address for an invoice sent with
+  ///[`crate::request::Invoice::is_flexible`], which has to be answered with the available shipping
+  ///options using [`crate::Bot::answer_shipping_query`]. Like [`UpdateKind::PreCheckoutQuery`] it
+  ///isn't sent in a chat.
+  ShippingQuery(ShippingQuery),
+  ///the membership of this bot changed, e.g. it has been added to a group or blocked by a user
+  MyChatMember(ChatMemberUpdate),
+  ///the membership of a user changed. The bot has to be an administrator of the chat, and
+  ///telegram only sends these updates when they are requested explicitly with
+  ///[`crate::Bot::set_allowed_updates`].
+  ChatMember(ChatMemberUpdate),
+  ///a user requested to join a chat via an invite link creating join requests, which can be
+  ///answered with [`crate::Bot::approve_chat_join_request`] or
+  ///[`crate::Bot::decline_chat_join_request`]. The bot has to be an administrator allowed to invite
+  ///users.
+  ChatJoinRequest(ChatJoinRequest),
+  ///an update of a kind this crate doesn't handle, only received if
+  ///[`crate::Bot::set_unknown_updates`] is enabled. [`Update::user`] has the id `0` and an empty
+  ///name if telegram didn't send a user, and [`Update::chat_id()`] is [`None`] if it didn't send a
+  ///chat.
+  Unknown {
+    ///the name of the teloxide update kind, e.g. `ChatBoost`
+    kind_name: String,
+  },
+}
+
+///the kinds of updates a bot can subscribe to, see [`crate::Bot::set_allowed_updates`]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum UpdateFilter {
+  Message,
+  EditedMessage,
+  Query,
+  PreCheckoutQuery,
+  ShippingQuery,
+  MyChatMember,
+  ChatMember,
+  ChatJoinRequest,
+  ///messages received through a business connection, see [`Message::business_connection_id`]
+  BusinessMessage,
+  EditedBusinessMessage,
+  ///posts in channels the bot is an administrator of, see [`Message::is_channel_post`]
+  ChannelPost,
+  EditedChannelPost,
+}
+
+impl UpdateFilter {
+  ///the filter required to receive updates of the given kind, [`None`] for
+  ///[`UpdateKind::Unknown`] which can't be requested
+  pub fn of(kind: &UpdateKind) -> Option<Self> {
+    match kind {
+      UpdateKind::Message { message, edit } if message.is_channel_post => Some(match edit {
+        false => Self::ChannelPost,
+        true => Self::EditedChannelPost,
+      }),
+      UpdateKind::Message { message, edit } => {
+        Some(match (message.business_connection_id.is_some(), edit) {
+          (false, false) => Self::Message,
+          (false, true) => Self::EditedMessage,
+          (true, false) => Self::BusinessMessage,
+          (true, true) => Self::EditedBusinessMessage,
+        })
+      }
+      UpdateKind::Query(_) => Some(Self::Query),
+      UpdateKind::PreCheckoutQuery(_) => Some(Self::PreCheckoutQuery),
+      UpdateKind::ShippingQuery(_) => Some(Self::ShippingQuery),
+      UpdateKind::MyChatMember(_) => Some(Self::MyChatMember),
+      UpdateKind::ChatMember(_) => Some(Self::ChatMember),
+      UpdateKind::ChatJoinRequest(_) => Some(Self::ChatJoinRequest),
+      UpdateKind::Unknown { .. } => None,
+    }
+  }
+
+  pub fn to_tg(self) -> AllowedUpdate {
+    match self {
+      Self::Message => AllowedUpdate::Message,
+      Self::EditedMessage => AllowedUpdate::EditedMessage,
+      Self::Query => AllowedUpdate::CallbackQuery,
+      Self::PreCheckoutQuery => AllowedUpdate::PreCheckoutQuery,
+      Self::ShippingQuery => AllowedUpdate::ShippingQuery,
+      Self::MyChatMember => AllowedUpdate::MyChatMember,
+      Self::ChatMember => AllowedUpdate::ChatMember,
+      Self::ChatJoinRequest => AllowedUpdate::ChatJoinRequest,
+      Self::BusinessMessage => AllowedUpdate::BusinessMessage,
+      Self::EditedBusinessMessage => AllowedUpdate::EditedBusinessMessage,
+      Self::ChannelPost => AllowedUpdate::ChannelPost,
+      Self::EditedChannelPost => AllowedUpdate::EditedChannelPost,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Sticker {
+  ///the id of the sticker file, which can be used to send the sticker again
+  pub file_id: String,
+  pub emoji: Option<String>,
+  ///the name of the sticker set this sticker belongs to
+  pub set_name: Option<String>,
+}
+
+impl Sticker {
+  pub fn from(sticker: &TgSticker) -> Self {
+    Self {
+      file_id: sticker.file.id.0.clone(),
+      emoji: sticker.emoji.clone(),
+      set_name: sticker.set_name.clone(),
+    }
+  }
+}
+
+///a photo in the sizes it is available in, see [`crate::Bot::get_user_profile_photos`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PhotoSizes {
+  ///the sizes ordered from the smallest to the largest
+  pub sizes: Vec<PhotoSize>,
 }
 
+impl PhotoSizes {
+  pub fn from(photo_sizes: &[TgPhotoSize]) -> Self {
+    let mut sizes: Vec<_> = photo_sizes.iter().map(PhotoSize::from).collect();
+    sizes.sort_by_key(|size| size.width as u64 * size.height as u64);
+    Self { sizes }
+  }
+
+  pub fn largest(&self) -> Option<&PhotoSize> {
+    self.sizes.last()
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PhotoSize {
+  ///the id of the file, which can be downloaded with [`crate::Bot::get_file_url`]
+  pub file_id: String,
+  pub width: u32,
+  pub height: u32,
+  ///the size of the file in bytes, if telegram knows it
+  pub file_size: Option<u32>,
+}
+
+impl PhotoSize {
+  pub fn from(photo_size: &TgPhotoSize) -> Self {
+    Self {
+      file_id: photo_size.file.id.0.clone(),
+      width: photo_size.width,
+      height: photo_size.height,
+      //teloxide falls back to the maximum for unknown sizes
+      file_size: Some(photo_size.file.size).filter(|&size| size != u32::MAX),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StickerSet {
+  pub name: String,
+  pub title: String,
+  pub stickers: Vec<Sticker>,
+}
+
+impl StickerSet {
+  pub fn from(sticker_set: &TgStickerSet) -> Self {
+    Self {
+      name: sticker_set.name.clone(),
+      title: sticker_set.title.clone(),
+      stickers: sticker_set.stickers.iter().map(Sticker::from).collect(),
+    }
+  }
+}
+
+///the votes of a poll, see [`crate::Bot::stop_poll`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PollResults {
+  pub question: String,
+  ///the options with their amount of votes, in the order they were sent in
+  pub options: Vec<(String, u32)>,
+  ///the amount of users who voted, which is less than the sum of votes for polls allowing multiple
+  ///answers
+  pub total_voter_count: u32,
+  pub correct_option_id: Option<u8>,
+  pub is_closed: bool,
+}
+
+impl PollResults {
+  pub fn from(poll: &Poll) -> Self {
+    Self {
+      question: poll.question.clone(),
+      options: poll
+        .options
+        .iter()
+        .map(|option| (option.text.clone(), option.voter_count))
+        .collect(),
+      total_voter_count: poll.total_voter_count,
+      correct_option_id: poll.correct_option_id,
+      is_closed: poll.is_closed,
+    }
+  }
+}
+
+///an invite link of a chat, see [`crate::Bot::create_chat_invite_link`]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct InviteLink {
+  ///the link itself. If another administrator created the link, its second part is replaced with
+  ///`…`.
+  pub link: String,
+  pub name: Option<String>,
+  pub expire_date: Option<SystemTime>,
+  pub member_limit: Option<u32>,
+  pub creates_join_request: bool,
+  ///whether this is the primary link of the chat, see [`crate::Bot::export_chat_invite_link`]
+  pub is_primary: bool,
+  pub is_revoked: bool,
+}
+
+impl InviteLink {
+  pub fn from(invite_link: &ChatInviteLink) -> Self {
+    Self {
+      link: invite_link.invite_link.clone(),
+      name: invite_link.name.clone(),
+      expire_date: invite_link
+        .expire_date
+        .map(|date| to_system_time(date.timestamp())),
+      member_limit: invite_link.member_limit,
+      creates_join_request: invite_link.creates_join_request,
+      is_primary: invite_link.is_primary,
+      is_revoked: invite_link.is_revoked,
+    }
+  }
+}
+
+///a completed payment
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Payment {
+  pub currency: String,
+  ///the total price in the smallest units of the currency
+  pub total_amount: u32,
+  pub invoice_payload: String,
+  pub telegram_payment_charge_id: String,
+  pub provider_payment_charge_id: String,
+}
+
+impl Payment {
+  pub fn from(successful_payment: &SuccessfulPayment) -> Self {
+    Self {
+      currency: successful_payment.currency.clone(),
+      total_amount: successful_payment.total_amount,
+      invoice_payload: successful_payment.invoice_payload.clone(),
+      telegram_payment_charge_id: successful_payment.telegram_payment_charge_id.0.clone(),
+      provider_payment_charge_id: successful_payment.provider_payment_charge_id.clone(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PreCheckoutQuery {
+  pub id: String,
+  pub currency: String,
+  ///the total price in the smallest units of the currency
+  pub total_amount: u32,
+  pub invoice_payload: String,
+  pub shipping_option_id: Option<String>,
+}
+
+impl PreCheckoutQuery {
+  pub fn from(pre_checkout_query: &teloxide::types::PreCheckoutQuery) -> Self {
+    Self {
+      id: pre_checkout_query.id.0.clone(),
+      currency: pre_checkout_query.currency.clone(),
+      total_amount: pre_checkout_query.total_amount,
+      invoice_payload: pre_checkout_query.invoice_payload.clone(),
+      shipping_option_id: pre_checkout_query.shipping_option_id.clone(),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ShippingQuery {
+  pub id: String,
+  pub invoice_payload: String,
+  pub shipping_address: Address,
+}
+
+impl ShippingQuery {
+  pub fn from(shipping_query: &teloxide::types::ShippingQuery) -> Self {
+    Self {
+      id: shipping_query.id.0.clone(),
+      invoice_payload: shipping_query.invoice_payload.clone(),
+      shipping_address: Address::from(&shipping_query.shipping_address),
+    }
+  }
+}
+
+///a shipping address entered by a user
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Address {
+  ///two letter ISO 3166-1 country code
+  pub country_code: String,
+  ///the state, empty if not applicable
+  pub state: String,
+  pub city: String,
+  pub street_line1: String,
+  pub street_line2: String,
+  pub post_code: String,
+}
+
+impl Address {
+  pub fn from(shipping_address: &TgShippingAddress) -> Self {
+    Self {
+      country_code: serde_json::to_value(shipping_address.country_code)
+        .ok()
+        .and_then(|code| code.as_str().map(str::to_owned))
+        .unwrap_or_default(),
+      state: shipping_address.state.clone(),
+      city: shipping_address.city.clone(),
+      street_line1: shipping_address.street_line1.clone(),
+      street_line2: shipping_address.street_line2.clone(),
+      post_code: shipping_address.post_code.clone(),
+    }
+  }
+}
+
+///a changed membership, [`Update::user`] is the user who caused the change
+#[derive(Debug, Clone)]
+pub struct ChatMemberUpdate {
+  ///the user whose membership changed
+  pub member: User,
+  pub old_status: ChatMemberStatus,
+  pub new_status: ChatMemberStatus,
+  pub timestamp: SystemTime,
+}
+
+impl ChatMemberUpdate {
+  pub fn from(chat_member_updated: &ChatMemberUpdated) -> Option<Self> {
+    Some(Self {
+      member: User::from(&chat_member_updated.new_chat_member.user)?,
+      old_status: ChatMemberStatus::from(chat_member_updated.old_chat_member.status()),
+      new_status: ChatMemberStatus::from(chat_member_updated.new_chat_member.status()),
+      timestamp: SystemTime::UNIX_EPOCH.add(Duration::from_secs(
+        chat_member_updated.date.timestamp() as u64,
+      )),
+    })
+  }
+}
+
+///a request to join a chat, [`Update::user`] is the user who wants to join
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ChatJoinRequest {
+  ///the chat the user wants to join
+  pub chat_id: ChatID,
+  ///the id of the user who wants to join
+  pub user_id: ChatID,
+  ///the private chat with the user, which the bot can use to message the user until the request
+  ///is answered, e.g. to ask a captcha, for at most 5 minutes
+  pub user_chat_id: ChatID,
+  pub bio: Option<String>,
+  ///the invite link the user requested to join with
+  pub invite_link: Option<InviteLink>,
+  pub timestamp: SystemTime,
+}
+
+impl ChatJoinRequest {
+  pub fn from(chat_join_request: &TgChatJoinRequest) -> Option<Self> {
+    Some(Self {
+      chat_id: from_tg_chat_id(chat_join_request.chat.id),
+      user_id: user_chat_id(chat_join_request.from.id)?,
+      user_chat_id: from_tg_chat_id(chat_join_request.user_chat_id),
+      bio: chat_join_request.bio.clone(),
+      invite_link: chat_join_request.invite_link.as_ref().map(InviteLink::from),
+      timestamp: to_system_time(chat_join_request.date.timestamp()),
+    })
+  }
+
+  ///let the user join the chat, see [`crate::Bot::approve_chat_join_request`]
+  pub fn approve(&self, bot: &crate::Bot) -> crate::Result<()> {
+    bot.approve_chat_join_request(self.chat_id, self.user_id)
+  }
+
+  ///reject the request, see [`crate::Bot::decline_chat_join_request`]
+  pub fn decline(&self, bot: &crate::Bot) -> crate::Result<()> {
+    bot.decline_chat_join_request(self.chat_id, self.user_id)
+  }
+}
+
+///the membership of a user in a chat, see [`crate::Bot::get_chat_member`]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ChatMember {
+  pub user: User,
+  pub status: ChatMemberStatus,
+  ///whether the user is in the chat, which is false for users who left or have been banned, and
+  ///for restricted users who aren't members
+  pub is_present: bool,
+}
+
+impl ChatMember {
+  pub fn from(chat_member: &teloxide::types::ChatMember) -> Option<Self> {
+    Some(Self {
+      user: User::from(&chat_member.user)?,
+      status: ChatMemberStatus::from(chat_member.status()),
+      is_present: chat_member.kind.is_present(),
+    })
+  }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ChatMemberStatus {
+  Owner,
+  Administrator,
+  Member,
+  Restricted,
+  Left,
+  Banned,
+}
+
+impl ChatMemberStatus {
+  pub fn from(status: teloxide::types::ChatMemberStatus) -> Self {
+    use teloxide::types::ChatMemberStatus as Status;
+    match status {
+      Status::Owner => Self::Owner,
+      Status::Administrator => Self::Administrator,
+      Status::Member => Self::Member,
+      Status::Restricted => Self::Restricted,
+      Status::Left => Self::Left,
+      Status::Banned => Self::Banned,
+    }
+  }
+}
+
+///the maximum depth of [`Message::replying_to`] chains created by [`Message::from`].
+///
+///Telegram only includes a single level of replies in updates, so a message never carries the
+///message its replied message is replying to. The Bot API offers no way of fetching messages by id,
+///so deeper reply chains have to be tracked by the bot itself.
+pub const MAX_REPLY_DEPTH: usize = 1;
+
 #[derive(Debug, Clone)]
 pub struct Message {
   pub id: MessageID,
+  ///the text of the message, empty for stickers and service messages like
+  ///[`Message::successful_payment`]
   pub text: String,
+  ///the message this message is replying to, see [`MAX_REPLY_DEPTH`]
   pub replying_to: Option<Box<Message>>,
   pub timestamp: SystemTime,
+  ///when the message has last been edited. Telegram only sends the edited version, so the previous
+  ///text isn't known.
+  pub edit_timestamp: Option<SystemTime>,
+  pub successful_payment: Option<Payment>,
+  pub sticker: Option<Sticker>,
+  ///the topic of a forum supergroup or the comment thread this message was sent in
+  pub thread_id: Option<MessageID>,
+  ///whether this message was sent to a topic of a forum supergroup, in which case
+  ///[`Message::thread_id`] identifies the topic
+  pub is_topic_message: bool,
+  ///the users mentioned in the text, in order
+  pub mentions: Vec<Mention>,
+  ///the rows of the inline keyboard attached to the message, empty if there is none. Buttons of
+  ///kinds which can't be created with [`Button`] are left out.
+  pub buttons: Vec<Vec<Button>>,
+  ///the part of [`Message::replying_to`] the user quoted in this reply
+  pub quote: Option<Quote>,
+  pub flags: MessageFlags,
+  ///the business connection the message has been received through, to reply on behalf of the
+  ///business account, see [`crate::request::SendMessage::business_connection`]
+  pub business_connection_id: Option<String>,
+  ///the users who joined the chat or have been added to it, for the service message telegram sends
+  ///about it, e.g. to greet newcomers
+  pub new_chat_members: Vec<User>,
+  ///the user who left the chat or has been removed from it, for the service message telegram sends
+  ///about it
+  pub left_chat_member: Option<User>,
+  ///whether the message has been posted in a channel, in which case the sender is the channel
+  ///itself, see [`User::from_chat`]
+  pub is_channel_post: bool,
 }
 
 impl Message {
   pub fn from(message: &teloxide::types::Message) -> Option<Self> {
+    Self::from_with_depth(message, MAX_REPLY_DEPTH)
+  }
+
+  fn from_with_depth(message: &teloxide::types::Message, reply_depth: usize) -> Option<Self> {
+    let successful_payment = message.successful_payment().map(Payment::from);
+    let sticker = message.sticker().map(Sticker::from);
+    let new_chat_members: Vec<User> = message
+      .new_chat_members()
+      .map(|users| users.iter().filter_map(User::from).collect())
+      .unwrap_or_default();
+    let left_chat_member = message.left_chat_member().and_then(User::from);
+    let is_service = !new_chat_members.is_empty() || left_chat_member.is_some();
     Self {
-      id: message.id.0,
-      text: message.text()?.to_owned(),
+      id: from_tg_message_id(message.id),
+      text: match message.text() {
+        Some(text) => text.to_owned(),
+        None
+          if successful_payment.is_some()
+            || sticker.is_some()
+            || message.game().is_some()
+            || is_service =>
+        {
+          String::new()
+        }
+        None => return None,
+      },
       replying_to: message
         .reply_to_message()
-        .and_then(|message| Self::from(message).map(|message| Box::new(message))),
+        .filter(|_| reply_depth > 0)
+        .and_then(|message| Self::from_with_depth(message, reply_depth - 1).map(Box::new)),
       timestamp: SystemTime::UNIX_EPOCH.add(Duration::from_secs(message.date.timestamp() as u64)),
+      edit_timestamp: message
+        .edit_date()
+        .map(|date| SystemTime::UNIX_EPOCH.add(Duration::from_secs(date.timestamp() as u64))),
+      successful_payment,
+      sticker,
+      thread_id: message
+        .thread_id
+        .map(|thread_id| from_tg_message_id(thread_id.0)),
+      is_topic_message: message.is_topic_message,
+      mentions: message
+        .text()
+        .zip(message.entities())
+        .map(|(text, entities)| parse_mentions(text, entities))
+        .unwrap_or_default(),
+      buttons: message
+        .reply_markup()
+        .map(|markup| {
+          markup
+            .inline_keyboard
+            .iter()
+            .map(|row| row.iter().filter_map(Button::from_tg).collect())
+            .collect()
+        })
+        .unwrap_or_default(),
+      quote: message.quote().map(Quote::from),
+      flags: MessageFlags::from(message),
+      business_connection_id: match &message.kind {
+        MessageKind::Common(common) => common
+          .business_connection_id
+          .as_ref()
+          .map(|id| id.0.clone()),
+        _ => None,
+      },
+      new_chat_members,
+      left_chat_member,
+      is_channel_post: message.chat.is_channel(),
     }
     .into()
   }
+
+  ///iterate over the messages this message is replying to, starting with the direct reply
+  pub fn reply_chain(&self) -> impl Iterator<Item = &Message> {
+    std::iter::successors(self.replying_to.as_deref(), |message| {
+      message.replying_to.as_deref()
+    })
+  }
+
+  ///the amount of words in the text, see [`count_words`]
+  pub fn word_count(&self) -> usize {
+    count_words(&self.text)
+  }
+
+  ///the amount of unicode chars in the text
+  pub fn char_count(&self) -> usize {
+    self.text.chars().count()
+  }
+
+  ///whether the message has no text, like stickers or service messages
+  pub fn is_empty(&self) -> bool {
+    self.text.is_empty()
+  }
+
+  ///the users mentioned without username, with the byte range of the mention in the text. Unlike
+  ///mentions by username, these include the id of the user.
+  pub fn text_mentions(&self) -> Vec<(User, Range<usize>)> {
+    self
+      .mentions
+      .iter()
+      .filter_map(|mention| match mention {
+        Mention::User { user, range, .. } => Some((user.clone(), range.clone())),
+        Mention::Username(_) => None,
+      })
+      .collect()
+  }
+}
+
+///a part of a message quoted in a reply
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Quote {
+  pub text: String,
+  ///the quoted text with the formatting of the original message
+  pub components: Vec<Component>,
+  ///the position of the quote in the original message, in UTF-16 code units
+  pub position: usize,
+  ///whether the user picked the quote, as opposed to telegram adding it automatically
+  pub is_manual: bool,
+}
+
+impl Quote {
+  pub fn from(quote: &TextQuote) -> Self {
+    Self {
+      text: quote.text.clone(),
+      components: from_entities(&quote.text, &quote.entities),
+      position: quote.position as usize,
+      is_manual: quote.is_manual,
+    }
+  }
+}
+
+///a user mentioned in a message
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Mention {
+  ///a mention of a user by their username, without the leading `@`. Telegram doesn't include the
+  ///id of the user, and bots can't look up users by their username.
+  Username(String),
+  ///a mention of a user without username, which links to the user with the given text. The range
+  ///is the position of the text in [`Message::text`], in bytes.
+  User {
+    user: User,
+    text: String,
+    range: Range<usize>,
+  },
+}
+
+fn parse_mentions(text: &str, entities: &[MessageEntity]) -> Vec<Mention> {
+  MessageEntityRef::parse(text, entities)
+    .into_iter()
+    .filter_map(|entity| match entity.kind() {
+      MessageEntityKind::Mention => Some(Mention::Username(
+        entity.text().trim_start_matches('@').to_owned(),
+      )),
+      MessageEntityKind::TextMention { user } => Some(Mention::User {
+        user: User::from(user)?,
+        text: entity.text().to_owned(),
+        range: entity.range(),
+      }),
+      _ => None,
+    })
+    .collect()
+}
+
+///count the words of a text. Words are separated by any amount of whitespace and have to contain
+///at least one alphanumeric char, so punctuation isn't counted. As chinese and japanese are written
+///without spaces, each of their ideographs and kana counts as a word of its own.
+pub fn count_words(text: &str) -> usize {
+  let mut words = 0;
+  let mut in_word = false;
+  for c in text.chars() {
+    if is_cjk(c) {
+      words += 1;
+      in_word = false;
+    } else if c.is_whitespace() {
+      in_word = false;
+    } else if c.is_alphanumeric() && !in_word {
+      words += 1;
+      in_word = true;
+    }
+  }
+  words
+}
+
+fn is_cjk(c: char) -> bool {
+  matches!(
+    c as u32,
+    0x3040..=0x30FF //hiragana and katakana
+      | 0x3400..=0x4DBF //cjk extension a
+      | 0x4E00..=0x9FFF //cjk unified ideographs
+      | 0xF900..=0xFAFF //cjk compatibility ideographs
+      | 0x20000..=0x2FA1F //cjk extensions b to f and supplement
+  )
 }
 
 #[derive(Debug)]
 pub struct Query {
+  ///the id to answer the query with, see [`crate::Bot::answer_callback_query`]
+  pub id: String,
+  ///the callback data of the button, empty if the button has none, like game buttons
   pub text: String,
-  pub message: Message,
+  ///the game to launch, if the query comes from the button of a game sent with
+  ///[`crate::Bot::send_game`]. It is answered with the url of the game, see [`Query::open_game`].
+  pub game_short_name: Option<String>,
+  ///the message with the button. [`None`] for messages sent in inline mode, messages which are too
+  ///old to be accessed and messages [`Message::from`] doesn't accept.
+  pub message: Option<Message>,
+  ///the id of the message with the button, if it has been sent in inline mode. These messages
+  ///aren't sent in a chat, so [`Update::chat_id()`] is [`None`] and the field is the id of the
+  ///private chat with the user.
+  pub inline_message_id: Option<String>,
   /// use [`Update::user`] instead
   #[deprecated]
   pub from: User,
-  /// use [`Update::chat_id`] instead
+  /// use [`Update::chat_id`] instead, [`None`] for messages sent in inline mode
   #[deprecated]
-  pub chat_id: ChatID,
+  pub chat_id: Option<ChatID>,
 }
 
 impl Query {
+  ///convert any callback query of a valid user, so every query can be answered
+  #[allow(deprecated)]
   pub fn from(callback_query: &CallbackQuery) -> Option<Self> {
-    Self {
-      text: callback_query.data.as_ref()?.to_owned(),
-      message: Message::from(callback_query.message.as_ref()?)?,
-      from: User::from(&callback_query.from),
-      chat_id: callback_query.chat_id()?.0,
+    Some(Self {
+      id: callback_query.id.0.clone(),
+      text: callback_query.data.clone().unwrap_or_default(),
+      game_short_name: callback_query.game_short_name.clone(),
+      message: callback_query.regular_message().and_then(Message::from),
+      inline_message_id: callback_query.inline_message_id.clone(),
+      from: User::from(&callback_query.from)?,
+      chat_id: callback_query.chat_id().map(from_tg_chat_id),
+    })
+  }
+
+  ///answer the query, see [`crate::Bot::answer_callback_query`]
+  pub fn answer(&self, bot: &crate::Bot, text: Option<&str>, alert: bool) -> crate::Result<()> {
+    bot.answer_callback_query(&self.id, text, alert)
+  }
+
+  ///answer the query of a game button with the url the game is opened at, see
+  ///[`crate::Bot::answer_callback_query_url`]
+  pub fn open_game(&self, bot: &crate::Bot, url: &str) -> crate::Result<()> {
+    bot.answer_callback_query_url(&self.id, url)
+  }
+
+  ///remove the buttons of the message and answer the query, so a one-shot button can only be
+  ///acted on once. Returns false if the buttons had already been removed, e.g. by a query of a
+  ///button tapped again before the message was updated, in which case the query should be ignored.
+  ///Fails without [`Query::message`] or if removing the buttons fails, but the query is answered
+  ///anyway. A query which has already been answered, e.g. by [`crate::Bot::set_auto_ack_callbacks`],
+  ///isn't an error.
+  #[allow(deprecated)]
+  pub fn consume(&self, bot: &crate::Bot) -> crate::Result<bool> {
+    let consumed = match self.chat_id.zip(self.message.as_ref()) {
+      Some((chat_id, message)) => match bot.remove_buttons(chat_id, message.id) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind == ErrorKind::MessageNotModified => Ok(false),
+        Err(e) => Err(e),
+      },
+      None => Err(TelegramError::new(
+        "can't remove the buttons of a query without message",
+      )),
+    };
+    let answered = self.answer(bot, None, false);
+    let consumed = consumed?;
+    match answered {
+      Err(e) if !is_query_answered(&e) => Err(e),
+      _ => Ok(consumed),
     }
-    .into()
+  }
+
+  ///deserialize the data of a button created with [`crate::request::Button::callback_data`]
+  pub fn data<T>(&self) -> crate::Result<T>
+  where
+    T: DeserializeOwned,
+  {
+    serde_json::from_str(&self.text)
+      .map_err(|e| TelegramError::new("failed to deserialize callback data").with_cause(e))
   }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct User {
   pub id: ChatID,
   pub user_name: Option<String>,
@@ -74,16 +863,33 @@ pub struct User {
 }
 
 impl User {
-  pub fn from(user: &teloxide::types::User) -> Self {
-    Self {
-      id: user.id.0 as ChatID,
+  ///[`None`] if the id of the user exceeds the chat id range, see [`user_chat_id`]
+  pub fn from(user: &teloxide::types::User) -> Option<Self> {
+    Some(Self {
+      id: user_chat_id(user.id)?,
       user_name: user.username.to_owned(),
       first_name: user.first_name.to_owned(),
       last_name: user.last_name.to_owned(),
+    })
+  }
+
+  ///a chat sending messages in its own name, like a channel posting, with its title as name
+  pub fn from_chat(chat: &teloxide::types::Chat) -> Self {
+    Self {
+      id: from_tg_chat_id(chat.id),
+      user_name: chat.username().map(str::to_owned),
+      first_name: chat.title().unwrap_or_default().to_owned(),
+      last_name: None,
     }
   }
 }
 
+///the chat id of the private chat with a user. Telegram user ids have at most 52 significant bits,
+///so this is only [`None`] for invalid ids, which would otherwise wrap into the id of another chat.
+pub(crate) fn user_chat_id(user_id: UserId) -> Option<ChatID> {
+  i64::try_from(user_id.0).ok().map(ChatID)
+}
+
 impl User {
   pub fn full_name(&self) -> String {
     format!(
@@ -93,3 +899,414 @@ impl User {
     )
   }
 }
+
+///properties of how a [`Message`] has been sent, e.g. for moderation or logging
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct MessageFlags {
+  ///whether the message has been forwarded from another chat
+  pub is_forwarded: bool,
+  ///whether the message has been forwarded automatically from the channel linked to the group
+  pub is_automatic_forward: bool,
+  ///whether the message can't be forwarded or saved, as the chat protects its content
+  pub has_protected_content: bool,
+  ///the username of the inline bot the message has been sent via
+  pub via_bot: Option<String>,
+}
+
+impl MessageFlags {
+  pub fn from(message: &teloxide::types::Message) -> Self {
+    Self {
+      is_forwarded: message.forward_origin().is_some(),
+      is_automatic_forward: message.is_automatic_forward(),
+      has_protected_content: message.has_protected_content(),
+      via_bot: message
+        .via_bot
+        .as_ref()
+        .and_then(|bot| bot.username.clone()),
+    }
+  }
+}
+
+///a bot command like `/start@MyBot foo bar`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Command {
+  ///the name of the command without the leading `/` and the `@botname` suffix
+  pub name: String,
+  ///everything after the command name, with leading whitespace removed
+  pub args: String,
+}
+
+impl Command {
+  ///parse a command from a message text. Commands with a `@botname` suffix are only accepted if
+  ///the suffix matches `bot_user_name`, since they are addressed to another bot otherwise.
+  pub fn parse(text: &str, bot_user_name: Option<&str>) -> Option<Self> {
+    let text = text.strip_prefix('/')?;
+    let (command, args) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let name = match command.split_once('@') {
+      Some((name, addressee)) => {
+        if !bot_user_name.is_some_and(|user_name| user_name.eq_ignore_ascii_case(addressee)) {
+          return None;
+        }
+        name
+      }
+      None => command,
+    };
+    if name.is_empty() {
+      return None;
+    }
+    Some(Self {
+      name: name.to_owned(),
+      args: args.trim_start().to_owned(),
+    })
+  }
+
+  ///split the [`Command::args`] at whitespace, keeping double quoted segments like `"two words"`
+  ///together. A backslash escapes the next char, e.g. `\"` for a literal quote. An unterminated
+  ///quote extends to the end of the args.
+  pub fn split_args(&self) -> Vec<String> {
+    let mut args = Vec::new();
+    //the arg being read, if any. Quotes start an arg even if it stays empty.
+    let mut arg: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = self.args.chars();
+    while let Some(c) = chars.next() {
+      match c {
+        '\\' => arg
+          .get_or_insert_with(String::new)
+          .push(chars.next().unwrap_or('\\')),
+        '"' => {
+          quoted = !quoted;
+          arg.get_or_insert_with(String::new);
+        }
+        c if c.is_whitespace() && !quoted => args.extend(arg.take()),
+        c => arg.get_or_insert_with(String::new).push(c),
+      }
+    }
+    args.extend(arg);
+    args
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use teloxide::types::{CallbackQuery, MessageEntity, MessageEntityKind, TextQuote, UserId};
+
+  use crate::format::{Component, Decoration};
+
+  use crate::update::{
+    count_words, parse_mentions, user_chat_id, ChatJoinRequest, Command, Mention, Message,
+    MessageFlags, PhotoSizes, Query, Quote, ShippingQuery, UpdateFilter, UpdateKind, User,
+  };
+  use crate::ChatID;
+
+  #[test]
+  fn test_game_query() {
+    let query = |fields: &str| {
+      serde_json::from_str::<CallbackQuery>(&format!(
+        r#"{{
+          "id": "1",
+          "from": {{"id": 5, "is_bot": false, "first_name": "foo"}},
+          "message": {{
+            "message_id": 2,
+            "date": 1,
+            "chat": {{"id": 5, "type": "private", "first_name": "foo"}},
+            "text": "bar"
+          }},
+          "chat_instance": "3"{}
+        }}"#,
+        fields
+      ))
+      .unwrap()
+    };
+    let game = Query::from(&query(r#", "game_short_name": "tetris""#)).unwrap();
+    assert_eq!(Some("tetris"), game.game_short_name.as_deref());
+    assert_eq!("", game.text);
+    let button = Query::from(&query(r#", "data": "baz""#)).unwrap();
+    assert_eq!(None, button.game_short_name);
+    assert_eq!("baz", button.text);
+    assert_eq!("", Query::from(&query("")).unwrap().text);
+  }
+
+  #[test]
+  #[allow(deprecated)]
+  fn test_inline_message_query() {
+    let query = serde_json::from_str::<CallbackQuery>(
+      r#"{
+        "id": "1",
+        "from": {"id": 5, "is_bot": false, "first_name": "foo"},
+        "inline_message_id": "bar",
+        "chat_instance": "3",
+        "data": "baz"
+      }"#,
+    )
+    .unwrap();
+    let query = Query::from(&query).unwrap();
+    assert_eq!(Some("bar"), query.inline_message_id.as_deref());
+    assert!(query.message.is_none());
+    assert_eq!(None, query.chat_id);
+    assert_eq!("baz", query.text);
+  }
+
+  #[test]
+  fn test_parse_command() {
+    let command = |name: &str, args: &str| {
+      Some(Command {
+        name: name.to_owned(),
+        args: args.to_owned(),
+      })
+    };
+    assert_eq!(command("cmd", ""), Command::parse("/cmd", Some("MyBot")));
+    assert_eq!(
+      command("cmd", "foo bar"),
+      Command::parse("/cmd@MyBot  foo bar", Some("MyBot"))
+    );
+    assert_eq!(None, Command::parse("/cmd@OtherBot", Some("MyBot")));
+    assert_eq!(None, Command::parse("/cmd@MyBot", None));
+    assert_eq!(None, Command::parse("cmd", Some("MyBot")));
+    assert_eq!(None, Command::parse("/ cmd", Some("MyBot")));
+  }
+
+  #[test]
+  fn test_message_flags() {
+    let message = serde_json::from_str::<teloxide::types::Message>(
+      r#"{
+        "message_id": 1,
+        "date": 1,
+        "chat": {"id": 5, "type": "supergroup", "title": "foo"},
+        "from": {"id": 6, "is_bot": false, "first_name": "bar"},
+        "via_bot": {"id": 7, "is_bot": true, "first_name": "baz", "username": "baz_bot"},
+        "has_protected_content": true,
+        "text": "qux"
+      }"#,
+    )
+    .unwrap();
+    assert_eq!(
+      MessageFlags {
+        is_forwarded: false,
+        is_automatic_forward: false,
+        has_protected_content: true,
+        via_bot: Some("baz_bot".to_owned()),
+      },
+      Message::from(&message).unwrap().flags
+    );
+  }
+
+  #[test]
+  fn test_business_message() {
+    let message = serde_json::from_str::<teloxide::types::Message>(
+      r#"{
+        "message_id": 1,
+        "date": 1,
+        "chat": {"id": 5, "type": "private", "first_name": "foo"},
+        "from": {"id": 5, "is_bot": false, "first_name": "foo"},
+        "business_connection_id": "bar",
+        "text": "baz"
+      }"#,
+    )
+    .unwrap();
+    let message = Message::from(&message).unwrap();
+    assert_eq!(Some("bar"), message.business_connection_id.as_deref());
+    assert_eq!(
+      Some(UpdateFilter::EditedBusinessMessage),
+      UpdateFilter::of(&UpdateKind::Message {
+        message,
+        edit: true
+      })
+    );
+  }
+
+  #[test]
+  fn test_chat_member_service_messages() {
+    let message = |fields: &str| {
+      let message = serde_json::from_str::<teloxide::types::Message>(&format!(
+        r#"{{
+          "message_id": 1,
+          "date": 1,
+          "chat": {{"id": -5, "type": "group", "title": "foo"}},
+          "from": {{"id": 6, "is_bot": false, "first_name": "bar"}},
+          {}
+        }}"#,
+        fields
+      ))
+      .unwrap();
+      Message::from(&message).unwrap()
+    };
+    let joined = message(
+      r#""new_chat_members": [
+        {"id": 6, "is_bot": false, "first_name": "bar"},
+        {"id": 7, "is_bot": false, "first_name": "baz"}
+      ]"#,
+    );
+    assert!(joined.is_empty());
+    assert_eq!(
+      vec![ChatID(6), ChatID(7)],
+      joined
+        .new_chat_members
+        .iter()
+        .map(|user| user.id)
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(None, joined.left_chat_member);
+    let left = message(r#""left_chat_member": {"id": 7, "is_bot": false, "first_name": "baz"}"#);
+    assert_eq!(Some(ChatID(7)), left.left_chat_member.map(|user| user.id));
+    assert!(left.new_chat_members.is_empty());
+  }
+
+  #[test]
+  fn test_shipping_query() {
+    let shipping_query = serde_json::from_str::<teloxide::types::ShippingQuery>(
+      r#"{
+        "id": "1",
+        "from": {"id": 5, "is_bot": false, "first_name": "foo"},
+        "invoice_payload": "bar",
+        "shipping_address": {
+          "country_code": "DE",
+          "state": "",
+          "city": "Berlin",
+          "street_line1": "baz 1",
+          "street_line2": "",
+          "post_code": "10115"
+        }
+      }"#,
+    )
+    .unwrap();
+    let shipping_query = ShippingQuery::from(&shipping_query);
+    assert_eq!("1", shipping_query.id);
+    assert_eq!("bar", shipping_query.invoice_payload);
+    assert_eq!("DE", shipping_query.shipping_address.country_code);
+    assert_eq!("Berlin", shipping_query.shipping_address.city);
+    assert_eq!(
+      Some(UpdateFilter::ShippingQuery),
+      UpdateFilter::of(&UpdateKind::ShippingQuery(shipping_query))
+    );
+  }
+
+  #[test]
+  fn test_photo_sizes() {
+    let photo_sizes = serde_json::from_str::<Vec<teloxide::types::PhotoSize>>(
+      r#"[
+        {"file_id": "big", "file_unique_id": "1", "width": 640, "height": 640, "file_size": 3},
+        {"file_id": "small", "file_unique_id": "2", "width": 160, "height": 160}
+      ]"#,
+    )
+    .unwrap();
+    let photo_sizes = PhotoSizes::from(&photo_sizes);
+    assert_eq!("small", photo_sizes.sizes[0].file_id);
+    assert_eq!(None, photo_sizes.sizes[0].file_size);
+    let largest = photo_sizes.largest().unwrap();
+    assert_eq!(
+      ("big", 640, Some(3)),
+      (largest.file_id.as_str(), largest.width, largest.file_size)
+    );
+  }
+
+  #[test]
+  fn test_chat_join_request() {
+    let chat_join_request = serde_json::from_str::<teloxide::types::ChatJoinRequest>(
+      r#"{
+        "chat": {"id": -5, "type": "supergroup", "title": "foo"},
+        "from": {"id": 6, "is_bot": false, "first_name": "bar"},
+        "user_chat_id": 6,
+        "date": 1,
+        "bio": "baz"
+      }"#,
+    )
+    .unwrap();
+    let chat_join_request = ChatJoinRequest::from(&chat_join_request).unwrap();
+    assert_eq!(ChatID(-5), chat_join_request.chat_id);
+    assert_eq!(ChatID(6), chat_join_request.user_id);
+    assert_eq!(Some("baz"), chat_join_request.bio.as_deref());
+  }
+
+  #[test]
+  fn test_split_args() {
+    let split = |args: &str| {
+      Command {
+        name: "cmd".to_owned(),
+        args: args.to_owned(),
+      }
+      .split_args()
+    };
+    assert_eq!(Vec::<String>::new(), split(""));
+    assert_eq!(vec!["foo", "bar"], split("foo  bar \n"));
+    assert_eq!(vec!["two words", "tag"], split("\"two words\" tag"));
+    assert_eq!(vec!["say \"hi\"", ""], split(r#"say\ \"hi\" """#));
+    assert_eq!(vec!["a\\b", "c d"], split("a\\\\b \"c d"));
+  }
+
+  #[test]
+  fn test_count_words() {
+    assert_eq!(0, count_words(""));
+    assert_eq!(0, count_words("  \n - "));
+    assert_eq!(3, count_words("foo  bar\n\nbuzz"));
+    assert_eq!(2, count_words("don't stop!"));
+    assert_eq!(4, count_words("你好，世界"));
+    assert_eq!(5, count_words("hello 世界 foo-bar ok"));
+  }
+
+  #[test]
+  fn test_user_chat_id() {
+    assert_eq!(Some(ChatID(42)), user_chat_id(UserId(42)));
+    assert_eq!(Some(ChatID(1 << 52)), user_chat_id(UserId(1 << 52)));
+    assert_eq!(
+      Some(ChatID(i64::MAX)),
+      user_chat_id(UserId(i64::MAX as u64))
+    );
+    assert_eq!(None, user_chat_id(UserId(i64::MAX as u64 + 1)));
+    assert_eq!(None, user_chat_id(UserId(u64::MAX)));
+
+    let user = serde_json::from_str::<teloxide::types::User>(
+      r#"{"id": 18446744073709551615, "is_bot": false, "first_name": "foo"}"#,
+    )
+    .unwrap();
+    assert_eq!(None, User::from(&user));
+  }
+
+  #[test]
+  fn test_quote() {
+    let quote = Quote::from(&TextQuote {
+      text: "foo bar".to_owned(),
+      entities: vec![MessageEntity::bold(4, 3)],
+      position: 2,
+      is_manual: true,
+    });
+    assert_eq!("foo bar", quote.text);
+    assert_eq!(
+      vec![
+        Component::from("foo "),
+        Component::from("bar").decorate(Decoration::Bold)
+      ],
+      quote.components
+    );
+    assert_eq!(2, quote.position);
+  }
+
+  #[test]
+  fn test_parse_mentions() {
+    let text = "hey 🦀 @steppy and Bob";
+    let user = teloxide::types::User {
+      id: UserId(42),
+      is_bot: false,
+      first_name: "Bob".to_owned(),
+      last_name: None,
+      username: None,
+      language_code: None,
+      is_premium: false,
+      added_to_attachment_menu: false,
+    };
+    let entities = [
+      MessageEntity::new(MessageEntityKind::Mention, 7, 7),
+      MessageEntity::text_mention(user, 19, 3),
+    ];
+    let mentions = parse_mentions(text, &entities);
+    assert_eq!(Mention::Username("steppy".to_owned()), mentions[0]);
+    assert!(matches!(
+      &mentions[1],
+      Mention::User { user, text, .. } if user.id == ChatID(42) && text == "Bob"
+    ));
+    assert!(matches!(
+      &mentions[1],
+      Mention::User { range, .. } if range == &(21..24)
+    ));
+  }
+}